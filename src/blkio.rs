@@ -8,16 +8,20 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/blkio-controller.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/blkio-controller.txt)
-use std::io::Write;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::{read_string_from, read_u64_from};
+use crate::util::{read_string_from, read_u64_from, write_to};
+use crate::{parse_pressure, Pressure};
 use crate::{
     BlkIoResources, ControllIdentifier, ControllerInternal, Controllers, CustomizedAttribute,
-    Resources, Subsystem,
+    DeviceNumber, MaxValue, Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `blkio` subsystem of a Cgroup.
@@ -63,6 +67,27 @@ pub struct IoService {
     pub total: u64,
 }
 
+/// Per-device activity from the control group, as reported by the `blkio.throttle.*` files of
+/// cgroup v1.
+#[derive(Eq, PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoServiceStat {
+    /// The device.
+    pub device: DeviceNumber,
+    /// How many items were read from the device.
+    pub read: u64,
+    /// How many items were written to the device.
+    pub write: u64,
+    /// How many items were synchronously transferred.
+    pub sync: u64,
+    /// How many items were asynchronously transferred.
+    pub async_: u64,
+    /// How many items were discarded, reported by Linux 4.19 and newer.
+    pub discard: u64,
+    /// Total number of items transferred.
+    pub total: u64,
+}
+
 #[derive(Eq, PartialEq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Per-device activity from the control group.
@@ -87,87 +112,233 @@ pub struct IoStat {
 }
 
 fn parse_io_service(s: String) -> Result<Vec<IoService>> {
-    s.lines()
-        .filter(|x| x.split_whitespace().count() == 3)
+    let lines = s
+        .lines()
+        .map(|x| x.split_whitespace().collect::<Vec<_>>())
+        .filter(|x| x.len() == 3)
         .map(|x| {
-            let mut spl = x.split_whitespace();
-            (spl.next().unwrap(), spl.next().unwrap(), spl.next().unwrap())
+            let (major, minor) = x[0].split_once(':').ok_or_else(|| Error::new(ParseError))?;
+            Ok((major, minor, x[1], x[2]))
         })
-        .map(|(a, b, c)| {
-            let mut spl = a.split(':');
-            (spl.next().unwrap(), spl.next().unwrap(), b, c)
-        })
-        .collect::<Vec<_>>()
+        .collect::<Result<Vec<_>>>()?;
+    let parse_u64 = |x: &str| {
+        x.parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e))
+    };
+    let parse_i16 = |x: &str| {
+        x.parse::<i16>()
+            .map_err(|e| Error::with_cause(ParseError, e))
+    };
+
+    lines
         .chunks(5)
         .map(|x| {
-            match x {
-                [(major, minor, "Read", read_val), (_, _, "Write", write_val),
-                   (_, _, "Sync", sync_val), (_, _, "Async", async_val),
-                   (_, _, "Total", total_val)] =>
-                    Some(IoService {
-                        major: major.parse::<i16>().unwrap(),
-                        minor: minor.parse::<i16>().unwrap(),
-                        read: read_val.parse::<u64>().unwrap(),
-                        write: write_val.parse::<u64>().unwrap(),
-                        sync: sync_val.parse::<u64>().unwrap(),
-                        r#async: async_val.parse::<u64>().unwrap(),
-                        total: total_val.parse::<u64>().unwrap(),
-                    }),
-               _ => None,
-            }
-        })
-        .fold(Ok(Vec::new()), |acc, x| {
-            if acc.is_err() || x.is_none() {
-                Err(Error::new(ParseError))
-            } else {
-                let mut acc = acc.unwrap();
-                acc.push(x.unwrap());
-                Ok(acc)
+            let ops = x.iter().map(|(_, _, op, _)| *op).collect::<Vec<_>>();
+            if ops != ["Read", "Write", "Sync", "Async", "Total"] {
+                return Err(Error::new(ParseError));
             }
+            let (major, minor, _, _) = x[0];
+            Ok(IoService {
+                major: parse_i16(major)?,
+                minor: parse_i16(minor)?,
+                read: parse_u64(x[0].3)?,
+                write: parse_u64(x[1].3)?,
+                sync: parse_u64(x[2].3)?,
+                r#async: parse_u64(x[3].3)?,
+                total: parse_u64(x[4].3)?,
+            })
         })
+        .collect()
 }
 
-fn get_value(s: &str) -> String {
-    let arr = s.split(':').collect::<Vec<&str>>();
-    if arr.len() != 2 {
-        return "0".to_string();
+/// Parses the `blkio.throttle.io_service_bytes` and `blkio.throttle.io_serviced` format, i.e.
+/// `8:0 Read 1024` lines, consecutive lines of the same device making up one entry.
+///
+/// Operations this crate does not know about are skipped, and so is the `Total` line summing up
+/// all devices at the end of the file.
+fn parse_io_service_stat(s: &str) -> Result<Vec<IoServiceStat>> {
+    let mut stats: Vec<IoServiceStat> = Vec::new();
+    for line in s.lines() {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let (dev, op, value) = match fields.as_slice() {
+            [dev, op, value] => (dev.parse::<DeviceNumber>()?, *op, *value),
+            [] | ["Total", _] => continue,
+            _ => return Err(Error::new(ParseError)),
+        };
+        let value = value
+            .parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
+
+        if stats.last().map(|stat| stat.device) != Some(dev) {
+            stats.push(IoServiceStat {
+                device: dev,
+                read: 0,
+                write: 0,
+                sync: 0,
+                async_: 0,
+                discard: 0,
+                total: 0,
+            });
+        }
+        let stat = stats.last_mut().unwrap();
+        match op {
+            "Read" => stat.read = value,
+            "Write" => stat.write = value,
+            "Sync" => stat.sync = value,
+            "Async" => stat.async_ = value,
+            "Discard" => stat.discard = value,
+            "Total" => stat.total = value,
+            _ => {}
+        }
     }
-    arr[1].to_string()
+    Ok(stats)
 }
 
-fn parse_io_stat(s: String) -> Vec<IoStat> {
-    // line:
-    // 8:0 rbytes=180224 wbytes=0 rios=3 wios=0 dbytes=0 dios=0
-    s.lines()
-        .filter(|x| x.split_whitespace().count() == 7)
-        .map(|x| {
-            let arr = x.split_whitespace().collect::<Vec<&str>>();
-            let device = arr[0].split(':').collect::<Vec<&str>>();
-            let (major, minor) = (device[0], device[1]);
+/// Parses `io.stat` into a map keyed by device. Lines with extra keys, such as the `cost.*` ones
+/// reported with the `io.cost` controller, are kept; missing keys are reported as 0.
+fn parse_io_stat_map(s: &str) -> Result<HashMap<DeviceNumber, IoStat>> {
+    let mut stats = HashMap::new();
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let dev = match fields.next() {
+            Some(dev) => dev.parse::<DeviceNumber>()?,
+            None => continue,
+        };
+        let mut stat = IoStat {
+            major: i16::try_from(dev.major).map_err(|e| Error::with_cause(ParseError, e))?,
+            minor: i16::try_from(dev.minor).map_err(|e| Error::with_cause(ParseError, e))?,
+            rbytes: 0,
+            wbytes: 0,
+            rios: 0,
+            wios: 0,
+            dbytes: 0,
+            dios: 0,
+        };
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::new(ParseError))?;
+            let slot = match key {
+                "rbytes" => &mut stat.rbytes,
+                "wbytes" => &mut stat.wbytes,
+                "rios" => &mut stat.rios,
+                "wios" => &mut stat.wios,
+                "dbytes" => &mut stat.dbytes,
+                "dios" => &mut stat.dios,
+                _ => continue,
+            };
+            *slot = value
+                .parse::<u64>()
+                .map_err(|e| Error::with_cause(ParseError, e))?;
+        }
+        stats.insert(dev, stat);
+    }
+    Ok(stats)
+}
 
-            IoStat {
-                major: major.parse::<i16>().unwrap(),
-                minor: minor.parse::<i16>().unwrap(),
-                rbytes: get_value(arr[1]).parse::<u64>().unwrap(),
-                wbytes: get_value(arr[2]).parse::<u64>().unwrap(),
-                rios: get_value(arr[3]).parse::<u64>().unwrap(),
-                wios: get_value(arr[4]).parse::<u64>().unwrap(),
-                dbytes: get_value(arr[5]).parse::<u64>().unwrap(),
-                dios: get_value(arr[6]).parse::<u64>().unwrap(),
+/// The limits of a block device in `io.max`. A limit set to `None` is written as `max`, i.e.
+/// unlimited.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoMax {
+    /// Bytes per second that can be read from the device.
+    pub rbps: Option<u64>,
+    /// Bytes per second that can be written to the device.
+    pub wbps: Option<u64>,
+    /// Read operations per second.
+    pub riops: Option<u64>,
+    /// Write operations per second.
+    pub wiops: Option<u64>,
+}
+
+impl fmt::Display for IoMax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = |v: Option<u64>| v.map_or_else(|| "max".to_string(), |v| v.to_string());
+        write!(
+            f,
+            "rbps={} wbps={} riops={} wiops={}",
+            limit(self.rbps),
+            limit(self.wbps),
+            limit(self.riops),
+            limit(self.wiops)
+        )
+    }
+}
+
+fn parse_io_max(s: &str) -> Result<HashMap<DeviceNumber, IoMax>> {
+    let mut limits = HashMap::new();
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let dev = match fields.next() {
+            Some(dev) => dev.parse::<DeviceNumber>()?,
+            None => continue,
+        };
+        let mut max = IoMax::default();
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::new(ParseError))?;
+            let value = match value.parse::<MaxValue>()? {
+                MaxValue::Max => None,
+                MaxValue::Value(v) => Some(v as u64),
+            };
+            match key {
+                "rbps" => max.rbps = value,
+                "wbps" => max.wbps = value,
+                "riops" => max.riops = value,
+                "wiops" => max.wiops = value,
+                _ => {}
             }
-        })
-        .collect::<Vec<IoStat>>()
+        }
+        limits.insert(dev, max);
+    }
+    Ok(limits)
+}
+
+/// The proportional weights found in `io.weight`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoWeight {
+    /// The weight used for the devices without a weight of their own.
+    pub default: u16,
+    /// The per-device weights overriding `default`.
+    pub devices: HashMap<DeviceNumber, u16>,
+}
+
+/// The range accepted by the kernel for `io.weight`.
+const IO_WEIGHT_RANGE: std::ops::RangeInclusive<u16> = 1..=10000;
+
+fn parse_io_weight(s: &str) -> Result<IoWeight> {
+    let mut weight = IoWeight::default();
+    for line in s.lines() {
+        let (key, value) = match line.split_once(' ') {
+            Some((key, value)) => (key, value.trim()),
+            None if line.trim().is_empty() => continue,
+            None => return Err(Error::new(ParseError)),
+        };
+        let value = value
+            .parse::<u16>()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
+        if key == "default" {
+            weight.default = value;
+        } else {
+            weight.devices.insert(key.parse::<DeviceNumber>()?, value);
+        }
+    }
+    Ok(weight)
 }
 
 fn parse_io_service_total(s: String) -> Result<u64> {
-    s.lines()
-        .filter(|x| x.split_whitespace().count() == 2)
-        .fold(Err(Error::new(ParseError)), |_, x| {
-            match x.split_whitespace().collect::<Vec<_>>().as_slice() {
-                ["Total", val] => val.parse::<u64>().map_err(|_| Error::new(ParseError)),
-                _ => Err(Error::new(ParseError)),
-            }
-        })
+    let line = s
+        .lines()
+        .rfind(|x| x.split_whitespace().count() == 2)
+        .ok_or_else(|| Error::new(ParseError))?;
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["Total", val] => val
+            .parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e)),
+        _ => Err(Error::new(ParseError)),
+    }
 }
 
 fn parse_blkio_data(s: String) -> Result<Vec<BlkIoData>> {
@@ -185,23 +356,25 @@ fn parse_blkio_data(s: String) -> Result<Vec<BlkIoData>> {
 
     let mut res = Vec::new();
 
-    let err = r.iter().try_for_each(|x| match x {
+    r.iter().try_for_each(|x| match x {
         [major, minor, data] => {
             res.push(BlkIoData {
-                major: major.parse::<i16>().unwrap(),
-                minor: minor.parse::<i16>().unwrap(),
-                data: data.parse::<u64>().unwrap(),
+                major: major
+                    .parse::<i16>()
+                    .map_err(|e| Error::with_cause(ParseError, e))?,
+                minor: minor
+                    .parse::<i16>()
+                    .map_err(|e| Error::with_cause(ParseError, e))?,
+                data: data
+                    .parse::<u64>()
+                    .map_err(|e| Error::with_cause(ParseError, e))?,
             });
             Ok(())
         }
         _ => Err(Error::new(ParseError)),
-    });
+    })?;
 
-    if err.is_err() {
-        Err(Error::new(ParseError))
-    } else {
-        Ok(res)
-    }
+    Ok(res)
 }
 
 /// Current state and statistics about how throttled are the block devices when accessed from the
@@ -348,43 +521,51 @@ impl ControllerInternal for BlkIoController {
         let res: &BlkIoResources = &res.blkio;
 
         if let Some(weight) = res.weight {
-            let _ = self.set_weight(weight as u64);
+            self.set_weight(weight as u64)?;
         }
         if let Some(leaf_weight) = res.leaf_weight {
-            let _ = self.set_leaf_weight(leaf_weight as u64);
+            self.set_leaf_weight(leaf_weight as u64)?;
         }
 
         for dev in &res.weight_device {
             if let Some(weight) = dev.weight {
-                let _ = self.set_weight_for_device(dev.major, dev.minor, weight as u64);
+                self.set_weight_for_device(DeviceNumber::new(dev.major, dev.minor), weight as u64)?;
             }
             if let Some(leaf_weight) = dev.leaf_weight {
-                let _ = self.set_leaf_weight_for_device(dev.major, dev.minor, leaf_weight as u64);
+                self.set_leaf_weight_for_device(
+                    DeviceNumber::new(dev.major, dev.minor),
+                    leaf_weight as u64,
+                )?;
             }
         }
 
         for dev in &res.throttle_read_bps_device {
-            let _ = self.throttle_read_bps_for_device(dev.major, dev.minor, dev.rate);
+            self.throttle_read_bps_for_device(DeviceNumber::new(dev.major, dev.minor), dev.rate)?;
         }
 
         for dev in &res.throttle_write_bps_device {
-            let _ = self.throttle_write_bps_for_device(dev.major, dev.minor, dev.rate);
+            self.throttle_write_bps_for_device(DeviceNumber::new(dev.major, dev.minor), dev.rate)?;
         }
 
         for dev in &res.throttle_read_iops_device {
-            let _ = self.throttle_read_iops_for_device(dev.major, dev.minor, dev.rate);
+            self.throttle_read_iops_for_device(DeviceNumber::new(dev.major, dev.minor), dev.rate)?;
         }
 
         for dev in &res.throttle_write_iops_device {
-            let _ = self.throttle_write_iops_for_device(dev.major, dev.minor, dev.rate);
+            self.throttle_write_iops_for_device(DeviceNumber::new(dev.major, dev.minor), dev.rate)?;
         }
 
-        res.attrs.iter().for_each(|(k, v)| {
-            let _ = self.set(k, v);
-        });
+        res.attrs.iter().try_for_each(|(k, v)| self.set(k, v))?;
 
         Ok(())
     }
+
+    fn reset_stats(&self) -> Result<()> {
+        if self.v2 {
+            return Err(Error::new(NotSupported));
+        }
+        BlkIoController::reset_stats(self)
+    }
 }
 
 impl ControllIdentifier for BlkIoController {
@@ -395,15 +576,9 @@ impl ControllIdentifier for BlkIoController {
 
 impl<'a> From<&'a Subsystem> for &'a BlkIoController {
     fn from(sub: &'a Subsystem) -> &'a BlkIoController {
-        unsafe {
-            match sub {
-                Subsystem::BlkIo(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::BlkIo(c) => c,
+            _ => panic!("Subsystem is not a BlkIoController"),
         }
     }
 }
@@ -418,17 +593,156 @@ impl BlkIoController {
         }
     }
 
+    /// Constructs a `BlkIoController` for the root of the `blkio` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::BlkIo)?;
+        Ok(Self::new(root, v2))
+    }
+
     fn blkio_v2(&self) -> BlkIo {
         BlkIo {
             io_stat: self
                 .open_path("io.stat", false)
                 .and_then(read_string_from)
-                .map(parse_io_stat)
+                .and_then(|s| parse_io_stat_map(&s))
+                .map(|stats| {
+                    let mut stats = stats.into_iter().collect::<Vec<_>>();
+                    stats.sort_by_key(|(dev, _)| *dev);
+                    stats.into_iter().map(|(_, stat)| stat).collect()
+                })
                 .unwrap_or_default(),
             ..Default::default()
         }
     }
 
+    /// Returns the per-device statistics found in `io.stat`.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn io_stat(&self) -> Result<HashMap<DeviceNumber, IoStat>> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path("io.stat", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_io_stat_map(&s))
+    }
+
+    /// Returns the bytes transferred from and to each device, as seen by the throttling policy.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn throttle_io_service_bytes(&self) -> Result<Vec<IoServiceStat>> {
+        self.read_io_service_stat("blkio.throttle.io_service_bytes")
+    }
+
+    /// Returns the I/O operations performed on each device, as seen by the throttling policy.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn throttle_io_serviced(&self) -> Result<Vec<IoServiceStat>> {
+        self.read_io_service_stat("blkio.throttle.io_serviced")
+    }
+
+    fn read_io_service_stat(&self, file_name: &str) -> Result<Vec<IoServiceStat>> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path(file_name, false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_io_service_stat(&s))
+    }
+
+    /// Returns the number of bytes transferred between the control group's tasks and all block
+    /// devices.
+    ///
+    /// On cgroup v1 this is the total of `blkio.throttle.io_service_bytes`, on cgroup v2 the sum
+    /// of `rbytes` and `wbytes` in `io.stat`.
+    pub fn io_service_bytes_total(&self) -> Result<u64> {
+        if self.v2 {
+            return self
+                .io_stat()
+                .map(|stats| stats.values().map(|s| s.rbytes + s.wbytes).sum());
+        }
+        self.open_path("blkio.throttle.io_service_bytes", false)
+            .and_then(read_string_from)
+            .and_then(parse_io_service_total)
+    }
+
+    /// Returns the limits configured in `io.max`, keyed by device.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn io_max(&self) -> Result<HashMap<DeviceNumber, IoMax>> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path("io.max", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_io_max(&s))
+    }
+
+    /// Set all the limits of the block device `dev` in `io.max` at once, e.g.
+    /// `8:0 rbps=1048576 wbps=max riops=max wiops=120`.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn set_io_max(&self, dev: DeviceNumber, limits: IoMax) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        let content = format!("{} {}", dev, limits);
+        self.open_path("io.max", true).and_then(|mut file| {
+            write_to(&mut file, &content)
+                .map_err(|e| Error::with_cause(WriteFailed("io.max".to_string(), content), e))
+        })
+    }
+
+    /// Returns the default and per-device proportional weights found in `io.weight`.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn io_weight(&self) -> Result<IoWeight> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path("io.weight", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_io_weight(&s))
+    }
+
+    /// Set the default proportional weight in `io.weight`, which must be within 1-10000.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn set_io_weight(&self, default: u16) -> Result<()> {
+        self.write_io_weight(format!("default {}", default), default)
+    }
+
+    /// Set the proportional weight of the block device `dev` in `io.weight`, which must be
+    /// within 1-10000.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn set_io_device_weight(&self, dev: DeviceNumber, weight: u16) -> Result<()> {
+        self.write_io_weight(format!("{} {}", dev, weight), weight)
+    }
+
+    fn write_io_weight(&self, content: String, weight: u16) -> Result<()> {
+        if !IO_WEIGHT_RANGE.contains(&weight) {
+            return Err(Error::new(ParseError));
+        }
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path("io.weight", true).and_then(|mut file| {
+            write_to(&mut file, &content)
+                .map_err(|e| Error::with_cause(WriteFailed("io.weight".to_string(), content), e))
+        })
+    }
+
+    /// Returns the I/O pressure stall information found in `io.pressure`.
+    ///
+    /// This is only available on cgroup v2 with a kernel built with `CONFIG_PSI`.
+    pub fn pressure(&self) -> Result<Pressure> {
+        self.open_path("io.pressure", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pressure(&s))
+    }
+
     /// Gathers statistics about and reports the state of the block devices used by the control
     /// group's tasks.
     pub fn blkio(&self) -> BlkIo {
@@ -665,7 +979,7 @@ impl BlkIoController {
     pub fn set_leaf_weight(&self, w: u64) -> Result<()> {
         self.open_path("blkio.leaf_weight", true)
             .and_then(|mut file| {
-                file.write_all(w.to_string().as_ref()).map_err(|e| {
+                write_to(&mut file, &w.to_string()).map_err(|e| {
                     Error::with_cause(
                         WriteFailed("blkio.leaf_weight".to_string(), w.to_string()),
                         e,
@@ -675,19 +989,18 @@ impl BlkIoController {
     }
 
     /// Same as `set_leaf_weight()`, but settable per each block device.
-    pub fn set_leaf_weight_for_device(&self, major: u64, minor: u64, weight: u64) -> Result<()> {
+    pub fn set_leaf_weight_for_device(&self, dev: DeviceNumber, weight: u64) -> Result<()> {
         self.open_path("blkio.leaf_weight_device", true)
             .and_then(|mut file| {
-                file.write_all(format!("{}:{} {}", major, minor, weight).as_ref())
-                    .map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed(
-                                "blkio.leaf_weight_device".to_string(),
-                                format!("{}:{} {}", major, minor, weight),
-                            ),
-                            e,
-                        )
-                    })
+                write_to(&mut file, &format!("{} {}", dev, weight)).map_err(|e| {
+                    Error::with_cause(
+                        WriteFailed(
+                            "blkio.leaf_weight_device".to_string(),
+                            format!("{} {}", dev, weight),
+                        ),
+                        e,
+                    )
+                })
             })
     }
 
@@ -695,7 +1008,7 @@ impl BlkIoController {
     pub fn reset_stats(&self) -> Result<()> {
         self.open_path("blkio.reset_stats", true)
             .and_then(|mut file| {
-                file.write_all("1".to_string().as_ref()).map_err(|e| {
+                write_to(&mut file, "1").map_err(|e| {
                     Error::with_cause(
                         WriteFailed("blkio.reset_stats".to_string(), "1".to_string()),
                         e,
@@ -705,63 +1018,63 @@ impl BlkIoController {
     }
 
     /// Throttle the bytes per second rate of read operation affecting the block device
-    /// `major:minor` to `bps`.
-    pub fn throttle_read_bps_for_device(&self, major: u64, minor: u64, bps: u64) -> Result<()> {
+    /// `dev` to `bps`.
+    pub fn throttle_read_bps_for_device(&self, dev: DeviceNumber, bps: u64) -> Result<()> {
         let mut file_name = "blkio.throttle.read_bps_device";
-        let mut content = format!("{}:{} {}", major, minor, bps);
+        let mut content = format!("{} {}", dev, bps);
         if self.v2 {
             file_name = "io.max";
-            content = format!("{}:{} rbps={}", major, minor, bps);
+            content = format!("{} rbps={}", dev, bps);
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
+            write_to(&mut file, &content).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
             })
         })
     }
 
     /// Throttle the I/O operations per second rate of read operation affecting the block device
-    /// `major:minor` to `bps`.
-    pub fn throttle_read_iops_for_device(&self, major: u64, minor: u64, iops: u64) -> Result<()> {
+    /// `dev` to `iops`.
+    pub fn throttle_read_iops_for_device(&self, dev: DeviceNumber, iops: u64) -> Result<()> {
         let mut file_name = "blkio.throttle.read_iops_device";
-        let mut content = format!("{}:{} {}", major, minor, iops);
+        let mut content = format!("{} {}", dev, iops);
         if self.v2 {
             file_name = "io.max";
-            content = format!("{}:{} riops={}", major, minor, iops);
+            content = format!("{} riops={}", dev, iops);
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
+            write_to(&mut file, &content).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
             })
         })
     }
     /// Throttle the bytes per second rate of write operation affecting the block device
-    /// `major:minor` to `bps`.
-    pub fn throttle_write_bps_for_device(&self, major: u64, minor: u64, bps: u64) -> Result<()> {
+    /// `dev` to `bps`.
+    pub fn throttle_write_bps_for_device(&self, dev: DeviceNumber, bps: u64) -> Result<()> {
         let mut file_name = "blkio.throttle.write_bps_device";
-        let mut content = format!("{}:{} {}", major, minor, bps);
+        let mut content = format!("{} {}", dev, bps);
         if self.v2 {
             file_name = "io.max";
-            content = format!("{}:{} wbps={}", major, minor, bps);
+            content = format!("{} wbps={}", dev, bps);
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
+            write_to(&mut file, &content).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
             })
         })
     }
 
     /// Throttle the I/O operations per second rate of write operation affecting the block device
-    /// `major:minor` to `bps`.
-    pub fn throttle_write_iops_for_device(&self, major: u64, minor: u64, iops: u64) -> Result<()> {
+    /// `dev` to `iops`.
+    pub fn throttle_write_iops_for_device(&self, dev: DeviceNumber, iops: u64) -> Result<()> {
         let mut file_name = "blkio.throttle.write_iops_device";
-        let mut content = format!("{}:{} {}", major, minor, iops);
+        let mut content = format!("{} {}", dev, iops);
         if self.v2 {
             file_name = "io.max";
-            content = format!("{}:{} wiops={}", major, minor, iops);
+            content = format!("{} wiops={}", dev, iops);
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref()).map_err(|e| {
+            write_to(&mut file, &content).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), content.to_string()), e)
             })
         })
@@ -775,14 +1088,14 @@ impl BlkIoController {
             file_name = "io.bfq.weight";
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(w.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &w.to_string()).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), w.to_string()), e)
             })
         })
     }
 
     /// Same as `set_weight()`, but settable per each block device.
-    pub fn set_weight_for_device(&self, major: u64, minor: u64, weight: u64) -> Result<()> {
+    pub fn set_weight_for_device(&self, dev: DeviceNumber, weight: u64) -> Result<()> {
         let mut file_name = "blkio.weight_device";
         if self.v2 {
             // Attation: there is no weight for device in runc
@@ -791,16 +1104,12 @@ impl BlkIoController {
             file_name = "io.bfq.weight";
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(format!("{}:{} {}", major, minor, weight).as_ref())
-                .map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed(
-                            file_name.to_string(),
-                            format!("{}:{} {}", major, minor, weight),
-                        ),
-                        e,
-                    )
-                })
+            write_to(&mut file, &format!("{} {}", dev, weight)).map_err(|e| {
+                Error::with_cause(
+                    WriteFailed(file_name.to_string(), format!("{} {}", dev, weight)),
+                    e,
+                )
+            })
         })
     }
 }
@@ -809,8 +1118,11 @@ impl CustomizedAttribute for BlkIoController {}
 #[cfg(test)]
 mod test {
     use crate::blkio::{parse_blkio_data, BlkIoData};
+    use crate::blkio::{parse_io_max, parse_io_stat_map, parse_io_weight, IoMax, IoStat};
     use crate::blkio::{parse_io_service, parse_io_service_total, IoService};
+    use crate::blkio::{parse_io_service_stat, IoServiceStat};
     use crate::error::*;
+    use crate::DeviceNumber;
 
     static TEST_VALUE: &str = "\
 8:32 Read 4280320
@@ -917,6 +1229,62 @@ Total 61823067136
         );
         let err = parse_io_service(TEST_WRONG_VALUE.to_string()).unwrap_err();
         assert_eq!(err.kind(), &ErrorKind::ParseError,);
+
+        let bad_device = TEST_VALUE.replacen("8:32 Read", "8 Read", 1);
+        let bad_value = TEST_VALUE.replacen("8:32 Write 0", "8:32 Write x", 1);
+        let bad_major = TEST_VALUE.replacen("8:32 Read", "a:32 Read", 1);
+        for case in [bad_device, bad_value, bad_major] {
+            let err = parse_io_service(case.clone()).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_parse_io_service_stat() {
+        let stats = parse_io_service_stat(
+            "\
+8:0 Read 1024
+8:0 Write 2048
+8:0 Sync 512
+8:0 Async 2560
+8:0 Discard 0
+8:0 Total 3072
+259:1 Read 10
+259:1 Write 20
+259:1 Total 30
+Total 3102
+",
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            vec![
+                IoServiceStat {
+                    device: DeviceNumber::new(8, 0),
+                    read: 1024,
+                    write: 2048,
+                    sync: 512,
+                    async_: 2560,
+                    discard: 0,
+                    total: 3072,
+                },
+                IoServiceStat {
+                    device: DeviceNumber::new(259, 1),
+                    read: 10,
+                    write: 20,
+                    sync: 0,
+                    async_: 0,
+                    discard: 0,
+                    total: 30,
+                },
+            ]
+        );
+
+        assert_eq!(parse_io_service_stat("Total 0\n").unwrap(), vec![]);
+        let err = parse_io_service_stat("8:0 Read x\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+        let err = parse_io_service_stat("8:0 Read\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
     }
 
     #[test]
@@ -946,5 +1314,90 @@ Total 61823067136
                 }
             ]
         );
+
+        for case in ["8:0", "8:0 x", "a:0 1"] {
+            let err = parse_blkio_data(case.to_string()).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_io_weight_serde() {
+        use crate::blkio::IoWeight;
+
+        let weight = parse_io_weight("default 100\n8:0 200\n").unwrap();
+        let json = serde_json::to_string(&weight).unwrap();
+        assert_eq!(json, r#"{"default":100,"devices":{"8:0":200}}"#);
+        assert_eq!(serde_json::from_str::<IoWeight>(&json).unwrap(), weight);
+        let bad = r#"{"default":100,"devices":{"8":200}}"#;
+        assert!(serde_json::from_str::<IoWeight>(bad).is_err());
+    }
+
+    #[test]
+    fn test_parse_io_stat_map() {
+        let stats = parse_io_stat_map(
+            "8:0 rbytes=180224 wbytes=0 rios=3 wios=0 dbytes=0 dios=0\n\
+             259:0 rbytes=4096 wbytes=8192 rios=1 wios=2 cost.vrate=100.00\n",
+        )
+        .unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats[&DeviceNumber::new(8, 0)],
+            IoStat {
+                major: 8,
+                minor: 0,
+                rbytes: 180224,
+                wbytes: 0,
+                rios: 3,
+                wios: 0,
+                dbytes: 0,
+                dios: 0,
+            }
+        );
+        let nvme = &stats[&DeviceNumber::new(259, 0)];
+        assert_eq!((nvme.wbytes, nvme.wios, nvme.dios), (8192, 2, 0));
+
+        // 259:65536 does not fit the i16 fields of IoStat.
+        for case in [
+            "8:0 rbytes",
+            "8 rbytes=1",
+            "8:0 rbytes=x",
+            "259:65536 rbytes=1",
+        ] {
+            let err = parse_io_stat_map(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_io_max_round_trip() {
+        let max = IoMax {
+            rbps: Some(1048576),
+            wiops: Some(120),
+            ..Default::default()
+        };
+        assert_eq!(max.to_string(), "rbps=1048576 wbps=max riops=max wiops=120");
+
+        let limits = parse_io_max(&format!("8:0 {}\n8:16 rbps=max\n", max)).unwrap();
+        assert_eq!(limits[&DeviceNumber::new(8, 0)], max);
+        assert_eq!(limits[&DeviceNumber::new(8, 16)], IoMax::default());
+
+        let err = parse_io_max("8:0 rbps=-1").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_parse_io_weight() {
+        let weight = parse_io_weight("default 100\n8:0 200\n259:0 10000\n").unwrap();
+        assert_eq!(weight.default, 100);
+        assert_eq!(weight.devices.len(), 2);
+        assert_eq!(weight.devices[&DeviceNumber::new(8, 0)], 200);
+        assert_eq!(weight.devices[&DeviceNumber::new(259, 0)], 10000);
+
+        for case in ["default", "default x", "8 200", "8:0 -1"] {
+            let err = parse_io_weight(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
     }
 }
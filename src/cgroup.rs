@@ -6,15 +6,28 @@
 
 //! This module handles cgroup operations. Start here!
 
+use log::*;
+
 use crate::error::ErrorKind::*;
 use crate::error::*;
 
-use crate::{CgroupPid, ControllIdentifier, Controller, Hierarchy, Resources, Subsystem};
+use crate::blkio::BlkIoController;
+use crate::cpu::{CpuController, CpuThrottling};
+use crate::cpuacct::CpuAcctController;
+use crate::freezer::FreezerController;
+use crate::memory::{MemController, MemoryStat};
+use crate::pid::PidController;
+use crate::util::FileWatch;
+use crate::{
+    parse_cgroup_events, CgroupEvents, CgroupPid, ControllIdentifier, Controller, Controllers,
+    Hierarchy, Resources, Subsystem,
+};
 
 use std::collections::HashMap;
 use std::convert::From;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// A control group is the central structure to this crate.
 ///
@@ -33,8 +46,8 @@ pub struct Cgroup {
     /// The list of subsystems that control this cgroup
     subsystems: Vec<Subsystem>,
 
-    /// The hierarchy.
-    hier: Box<dyn Hierarchy>,
+    /// The hierarchy, shared with the handles of its child control groups.
+    hier: Arc<dyn Hierarchy>,
     path: String,
 
     /// List of controllers specifically enabled in the control group.
@@ -45,7 +58,7 @@ impl Clone for Cgroup {
     fn clone(&self) -> Self {
         Cgroup {
             subsystems: self.subsystems.clone(),
-            hier: crate::hierarchies::auto(),
+            hier: self.hier.clone(),
             path: self.path.clone(),
             specified_controllers: None,
         }
@@ -56,7 +69,7 @@ impl Default for Cgroup {
     fn default() -> Self {
         Cgroup {
             subsystems: Vec::new(),
-            hier: crate::hierarchies::auto(),
+            hier: Arc::from(crate::hierarchies::auto()),
             path: "".to_string(),
             specified_controllers: None,
         }
@@ -74,7 +87,10 @@ impl Cgroup {
             create_v2_cgroup(self.hier.root(), &self.path, &self.specified_controllers)
         } else {
             for subsystem in &self.subsystems {
-                subsystem.to_controller().create();
+                let c = subsystem.to_controller();
+                if let Err(e) = c.create() {
+                    warn!("error create_dir: {:?} error: {:?}", c.path(), e);
+                }
             }
             Ok(())
         }
@@ -138,7 +154,7 @@ impl Cgroup {
         Cgroup {
             path: path.to_str().unwrap().to_string(),
             subsystems,
-            hier,
+            hier: Arc::from(hier),
             specified_controllers: None,
         }
     }
@@ -165,7 +181,7 @@ impl Cgroup {
         Cgroup {
             path: path.to_str().unwrap().to_string(),
             subsystems,
-            hier,
+            hier: Arc::from(hier),
             specified_controllers: Some(specified_controllers),
         }
     }
@@ -208,7 +224,7 @@ impl Cgroup {
 
         Cgroup {
             subsystems,
-            hier,
+            hier: Arc::from(hier),
             path: path.to_str().unwrap().to_string(),
             specified_controllers: None,
         }
@@ -253,6 +269,59 @@ impl Cgroup {
         })
     }
 
+    /// Deletes the control group together with all its descendants.
+    ///
+    /// The control group is thawed first if a freezer controller is attached, as frozen tasks
+    /// cannot be migrated. Descendants are then removed depth-first, and the remaining tasks
+    /// moved to the parent control group before the control group itself is removed.
+    pub fn delete_recursive(&self) -> Result<()> {
+        if let Some(freezer) = self.controller_of::<FreezerController>() {
+            if freezer.exists() {
+                freezer.thaw()?;
+            }
+        }
+
+        let mut children = self
+            .subsystems
+            .iter()
+            .filter_map(|sub| fs::read_dir(sub.to_controller().path()).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .map(|entry| entry.file_name())
+            .collect::<Vec<_>>();
+        children.sort();
+        children.dedup();
+
+        for name in children {
+            let child = Cgroup {
+                subsystems: self
+                    .subsystems
+                    .iter()
+                    .map(|sub| sub.clone().enter(Path::new(&name)))
+                    .collect(),
+                hier: self.hier.clone(),
+                path: Path::new(&self.path)
+                    .join(&name)
+                    .to_string_lossy()
+                    .to_string(),
+                specified_controllers: self.specified_controllers.clone(),
+            };
+            child.delete_recursive()?;
+        }
+
+        for pid in self.procs() {
+            match self.move_task_to_parent_by_tgid(pid) {
+                Ok(()) => {}
+                // The process exited in the meantime.
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.delete()
+    }
+
     /// Apply a set of resource limits to the control group.
     pub fn apply(&self, res: &Resources) -> Result<()> {
         self.subsystems
@@ -260,6 +329,31 @@ impl Cgroup {
             .try_fold((), |_, e| e.to_controller().apply(res))
     }
 
+    /// Reads the current limits of the control group back into a `Resources`, e.g. to save and
+    /// later re-`apply()` them, or to detect drift from what was applied.
+    ///
+    /// The CPU, cpuset, memory, pids, devices, net_cls and hugetlb settings are read from the
+    /// attached controllers. Everything else, including the fields of controllers that are not
+    /// attached and values that cannot be read, is left at its default. Values are the ones the
+    /// kernel reports, e.g. an unlimited cgroup v1 memory limit reads as a large number rather
+    /// than `-1`.
+    pub fn snapshot(&self) -> Resources {
+        let mut res = Resources::default();
+        for sub in &self.subsystems {
+            match sub {
+                Subsystem::Cpu(c) => c.snapshot(&mut res),
+                Subsystem::CpuSet(c) => c.snapshot(&mut res),
+                Subsystem::Mem(c) => c.snapshot(&mut res),
+                Subsystem::Pid(c) => c.snapshot(&mut res),
+                Subsystem::Devices(c) => c.snapshot(&mut res),
+                Subsystem::NetCls(c) => c.snapshot(&mut res),
+                Subsystem::HugeTlb(c) => c.snapshot(&mut res),
+                _ => {}
+            }
+        }
+        res
+    }
+
     /// Retrieve a container based on type inference.
     ///
     /// ## Example:
@@ -275,14 +369,9 @@ impl Cgroup {
         &'a T: From<&'a Subsystem>,
         T: Controller + ControllIdentifier,
     {
-        for i in &self.subsystems {
-            if i.to_controller().control_type() == T::controller_type() {
-                // N.B.:
-                // https://play.rust-lang.org/?gist=978b2846bacebdaa00be62374f4f4334&version=stable&mode=debug&edition=2015
-                return Some(i.into());
-            }
-        }
-        None
+        self.subsystems
+            .iter()
+            .find_map(|i| i.try_as_controller().ok())
     }
 
     /// Removes tasks from the control group by thread group id.
@@ -374,6 +463,17 @@ impl Cgroup {
         }
     }
 
+    /// Attach the calling process, with all its threads, to the control group.
+    pub fn attach_self(&self) -> Result<()> {
+        self.add_task_by_tgid(CgroupPid::from(std::process::id() as u64))
+    }
+
+    /// Move the calling process, with all its threads, to the root control group of the
+    /// hierarchy, e.g. so that the control group it was attached to can be deleted.
+    pub fn escape_to_root(&self) -> Result<()> {
+        self.remove_task_by_tgid(CgroupPid::from(std::process::id() as u64))
+    }
+
     /// set cgroup.type
     pub fn set_cgroup_type(&self, cgroup_type: &str) -> Result<()> {
         if self.v2() {
@@ -405,6 +505,110 @@ impl Cgroup {
         }
     }
 
+    /// Returns the state reported in `cgroup.events`, e.g. whether the control group still has
+    /// any process.
+    ///
+    /// Only supported for v2 cgroups.
+    pub fn events(&self) -> Result<CgroupEvents> {
+        if !self.v2() {
+            return Err(Error::new(CgroupVersion));
+        }
+        match self.subsystems().first() {
+            Some(sub) => sub.to_controller().events(),
+            None => Err(Error::new(SubsystemsEmpty)),
+        }
+    }
+
+    /// Watches `cgroup.events` and yields the `populated` state each time it flips, e.g. `false`
+    /// once the last process of the control group and its descendants exits.
+    ///
+    /// cgroup v2 has no release agent, so this is the way to remove a control group as soon as it
+    /// is empty. The iterator ends when the control group is removed. Only supported for v2
+    /// cgroups.
+    pub fn watch_populated(&self) -> Result<PopulatedWatcher> {
+        if !self.v2() {
+            return Err(Error::new(CgroupVersion));
+        }
+        PopulatedWatcher::new(self.hier.root().join(&self.path).join("cgroup.events"))
+    }
+
+    /// Gathers the main counters of every controller attached to the control group in one go.
+    ///
+    /// A field is `None` when its controller is not attached or its file cannot be read; on
+    /// cgroup v2 there is no `cpuacct` controller, so `cpuacct_usage` is always `None` there.
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            cpu_throttling: self
+                .controller_of::<CpuController>()
+                .and_then(|c| c.cpu_stat().ok()),
+            memory: self
+                .controller_of::<MemController>()
+                .and_then(|c| c.stat().ok()),
+            cpuacct_usage: self
+                .controller_of::<CpuAcctController>()
+                .and_then(|c| c.usage().ok()),
+            blkio_service_bytes: self
+                .controller_of::<BlkIoController>()
+                .and_then(|c| c.io_service_bytes_total().ok()),
+            pids_current: self
+                .controller_of::<PidController>()
+                .and_then(|c| c.get_pid_current().ok()),
+        }
+    }
+
+    /// Returns the controllers available to this control group, read from `cgroup.controllers`.
+    ///
+    /// Only supported for v2 cgroups.
+    pub fn available_controllers(&self) -> Result<Vec<Controllers>> {
+        if !self.v2() {
+            return Err(Error::new(CgroupVersion));
+        }
+
+        let p = self.hier.root().join(&self.path).join("cgroup.controllers");
+        let content = fs::read_to_string(&p)
+            .map_err(|e| Error::with_cause(ReadFailed(p.display().to_string()), e))?;
+        Ok(content
+            .split_whitespace()
+            .filter_map(v2_controller_from_name)
+            .collect())
+    }
+
+    /// Enables `controllers` for the children of this control group by writing them to
+    /// `cgroup.subtree_control`.
+    ///
+    /// Only supported for v2 cgroups. The kernel rejects the whole write if any of the
+    /// controllers is unavailable, the error then carries the full list that was written.
+    pub fn enable_controllers(&self, controllers: &[Controllers]) -> Result<()> {
+        self.write_subtree_control('+', controllers)
+    }
+
+    /// Disables `controllers` for the children of this control group.
+    ///
+    /// Only supported for v2 cgroups.
+    pub fn disable_controllers(&self, controllers: &[Controllers]) -> Result<()> {
+        self.write_subtree_control('-', controllers)
+    }
+
+    fn write_subtree_control(&self, op: char, controllers: &[Controllers]) -> Result<()> {
+        if !self.v2() {
+            return Err(Error::new(CgroupVersion));
+        }
+
+        let p = self
+            .hier
+            .root()
+            .join(&self.path)
+            .join("cgroup.subtree_control");
+        let tokens = controllers
+            .iter()
+            .map(|c| format!("{}{}", op, v2_controller_name(c)))
+            .collect::<Vec<String>>();
+
+        let value = tokens.join(" ");
+        fs::write(&p, &value)
+            .map_err(|e| Error::with_cause(WriteFailed(p.display().to_string(), value), e))
+    }
+
     /// Set notify_on_release to the control group.
     pub fn set_notify_on_release(&self, enable: bool) -> Result<()> {
         self.subsystems()
@@ -478,6 +682,71 @@ impl Cgroup {
 
 pub const UNIFIED_MOUNTPOINT: &str = "/sys/fs/cgroup";
 
+/// A snapshot of the main counters of a control group, see `Cgroup::statistics()`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statistics {
+    /// The CFS bandwidth throttling statistics found in `cpu.stat`.
+    pub cpu_throttling: Option<CpuThrottling>,
+    /// The statistics found in `memory.stat`.
+    pub memory: Option<MemoryStat>,
+    /// The CPU time (in nanoseconds) spent by the tasks, found in `cpuacct.usage`.
+    pub cpuacct_usage: Option<u64>,
+    /// The number of bytes transferred from and to the block devices.
+    pub blkio_service_bytes: Option<u64>,
+    /// The number of processes currently in the control group, found in `pids.current`.
+    pub pids_current: Option<u64>,
+}
+
+/// Yields the `populated` state of a control group each time it changes, see
+/// `Cgroup::watch_populated()`.
+#[derive(Debug)]
+pub struct PopulatedWatcher {
+    watch: FileWatch,
+    last: bool,
+}
+
+impl PopulatedWatcher {
+    fn new(path: PathBuf) -> Result<Self> {
+        let last = read_populated(&path)?;
+        Ok(PopulatedWatcher {
+            watch: FileWatch::new(path)?,
+            last,
+        })
+    }
+}
+
+fn read_populated(path: &Path) -> Result<bool> {
+    fs::read_to_string(path)
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))
+        .and_then(|s| parse_cgroup_events(&s))
+        .map(|events| events.populated)
+}
+
+impl Iterator for PopulatedWatcher {
+    type Item = Result<bool>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.watch.path().exists() {
+                return None;
+            }
+            // cgroup.events is not a stream, the whole file is read again on every change.
+            match read_populated(self.watch.path()) {
+                Ok(populated) if populated != self.last => {
+                    self.last = populated;
+                    return Some(Ok(populated));
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if let Err(e) = self.watch.wait() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
 fn enable_controllers(controllers: &[String], path: &Path) {
     let f = path.join("cgroup.subtree_control");
     for c in controllers {
@@ -486,6 +755,28 @@ fn enable_controllers(controllers: &[String], path: &Path) {
     }
 }
 
+/// The name of the controller in the unified hierarchy.
+fn v2_controller_name(c: &Controllers) -> String {
+    match c {
+        Controllers::BlkIo => "io".to_string(),
+        c => c.to_string(),
+    }
+}
+
+fn v2_controller_from_name(name: &str) -> Option<Controllers> {
+    match name {
+        "cpu" => Some(Controllers::Cpu),
+        "cpuset" => Some(Controllers::CpuSet),
+        "io" => Some(Controllers::BlkIo),
+        "memory" => Some(Controllers::Mem),
+        "pids" => Some(Controllers::Pids),
+        "hugetlb" => Some(Controllers::HugeTlb),
+        "rdma" => Some(Controllers::Rdma),
+        "perf_event" => Some(Controllers::PerfEvent),
+        _ => None,
+    }
+}
+
 fn supported_controllers() -> Vec<String> {
     let p = format!("{}/{}", UNIFIED_MOUNTPOINT, "cgroup.controllers");
     let ret = fs::read_to_string(p.as_str());
@@ -575,3 +866,120 @@ fn get_cgroups_relative_paths_by_path(path: String) -> Result<HashMap<String, St
     }
     Ok(m)
 }
+
+/// Returns the control groups the process `pid` belongs to, as the controllers and their paths
+/// relative to the root of the hierarchy, read from `/proc/<pid>/cgroup`.
+///
+/// The cgroup v2 line (`0::/path`) does not list any controller; it is reported for every
+/// controller available at the root of the unified hierarchy, which is none in hybrid mode.
+/// Controllers this crate does not know about are skipped.
+pub fn cgroups_of_pid(pid: u64) -> Result<Vec<(Controllers, PathBuf)>> {
+    let path = format!("/proc/{}/cgroup", pid);
+    let content = fs::read_to_string(&path).map_err(|e| Error::with_cause(ReadFailed(path), e))?;
+    let unified = supported_controllers()
+        .iter()
+        .filter_map(|name| v2_controller_from_name(name.trim()))
+        .collect::<Vec<_>>();
+    parse_proc_cgroup(&content, &unified)
+}
+
+/// Parses the `hierarchy-id:controllers:path` lines of `/proc/<pid>/cgroup`, attributing the
+/// controller-less v2 line to `unified`.
+fn parse_proc_cgroup(s: &str, unified: &[Controllers]) -> Result<Vec<(Controllers, PathBuf)>> {
+    let mut cgroups = Vec::new();
+    for line in s.lines().filter(|l| !l.is_empty()) {
+        // The path is last and may itself contain colons.
+        let fields = line.splitn(3, ':').collect::<Vec<_>>();
+        let (controllers, path) = match fields.as_slice() {
+            [_, controllers, path] => (*controllers, PathBuf::from(path)),
+            _ => return Err(Error::new(ParseError)),
+        };
+
+        if controllers.is_empty() {
+            cgroups.extend(unified.iter().map(|c| (c.clone(), path.clone())));
+            continue;
+        }
+        for name in controllers.split(',') {
+            if let Ok(c) = name.parse::<Controllers>() {
+                cgroups.push((c, path.clone()));
+            }
+        }
+    }
+    Ok(cgroups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::TempDir;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_populated_watcher() {
+        let dir = TempDir::new("populated_watcher");
+        let path = dir.join("cgroup.events");
+        fs::write(&path, "populated 1\nfrozen 0\n").unwrap();
+
+        let mut watcher = PopulatedWatcher::new(path.clone()).unwrap();
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::write(&path, "populated 0\nfrozen 0\n").unwrap();
+            })
+        };
+        assert!(!watcher.next().unwrap().unwrap());
+        writer.join().unwrap();
+
+        fs::write(&path, "populated 1\nfrozen 0\n").unwrap();
+        assert!(watcher.next().unwrap().unwrap());
+
+        drop(dir);
+        assert!(watcher.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_proc_cgroup() {
+        let content = "\
+12:pids:/user.slice/user-1000.slice
+11:cpu,cpuacct:/user.slice
+10:misc:/
+1:name=systemd:/user.slice/user-1000.slice/session-1.scope
+0::/user.slice/user-1000.slice/session-1.scope
+";
+        let scope = PathBuf::from("/user.slice/user-1000.slice/session-1.scope");
+
+        let cgroups = parse_proc_cgroup(content, &[]).unwrap();
+        assert_eq!(
+            cgroups,
+            vec![
+                (
+                    Controllers::Pids,
+                    PathBuf::from("/user.slice/user-1000.slice")
+                ),
+                (Controllers::Cpu, PathBuf::from("/user.slice")),
+                (Controllers::CpuAcct, PathBuf::from("/user.slice")),
+                (Controllers::Systemd, scope.clone()),
+            ]
+        );
+
+        let cgroups = parse_proc_cgroup(content, &[Controllers::Mem, Controllers::BlkIo]).unwrap();
+        assert_eq!(cgroups.len(), 6);
+        assert_eq!(cgroups[4], (Controllers::Mem, scope.clone()));
+        assert_eq!(cgroups[5], (Controllers::BlkIo, scope));
+
+        // A pure v2 host, with a colon in the path.
+        let cgroups = parse_proc_cgroup("0::/a:b\n", &[Controllers::Pids]).unwrap();
+        assert_eq!(cgroups, vec![(Controllers::Pids, PathBuf::from("/a:b"))]);
+
+        let err = parse_proc_cgroup("garbage\n", &[]).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_cgroups_of_pid() {
+        let cgroups = cgroups_of_pid(std::process::id() as u64).unwrap();
+        assert!(cgroups.iter().all(|(_, path)| path.is_absolute()));
+    }
+}
@@ -156,6 +156,93 @@ impl CgroupBuilder {
     }
 }
 
+/// A builder composing a `Resources` across controllers, to be applied with `Cgroup::apply()`.
+///
+/// Limits that are not set stay `None`, so `apply()` leaves the respective control files
+/// untouched.
+///
+/// ```rust
+/// # use cgroups_rs::cgroup_builder::ResourcesBuilder;
+/// # use cgroups_rs::MaxValue;
+/// let res = ResourcesBuilder::new()
+///     .cpu_shares(1024)
+///     .cpuset_cpus("0-3")
+///     .memory_limit(1 << 30)
+///     .pids_max(MaxValue::Value(100))
+///     .build();
+/// assert_eq!(res.cpu.shares, Some(1024));
+/// assert_eq!(res.memory.swappiness, None);
+/// ```
+#[derive(Debug, Default)]
+pub struct ResourcesBuilder {
+    resources: Resources,
+}
+
+impl ResourcesBuilder {
+    pub fn new() -> ResourcesBuilder {
+        ResourcesBuilder::default()
+    }
+
+    /// See `CpuController::set_shares()`.
+    pub fn cpu_shares(mut self, shares: u64) -> Self {
+        self.resources.cpu.shares = Some(shares);
+        self
+    }
+
+    /// See `CpuController::set_cfs_quota()`.
+    pub fn cpu_quota(mut self, quota: i64) -> Self {
+        self.resources.cpu.quota = Some(quota);
+        self
+    }
+
+    /// See `CpuController::set_cfs_period()`.
+    pub fn cpu_period(mut self, period: u64) -> Self {
+        self.resources.cpu.period = Some(period);
+        self
+    }
+
+    /// See `CpuSetController::set_cpus()`.
+    pub fn cpuset_cpus(mut self, cpus: &str) -> Self {
+        self.resources.cpu.cpus = Some(cpus.to_string());
+        self
+    }
+
+    /// See `CpuSetController::set_mems()`.
+    pub fn cpuset_mems(mut self, mems: &str) -> Self {
+        self.resources.cpu.mems = Some(mems.to_string());
+        self
+    }
+
+    /// See `MemController::set_limit()`.
+    pub fn memory_limit(mut self, limit: i64) -> Self {
+        self.resources.memory.memory_hard_limit = Some(limit);
+        self
+    }
+
+    /// See `MemController::set_soft_limit()`.
+    pub fn memory_soft_limit(mut self, limit: i64) -> Self {
+        self.resources.memory.memory_soft_limit = Some(limit);
+        self
+    }
+
+    /// See `MemController::set_memswap_limit()`.
+    pub fn memory_swap_limit(mut self, limit: i64) -> Self {
+        self.resources.memory.memory_swap_limit = Some(limit);
+        self
+    }
+
+    /// See `PidController::set_pid_max()`.
+    pub fn pids_max(mut self, max: MaxValue) -> Self {
+        self.resources.pid.maximum_number_of_processes = Some(max);
+        self
+    }
+
+    /// Finish composing the resources.
+    pub fn build(self) -> Resources {
+        self.resources
+    }
+}
+
 /// A builder that configures the memory controller of a control group.
 pub struct MemoryResourceBuilder {
     cgroup: CgroupBuilder,
@@ -10,12 +10,17 @@
 //!  [Documentation/scheduler/sched-design-CFS.txt](https://www.kernel.org/doc/Documentation/scheduler/sched-design-CFS.txt)
 //!  paragraph 7 ("GROUP SCHEDULER EXTENSIONS TO CFS").
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::PathBuf;
 
+use crate::cpuset::online_cpus;
 use crate::error::ErrorKind::*;
 use crate::error::*;
-use crate::{parse_max_value, read_i64_from, read_u64_from};
+use crate::hierarchies;
+#[cfg(feature = "tokio")]
+use crate::util::parse_from;
+use crate::util::{read_i64_from, read_string_from, read_u64_from, write_to, write_u64_to};
+use crate::{parse_max_value, parse_pressure, Pressure};
 
 use crate::{
     ControllIdentifier, ControllerInternal, Controllers, CpuResources, CustomizedAttribute,
@@ -44,6 +49,32 @@ pub struct Cpu {
     pub stat: String,
 }
 
+/// Statistics about the CFS bandwidth throttling of the control group, as found in `cpu.stat`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuThrottling {
+    /// Number of enforcement periods that have elapsed.
+    pub nr_periods: u64,
+    /// Number of times the control group has been throttled.
+    pub nr_throttled: u64,
+    /// Total time (in nanoseconds) the control group's tasks have been throttled for.
+    pub throttled_time: u64,
+}
+
+/// The knobs of the `cpu` controller, see `CpuController::control_file()`.
+#[derive(Debug, Clone, Copy)]
+enum Knob {
+    Shares,
+    CfsPeriod,
+    CfsQuota,
+    Stat,
+    Pressure,
+    UclampMin,
+    UclampMax,
+    RtRuntime,
+    RtPeriod,
+}
+
 /// The current state of the control group and its processes.
 #[derive(Debug)]
 struct CfsQuotaAndPeriod {
@@ -80,9 +111,7 @@ impl ControllerInternal for CpuController {
         update_and_test!(self, set_cfs_period, res.period, cfs_period);
         update_and_test!(self, set_cfs_quota, res.quota, cfs_quota);
 
-        res.attrs.iter().for_each(|(k, v)| {
-            let _ = self.set(k, v);
-        });
+        res.attrs.iter().try_for_each(|(k, v)| self.set(k, v))?;
 
         // TODO: rt properties (CONFIG_RT_GROUP_SCHED) are not yet supported
 
@@ -98,15 +127,9 @@ impl ControllIdentifier for CpuController {
 
 impl<'a> From<&'a Subsystem> for &'a CpuController {
     fn from(sub: &'a Subsystem) -> &'a CpuController {
-        unsafe {
-            match sub {
-                Subsystem::Cpu(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Cpu(c) => c,
+            _ => panic!("Subsystem is not a CpuController"),
         }
     }
 }
@@ -121,23 +144,72 @@ impl CpuController {
         }
     }
 
+    /// Constructs a `CpuController` for the root of the `cpu` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::Cpu)?;
+        Ok(Self::new(root, v2))
+    }
+
+    /// Reads back the values `apply()` sets from `res.cpu`, see `Cgroup::snapshot()`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        res.cpu.shares = self.shares().ok();
+        res.cpu.quota = self.cfs_quota().ok();
+        res.cpu.period = self.cfs_period().ok();
+    }
+
+    /// Returns the file implementing `knob` in the detected cgroup version.
+    fn control_file(&self, knob: Knob) -> &'static str {
+        match (knob, self.v2) {
+            (Knob::Shares, false) => "cpu.shares",
+            (Knob::Shares, true) => "cpu.weight",
+            (Knob::CfsPeriod, false) => "cpu.cfs_period_us",
+            (Knob::CfsQuota, false) => "cpu.cfs_quota_us",
+            // Quota and period share a single file on cgroup v2.
+            (Knob::CfsPeriod, true) | (Knob::CfsQuota, true) => "cpu.max",
+            (Knob::Stat, _) => "cpu.stat",
+            (Knob::Pressure, _) => "cpu.pressure",
+            (Knob::UclampMin, _) => "cpu.uclamp.min",
+            (Knob::UclampMax, _) => "cpu.uclamp.max",
+            (Knob::RtRuntime, _) => "cpu.rt_runtime_us",
+            (Knob::RtPeriod, _) => "cpu.rt_period_us",
+        }
+    }
+
     /// Returns CPU time statistics based on the processes in the control group.
     pub fn cpu(&self) -> Cpu {
+        let file_name = self.control_file(Knob::Stat);
         Cpu {
             stat: self
-                .open_path("cpu.stat", false)
+                .open_path(file_name, false)
                 .and_then(|mut file| {
                     let mut s = String::new();
                     let res = file.read_to_string(&mut s);
                     match res {
                         Ok(_) => Ok(s),
-                        Err(e) => Err(Error::with_cause(ReadFailed("cpu.stat".to_string()), e)),
+                        Err(e) => Err(Error::with_cause(ReadFailed(file_name.to_string()), e)),
                     }
                 })
                 .unwrap_or_default(),
         }
     }
 
+    /// Returns the throttling statistics found in `cpu.stat`.
+    pub fn cpu_stat(&self) -> Result<CpuThrottling> {
+        self.open_path(self.control_file(Knob::Stat), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_cpu_stat(&s))
+    }
+
+    /// Returns the CPU pressure stall information found in `cpu.pressure`.
+    ///
+    /// This is only available on cgroup v2 with a kernel built with `CONFIG_PSI`.
+    pub fn pressure(&self) -> Result<Pressure> {
+        self.open_path(self.control_file(Knob::Pressure), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pressure(&s))
+    }
+
     /// Configures the CPU bandwidth (in relative relation to other control groups and this control
     /// group's parent).
     ///
@@ -145,56 +217,55 @@ impl CpuController {
     /// `shares` to `200` ensures that control group `B` receives twice as much as CPU bandwidth.
     /// (Assuming both `A` and `B` are of the same parent)
     pub fn set_shares(&self, shares: u64) -> Result<()> {
-        let mut file_name = "cpu.shares";
-        if self.v2 {
-            file_name = "cpu.weight";
-        }
+        let file_name = self.control_file(Knob::Shares);
         // NOTE: .CpuShares is not used here. Conversion is the caller's responsibility.
-        self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(shares.to_string().as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed(file_name.to_string(), shares.to_string()), e)
-            })
-        })
+        self.open_path(file_name, true)
+            .and_then(|file| write_u64_to(file, file_name, shares))
     }
 
     /// Retrieve the CPU bandwidth that this control group (relative to other control groups and
     /// this control group's parent) can use.
     pub fn shares(&self) -> Result<u64> {
-        let mut file = "cpu.shares";
-        if self.v2 {
-            file = "cpu.weight";
-        }
+        let file = self.control_file(Knob::Shares);
         self.open_path(file, false).and_then(read_u64_from)
     }
 
+    async_twin! {
+        /// Same as `set_shares()`, but writes through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        set_shares_async(&self, shares: u64),
+        write self.control_file(Knob::Shares), &shares.to_string()
+    }
+
+    async_twin! {
+        /// Same as `shares()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        shares_async(&self) -> u64,
+        read self.control_file(Knob::Shares), parse_from
+    }
+
     /// Specify a period (when using the CFS scheduler) of time in microseconds for how often this
     /// control group's access to the CPU should be reallocated.
     pub fn set_cfs_period(&self, us: u64) -> Result<()> {
         if self.v2 {
             return self.set_cfs_quota_and_period(None, Some(us));
         }
-        self.open_path("cpu.cfs_period_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.cfs_period_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
-            })
+        let file_name = self.control_file(Knob::CfsPeriod);
+        self.open_path(file_name, true)
+            .and_then(|file| write_u64_to(file, file_name, us))
     }
 
     /// Retrieve the period of time of how often this cgroup's access to the CPU should be
     /// reallocated in microseconds.
     pub fn cfs_period(&self) -> Result<u64> {
+        let file = self.control_file(Knob::CfsPeriod);
         if self.v2 {
             let current_value = self
-                .open_path("cpu.max", false)
+                .open_path(file, false)
                 .and_then(parse_cfs_quota_and_period)?;
             return Ok(current_value.period);
         }
-        self.open_path("cpu.cfs_period_us", false)
-            .and_then(read_u64_from)
+        self.open_path(file, false).and_then(read_u64_from)
     }
 
     /// Specify a quota (when using the CFS scheduler) of time in microseconds for which all tasks
@@ -203,29 +274,25 @@ impl CpuController {
         if self.v2 {
             return self.set_cfs_quota_and_period(Some(us), None);
         }
-        self.open_path("cpu.cfs_quota_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.cfs_quota_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::CfsQuota);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &us.to_string()).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), us.to_string()), e)
             })
+        })
     }
 
     /// Retrieve the quota of time for which all tasks in this cgroup can run during one period, in
     /// microseconds.
     pub fn cfs_quota(&self) -> Result<i64> {
+        let file = self.control_file(Knob::CfsQuota);
         if self.v2 {
             let current_value = self
-                .open_path("cpu.max", false)
+                .open_path(file, false)
                 .and_then(parse_cfs_quota_and_period)?;
             return Ok(current_value.quota.to_i64());
         }
-
-        self.open_path("cpu.cfs_quota_us", false)
-            .and_then(read_i64_from)
+        self.open_path(file, false).and_then(read_i64_from)
     }
 
     pub fn set_cfs_quota_and_period(&self, quota: Option<i64>, period: Option<u64>) -> Result<()> {
@@ -248,15 +315,16 @@ impl CpuController {
         // which indicates that the group may consume upto $MAX in each $PERIOD duration.
         // “max” for $MAX indicates no limit. If only one number is written, $MAX is updated.
 
+        let file_name = self.control_file(Knob::CfsQuota);
         let current_value = self
-            .open_path("cpu.max", false)
+            .open_path(file_name, false)
             .and_then(parse_cfs_quota_and_period)?;
 
         let new_quota = if let Some(q) = quota {
             if q > 0 {
-                q.to_string()
+                MaxValue::Value(q).to_string()
             } else {
-                "max".to_string()
+                MaxValue::Max.to_string()
             }
         } else {
             current_value.quota.to_string()
@@ -269,34 +337,146 @@ impl CpuController {
         };
 
         let line = format!("{} {}", new_quota, new_period);
-        self.open_path("cpu.max", true).and_then(|mut file| {
-            file.write_all(line.as_ref())
-                .map_err(|e| Error::with_cause(WriteFailed("cpu.max".to_string(), line), e))
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &line)
+                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), line), e))
         })
     }
 
+    /// Limit the control group to `percent` of the machine's CPU time, keeping the current CFS
+    /// period.
+    ///
+    /// The quota is `period * percent / 100` times the number of online CPUs, so on a 4-core
+    /// machine `50.0` allows two full CPUs running in parallel and `25.0` a single one. Values
+    /// above `100.0` are accepted but clamped to all online CPUs, since a larger quota could never
+    /// be used up. The quota is also raised to the kernel's minimum of 1ms.
+    ///
+    /// On cgroup v1 this writes `cpu.cfs_quota_us`, on cgroup v2 `cpu.max`. A `percent` that is
+    /// not a positive number fails with `OutOfRange`.
+    pub fn set_cpu_limit_percent(&self, percent: f64) -> Result<()> {
+        if !percent.is_finite() || percent <= 0.0 {
+            return Err(Error::new(OutOfRange(
+                self.control_file(Knob::CfsQuota).to_string(),
+                percent.to_string(),
+            )));
+        }
+        let period = self.cfs_period()?;
+        let cpus = online_cpus()?.len() as u64;
+        self.set_cfs_quota(cpu_limit_quota(period, percent, cpus))
+    }
+
+    /// Set the minimum utilization, as a percentage from `0.0` to `100.0`, that the scheduler
+    /// assumes for the tasks of the control group (`cpu.uclamp.min`).
+    ///
+    /// Fails with `NotSupported` if the kernel was built without `CONFIG_UCLAMP_TASK_GROUP`.
+    pub fn set_uclamp_min(&self, percent: f32) -> Result<()> {
+        self.write_uclamp(Knob::UclampMin, percent)
+    }
+
+    /// Retrieve the minimum utilization set by `set_uclamp_min()`.
+    pub fn uclamp_min(&self) -> Result<f32> {
+        self.open_path(self.control_file(Knob::UclampMin), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_uclamp(&s))
+    }
+
+    /// Set the maximum utilization, as a percentage from `0.0` to `100.0`, that the scheduler
+    /// assumes for the tasks of the control group (`cpu.uclamp.max`). `100.0` is written as
+    /// `max`, i.e. no clamping.
+    ///
+    /// Fails with `NotSupported` if the kernel was built without `CONFIG_UCLAMP_TASK_GROUP`.
+    pub fn set_uclamp_max(&self, percent: f32) -> Result<()> {
+        self.write_uclamp(Knob::UclampMax, percent)
+    }
+
+    /// Retrieve the maximum utilization set by `set_uclamp_max()`, `max` being read as `100.0`.
+    pub fn uclamp_max(&self) -> Result<f32> {
+        self.open_path(self.control_file(Knob::UclampMax), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_uclamp(&s))
+    }
+
+    fn write_uclamp(&self, knob: Knob, percent: f32) -> Result<()> {
+        let file_name = self.control_file(knob);
+        // Also rejects NaN.
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(Error::new(OutOfRange(
+                file_name.to_string(),
+                percent.to_string(),
+            )));
+        }
+        let value = if percent == 100.0 {
+            MaxValue::Max.to_string()
+        } else {
+            format!("{:.2}", percent)
+        };
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &value)
+                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), value), e))
+        })
+    }
+
+    /// Retrieve the time in microseconds for which real-time tasks in this control group can
+    /// run during one period, `-1` meaning no limit.
+    ///
+    /// Fails with `NotSupported` if the kernel was built without `CONFIG_RT_GROUP_SCHED`.
+    pub fn rt_runtime(&self) -> Result<i64> {
+        self.open_path(self.control_file(Knob::RtRuntime), false)
+            .and_then(read_i64_from)
+    }
+
+    async_twin! {
+        /// Same as `rt_runtime()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        rt_runtime_async(&self) -> i64,
+        read self.control_file(Knob::RtRuntime), parse_from
+    }
+
+    /// Retrieve the period in microseconds over which the real-time runtime is allotted.
+    ///
+    /// Fails with `NotSupported` if the kernel was built without `CONFIG_RT_GROUP_SCHED`.
+    pub fn rt_period_us(&self) -> Result<u64> {
+        self.open_path(self.control_file(Knob::RtPeriod), false)
+            .and_then(read_u64_from)
+    }
+
+    async_twin! {
+        /// Same as `rt_period_us()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        rt_period_us_async(&self) -> u64,
+        read self.control_file(Knob::RtPeriod), parse_from
+    }
+
     pub fn set_rt_runtime(&self, us: i64) -> Result<()> {
-        self.open_path("cpu.rt_runtime_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.rt_runtime_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::RtRuntime);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &us.to_string()).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), us.to_string()), e)
             })
+        })
+    }
+
+    async_twin! {
+        /// Same as `set_rt_runtime()`, but writes through `tokio::fs` so that the async runtime is
+        /// not blocked.
+        set_rt_runtime_async(&self, us: i64),
+        write self.control_file(Knob::RtRuntime), &us.to_string()
     }
 
     pub fn set_rt_period_us(&self, us: u64) -> Result<()> {
-        self.open_path("cpu.rt_period_us", true)
-            .and_then(|mut file| {
-                file.write_all(us.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpu.rt_period_us".to_string(), us.to_string()),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::RtPeriod);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &us.to_string()).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), us.to_string()), e)
             })
+        })
+    }
+
+    async_twin! {
+        /// Same as `set_rt_period_us()`, but writes through `tokio::fs` so that the async runtime
+        /// is not blocked.
+        set_rt_period_us_async(&self, us: u64),
+        write self.control_file(Knob::RtPeriod), &us.to_string()
     }
 }
 
@@ -319,3 +499,133 @@ fn parse_cfs_quota_and_period(mut file: File) -> Result<CfsQuotaAndPeriod> {
 
     Ok(CfsQuotaAndPeriod { quota, period })
 }
+
+/// Parse `cpu.stat`, which reports `throttled_time` in nanoseconds on v1 and `throttled_usec` in
+/// microseconds on v2. Other keys, e.g. `usage_usec` on v2, are ignored.
+fn parse_cpu_stat(s: &str) -> Result<CpuThrottling> {
+    let mut stat = CpuThrottling::default();
+    for line in s.lines() {
+        let mut sp = line.split_whitespace();
+        let (key, value) = match (sp.next(), sp.next()) {
+            (Some(k), Some(v)) => (k, v),
+            _ => continue,
+        };
+        let value = value
+            .parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
+        match key {
+            "nr_periods" => stat.nr_periods = value,
+            "nr_throttled" => stat.nr_throttled = value,
+            "throttled_time" => stat.throttled_time = value,
+            "throttled_usec" => stat.throttled_time = value * 1000,
+            _ => {}
+        }
+    }
+    Ok(stat)
+}
+
+/// Parses `cpu.uclamp.min` or `cpu.uclamp.max`, which hold a percentage such as `80.00` or `max`.
+fn parse_uclamp(s: &str) -> Result<f32> {
+    match s.trim() {
+        "max" => Ok(100.0),
+        percent => percent
+            .parse::<f32>()
+            .map_err(|e| Error::with_cause(ParseError, e)),
+    }
+}
+
+/// The CFS quota giving `percent` of `cpus` CPUs per `period`, see `set_cpu_limit_percent()`.
+fn cpu_limit_quota(period: u64, percent: f64, cpus: u64) -> i64 {
+    let cpus = cpus.max(1);
+    let quota = (period as f64 * percent / 100.0 * cpus as f64).round() as u64;
+    quota.min(period * cpus).max(1000) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{
+        cpu_limit_quota, parse_cpu_stat, parse_uclamp, CpuController, CpuThrottling, Knob,
+    };
+    use crate::cpuset::online_cpus;
+    use crate::error::ErrorKind;
+    use crate::util::tests::TempDir;
+
+    #[test]
+    fn test_cpu_limit_quota() {
+        let cpus = online_cpus().unwrap().len() as u64;
+        assert_eq!(cpu_limit_quota(100_000, 50.0, cpus), 50_000 * cpus as i64);
+
+        // The same percentage allows more CPU time on a larger machine.
+        assert_eq!(cpu_limit_quota(100_000, 50.0, 1), 50_000);
+        assert_eq!(cpu_limit_quota(100_000, 50.0, 4), 200_000);
+        assert_eq!(cpu_limit_quota(100_000, 200.0, 4), 400_000);
+        assert_eq!(cpu_limit_quota(100_000, 0.1, 4), 1000);
+        assert_eq!(cpu_limit_quota(100_000, 33.3333, 3), 100_000);
+    }
+
+    #[test]
+    fn test_control_file() {
+        let v1 = CpuController::new("/sys/fs/cgroup/cpu".into(), false);
+        let v2 = CpuController::new("/sys/fs/cgroup".into(), true);
+        assert_eq!(v1.control_file(Knob::Shares), "cpu.shares");
+        assert_eq!(v2.control_file(Knob::Shares), "cpu.weight");
+        assert_eq!(v1.control_file(Knob::CfsQuota), "cpu.cfs_quota_us");
+        assert_eq!(v2.control_file(Knob::CfsQuota), "cpu.max");
+        assert_eq!(v2.control_file(Knob::CfsPeriod), "cpu.max");
+        assert_eq!(v2.control_file(Knob::UclampMax), "cpu.uclamp.max");
+    }
+
+    #[test]
+    fn test_uclamp() {
+        assert_eq!(parse_uclamp("max\n").unwrap(), 100.0);
+        assert_eq!(parse_uclamp("20.50\n").unwrap(), 20.5);
+        assert!(parse_uclamp("").is_err());
+
+        let dir = TempDir::new("uclamp");
+        let cpu = CpuController::new(dir.to_path_buf(), true);
+
+        let err = cpu.set_uclamp_max(80.0).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+
+        std::fs::write(dir.join("cpu.uclamp.max"), "max\n").unwrap();
+        cpu.set_uclamp_max(80.0).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("cpu.uclamp.max")).unwrap(),
+            "80.00"
+        );
+        assert_eq!(cpu.uclamp_max().unwrap(), 80.0);
+        cpu.set_uclamp_max(100.0).unwrap();
+        assert_eq!(cpu.uclamp_max().unwrap(), 100.0);
+
+        for percent in [-1.0, 100.5, f32::NAN] {
+            let err = cpu.set_uclamp_max(percent).unwrap_err();
+            assert_eq!(
+                err.kind(),
+                &ErrorKind::OutOfRange("cpu.uclamp.max".to_string(), percent.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let v1 = "nr_periods 12\nnr_throttled 3\nthrottled_time 4500000\n";
+        let v2 = "\
+usage_usec 1337
+user_usec 1000
+system_usec 337
+nr_periods 12
+nr_throttled 3
+throttled_usec 4500
+";
+        let expected = CpuThrottling {
+            nr_periods: 12,
+            nr_throttled: 3,
+            throttled_time: 4500000,
+        };
+        assert_eq!(parse_cpu_stat(v1).unwrap(), expected);
+        assert_eq!(parse_cpu_stat(v2).unwrap(), expected);
+
+        let err = parse_cpu_stat("nr_periods x").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+}
@@ -7,13 +7,15 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/cpuacct.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/cpuacct.txt)
-use std::io::Write;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::{read_string_from, read_u64_from};
+#[cfg(feature = "tokio")]
+use crate::util::parse_from;
+use crate::util::{read_string_from, read_u64_from, write_to};
 use crate::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
 
 /// A controller that allows controlling the `cpuacct` subsystem of a Cgroup.
@@ -58,6 +60,18 @@ pub struct CpuAcct {
     pub usage_user: u64,
 }
 
+/// The CPU time spent by the tasks, as found in `cpuacct.stat`.
+///
+/// Both values are in `USER_HZ` units, i.e. clock ticks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuAcctStat {
+    /// CPU time spent in user mode.
+    pub user: u64,
+    /// CPU time spent in kernel mode.
+    pub system: u64,
+}
+
 impl ControllerInternal for CpuAcctController {
     fn control_type(&self) -> Controllers {
         Controllers::CpuAcct
@@ -75,6 +89,10 @@ impl ControllerInternal for CpuAcctController {
     fn apply(&self, _res: &Resources) -> Result<()> {
         Ok(())
     }
+
+    fn reset_stats(&self) -> Result<()> {
+        self.reset()
+    }
 }
 
 impl ControllIdentifier for CpuAcctController {
@@ -85,15 +103,9 @@ impl ControllIdentifier for CpuAcctController {
 
 impl<'a> From<&'a Subsystem> for &'a CpuAcctController {
     fn from(sub: &'a Subsystem) -> &'a CpuAcctController {
-        unsafe {
-            match sub {
-                Subsystem::CpuAcct(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::CpuAcct(c) => c,
+            _ => panic!("Subsystem is not a CpuAcctController"),
         }
     }
 }
@@ -107,6 +119,17 @@ impl CpuAcctController {
         }
     }
 
+    /// Constructs a `CpuAcctController` for the root of the `cpuacct` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn mounted() -> Result<Self> {
+        match hierarchies::controller_root(Controllers::CpuAcct)? {
+            (root, false) => Ok(Self::new(root)),
+            _ => Err(Error::new(CgroupVersion)),
+        }
+    }
+
     /// Gathers the statistics that are available in the control group into a `CpuAcct` structure.
     pub fn cpuacct(&self) -> CpuAcct {
         CpuAcct {
@@ -145,12 +168,100 @@ impl CpuAcctController {
         }
     }
 
+    /// Total CPU time (in nanoseconds) spent by the tasks.
+    ///
+    /// The kernel charges CPU time to every ancestor of a task's control group, so this already
+    /// includes the usage of all descendant control groups; there is nothing to roll up.
+    pub fn usage(&self) -> Result<u64> {
+        self.open_path("cpuacct.usage", false)
+            .and_then(read_u64_from)
+    }
+
+    async_twin! {
+        /// Same as `usage()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        usage_async(&self) -> u64,
+        read "cpuacct.usage", parse_from
+    }
+
+    /// CPU time (in nanoseconds) spent by the tasks, indexed by CPU.
+    pub fn usage_percpu(&self) -> Result<Vec<u64>> {
+        self.open_path("cpuacct.usage_percpu", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_usage_percpu(&s))
+    }
+
+    /// CPU time spent by the tasks, split into `user` and `system` time.
+    pub fn stat(&self) -> Result<CpuAcctStat> {
+        self.open_path("cpuacct.stat", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_stat(&s))
+    }
+
     /// Reset the statistics the kernel has gathered about the control group.
     pub fn reset(&self) -> Result<()> {
         self.open_path("cpuacct.usage", true).and_then(|mut file| {
-            file.write_all(b"0").map_err(|e| {
+            write_to(&mut file, "0").map_err(|e| {
                 Error::with_cause(WriteFailed("cpuacct.usage".to_string(), "0".to_string()), e)
             })
         })
     }
 }
+
+fn parse_usage_percpu(s: &str) -> Result<Vec<u64>> {
+    s.split_whitespace()
+        .map(|x| x.parse().map_err(|e| Error::with_cause(ParseError, e)))
+        .collect()
+}
+
+fn parse_stat(s: &str) -> Result<CpuAcctStat> {
+    let mut stat = CpuAcctStat::default();
+    for line in s.lines() {
+        let mut sp = line.split_whitespace();
+        let (key, value) = match (sp.next(), sp.next()) {
+            (Some(k), Some(v)) => (k, v),
+            _ => return Err(Error::new(ParseError)),
+        };
+        let value = value
+            .parse()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
+        match key {
+            "user" => stat.user = value,
+            "system" => stat.system = value,
+            _ => {}
+        }
+    }
+    Ok(stat)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpuacct::{parse_stat, parse_usage_percpu, CpuAcctStat};
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_parse_usage_percpu() {
+        assert_eq!(
+            parse_usage_percpu("8348363768 0 8598185449 \n").unwrap(),
+            vec![8348363768, 0, 8598185449]
+        );
+        assert_eq!(parse_usage_percpu("").unwrap(), Vec::<u64>::new());
+        let err = parse_usage_percpu("1 x").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        assert_eq!(
+            parse_stat("user 3057\nsystem 1547\n").unwrap(),
+            CpuAcctStat {
+                user: 3057,
+                system: 1547,
+            }
+        );
+        for case in ["user", "user x\nsystem 1"] {
+            let err = parse_stat(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+}
@@ -10,17 +10,44 @@
 //!  [Documentation/cgroup-v1/cpusets.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/cpusets.txt)
 
 use log::*;
-use std::io::Write;
-use std::path::PathBuf;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::{read_string_from, read_u64_from};
+use crate::util::{
+    parse_bool, parse_from, read_string_from, write_to, ControlDir, FdCache, FileWatch,
+};
 use crate::{
     ControllIdentifier, ControllerInternal, Controllers, CpuResources, Resources, Subsystem,
 };
 
+/// The knobs of the `cpuset` controller, see `CpuSetController::control_file()`.
+#[derive(Debug, Clone, Copy)]
+enum Knob {
+    Cpus,
+    Mems,
+    EffectiveCpus,
+    EffectiveMems,
+    CpuExclusive,
+    MemExclusive,
+    MemHardwall,
+    MemoryMigrate,
+    MemoryPressure,
+    MemoryPressureEnabled,
+    MemorySpreadPage,
+    MemorySpreadSlab,
+    SchedLoadBalance,
+    SchedRelaxDomainLevel,
+    Partition,
+    ExclusiveCpus,
+    EffectiveExclusiveCpus,
+    CloneChildren,
+}
+
 /// A controller that allows controlling the `cpuset` subsystem of a Cgroup.
 ///
 /// In essence, this controller is responsible for restricting the tasks in the control group to a
@@ -30,9 +57,42 @@ pub struct CpuSetController {
     base: PathBuf,
     path: PathBuf,
     v2: bool,
+    fd_cache: FdCache,
+}
+
+/// Yields the effective CPUs of a control group each time they change, see
+/// `CpuSetController::watch_effective_cpus()`.
+#[derive(Debug)]
+pub struct EffectiveCpuWatcher {
+    watch: FileWatch,
+    last: Vec<u32>,
+}
+
+impl Iterator for EffectiveCpuWatcher {
+    type Item = Result<Vec<u32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.watch.path().exists() {
+                return None;
+            }
+            match read_list(self.watch.path()) {
+                Ok(cpus) if cpus != self.last => {
+                    self.last = cpus.clone();
+                    return Some(Ok(cpus));
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if let Err(e) = self.watch.wait() {
+                return Some(Err(e));
+            }
+        }
+    }
 }
 
 /// The current state of the `cpuset` controller for this control group.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CpuSet {
     /// If true, no other control groups can share the CPUs listed in the `cpus` field.
@@ -73,20 +133,191 @@ pub struct CpuSet {
     /// The format is the same as the `cpus`, `effective_cpus` and `effective_mems` fields.
     pub mems: Vec<(u64, u64)>,
     /// If true, the kernel will attempt to rebalance the load between the CPUs specified in the
-    /// `cpus` field of this control group.
-    pub sched_load_balance: bool,
-    /// Represents how much work the kernel should do to rebalance this cpuset.
+    /// `cpus` field of this control group. The kernel exposes it as a `0`/`1` toggle in
+    /// `cpuset.sched_load_balance`, which only exists on cgroup v1.
     ///
-    /// | `sched_load_balance` | Effect |
-    /// | -------------------- | ------ |
-    /// |          -1          | Use the system default value |
-    /// |           0          | Only balance loads periodically |
-    /// |           1          | Immediately balance the load across tasks on the same core |
-    /// |           2          | Immediately balance the load across cores in the same CPU package |
-    /// |           4          | Immediately balance the load across CPUs on the same node |
-    /// |           5          | Immediately balance the load between CPUs even if the system is NUMA |
-    /// |           6          | Immediately balance the load between all CPUs |
-    pub sched_relax_domain_level: u64,
+    /// How aggressively the balancing happens is controlled separately by
+    /// `sched_relax_domain_level`.
+    pub sched_load_balance: bool,
+    /// Represents how much work the kernel should do to rebalance this cpuset, see
+    /// `RelaxDomainLevel`.
+    pub sched_relax_domain_level: RelaxDomainLevel,
+}
+
+/// How far the scheduler searches for an idle CPU when a task wakes up or a CPU becomes idle, as
+/// found in `cpuset.sched_relax_domain_level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RelaxDomainLevel {
+    /// `-1`: use the system default value.
+    #[default]
+    SystemDefault,
+    /// `0`: do not search, only balance loads periodically.
+    Periodic,
+    /// `1`: immediately balance the load across the hyperthreads of the same core.
+    SameCore,
+    /// `2`: immediately balance the load across cores in the same CPU package.
+    SamePackage,
+    /// `3`: immediately balance the load across CPUs on the same node.
+    SameNode,
+    /// `4`: immediately balance the load across a chunk of nodes on NUMA systems.
+    AllCpusNuma,
+    /// `5`: immediately balance the load between all CPUs.
+    AllCpus,
+}
+
+impl From<RelaxDomainLevel> for i64 {
+    fn from(level: RelaxDomainLevel) -> i64 {
+        match level {
+            RelaxDomainLevel::SystemDefault => -1,
+            RelaxDomainLevel::Periodic => 0,
+            RelaxDomainLevel::SameCore => 1,
+            RelaxDomainLevel::SamePackage => 2,
+            RelaxDomainLevel::SameNode => 3,
+            RelaxDomainLevel::AllCpusNuma => 4,
+            RelaxDomainLevel::AllCpus => 5,
+        }
+    }
+}
+
+impl TryFrom<i64> for RelaxDomainLevel {
+    type Error = Error;
+
+    fn try_from(level: i64) -> Result<Self> {
+        match level {
+            -1 => Ok(RelaxDomainLevel::SystemDefault),
+            0 => Ok(RelaxDomainLevel::Periodic),
+            1 => Ok(RelaxDomainLevel::SameCore),
+            2 => Ok(RelaxDomainLevel::SamePackage),
+            3 => Ok(RelaxDomainLevel::SameNode),
+            4 => Ok(RelaxDomainLevel::AllCpusNuma),
+            5 => Ok(RelaxDomainLevel::AllCpus),
+            _ => Err(Error::new(ParseError)),
+        }
+    }
+}
+
+fn parse_relax_domain_level(s: &str) -> Result<RelaxDomainLevel> {
+    parse_from::<i64>(s).and_then(RelaxDomainLevel::try_from)
+}
+
+/// A writable field of `CpuSet` that differs, along with its desired value, see `diff()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CpuSetChange {
+    /// See `CpuSet::cpus`.
+    Cpus(Vec<(u64, u64)>),
+    /// See `CpuSet::mems`.
+    Mems(Vec<(u64, u64)>),
+    /// See `CpuSet::cpu_exclusive`.
+    CpuExclusive(bool),
+    /// See `CpuSet::mem_exclusive`.
+    MemExclusive(bool),
+    /// See `CpuSet::mem_hardwall`.
+    MemHardwall(bool),
+    /// See `CpuSet::memory_migrate`.
+    MemoryMigrate(bool),
+    /// See `CpuSet::memory_pressure_enabled`.
+    MemoryPressureEnabled(bool),
+    /// See `CpuSet::memory_spread_page`.
+    MemorySpreadPage(bool),
+    /// See `CpuSet::memory_spread_slab`.
+    MemorySpreadSlab(bool),
+    /// See `CpuSet::sched_load_balance`.
+    SchedLoadBalance(bool),
+    /// See `CpuSet::sched_relax_domain_level`.
+    SchedRelaxDomainLevel(RelaxDomainLevel),
+}
+
+/// Returns the changes turning `current` into `desired`, so that a reconciliation loop only
+/// writes the knobs that differ with `CpuSetController::apply_diff()`.
+///
+/// Read-only fields, i.e. the effective lists and `memory_pressure`, are ignored, as is
+/// `memory_pressure_enabled` when `desired` leaves it to `None`. `cpus` and `mems` come first so
+/// that they are written before the flags depending on them, such as `cpu_exclusive`.
+pub fn diff(current: &CpuSet, desired: &CpuSet) -> Vec<CpuSetChange> {
+    let mut changes = Vec::new();
+    if current.cpus != desired.cpus {
+        changes.push(CpuSetChange::Cpus(desired.cpus.clone()));
+    }
+    if current.mems != desired.mems {
+        changes.push(CpuSetChange::Mems(desired.mems.clone()));
+    }
+
+    let mut flag = |current: bool, desired: bool, change: fn(bool) -> CpuSetChange| {
+        if current != desired {
+            changes.push(change(desired));
+        }
+    };
+    flag(
+        current.cpu_exclusive,
+        desired.cpu_exclusive,
+        CpuSetChange::CpuExclusive,
+    );
+    flag(
+        current.mem_exclusive,
+        desired.mem_exclusive,
+        CpuSetChange::MemExclusive,
+    );
+    flag(
+        current.mem_hardwall,
+        desired.mem_hardwall,
+        CpuSetChange::MemHardwall,
+    );
+    flag(
+        current.memory_migrate,
+        desired.memory_migrate,
+        CpuSetChange::MemoryMigrate,
+    );
+    flag(
+        current.memory_spread_page,
+        desired.memory_spread_page,
+        CpuSetChange::MemorySpreadPage,
+    );
+    flag(
+        current.memory_spread_slab,
+        desired.memory_spread_slab,
+        CpuSetChange::MemorySpreadSlab,
+    );
+    flag(
+        current.sched_load_balance,
+        desired.sched_load_balance,
+        CpuSetChange::SchedLoadBalance,
+    );
+
+    if let Some(enabled) = desired.memory_pressure_enabled {
+        if current.memory_pressure_enabled != Some(enabled) {
+            changes.push(CpuSetChange::MemoryPressureEnabled(enabled));
+        }
+    }
+    if current.sched_relax_domain_level != desired.sched_relax_domain_level {
+        changes.push(CpuSetChange::SchedRelaxDomainLevel(
+            desired.sched_relax_domain_level,
+        ));
+    }
+    changes
+}
+
+/// The partition type of a cpuset, as found in `cpuset.cpus.partition` (cgroup v2 only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionType {
+    /// The cpuset is a regular member of its parent's partition.
+    Member,
+    /// The cpuset is the root of a new partition, owning its CPUs exclusively.
+    Root,
+    /// The cpuset is a root partition whose CPUs are isolated from the scheduler's load balancing.
+    Isolated,
+}
+
+impl PartitionType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PartitionType::Member => "member",
+            PartitionType::Root => "root",
+            PartitionType::Isolated => "isolated",
+        }
+    }
 }
 
 impl ControllerInternal for CpuSetController {
@@ -111,8 +342,8 @@ impl ControllerInternal for CpuSetController {
         // get the resources that apply to this controller
         let res: &CpuResources = &res.cpu;
 
-        update!(self, set_cpus, res.cpus.as_ref());
-        update!(self, set_mems, res.mems.as_ref());
+        update!(self, set_cpus, res.cpus.as_deref());
+        update!(self, set_mems, res.mems.as_deref());
 
         Ok(())
     }
@@ -202,15 +433,9 @@ impl ControllIdentifier for CpuSetController {
 
 impl<'a> From<&'a Subsystem> for &'a CpuSetController {
     fn from(sub: &'a Subsystem) -> &'a CpuSetController {
-        unsafe {
-            match sub {
-                Subsystem::CpuSet(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::CpuSet(c) => c,
+            _ => panic!("Subsystem is not a CpuSetController"),
         }
     }
 }
@@ -252,6 +477,163 @@ fn parse_range(s: String) -> Result<Vec<(u64, u64)>> {
     Ok(fin)
 }
 
+/// Returns the CPUs that are online, read from `/sys/devices/system/cpu/online`.
+pub fn online_cpus() -> Result<Vec<u32>> {
+    read_list(Path::new("/sys/devices/system/cpu/online"))
+}
+
+/// Returns the memory nodes that are online, read from `/sys/devices/system/node/online`.
+///
+/// The file is missing on kernels built without NUMA support.
+pub fn online_mems() -> Result<Vec<u32>> {
+    read_list(Path::new("/sys/devices/system/node/online"))
+}
+
+fn read_list(path: &Path) -> Result<Vec<u32>> {
+    fs::read_to_string(path)
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))
+        .and_then(|s| parse_list(&s))
+}
+
+/// Expand a string like "0-3,7" into a sorted list of indices, e.g. `[0, 1, 2, 3, 7]`.
+fn parse_list(s: &str) -> Result<Vec<u32>> {
+    let mut fin = Vec::new();
+
+    for (start, end) in parse_range(s.trim().to_string())? {
+        if start > end {
+            return Err(Error::new(ParseError));
+        }
+        let start = u32::try_from(start).map_err(|e| Error::with_cause(ParseError, e))?;
+        let end = u32::try_from(end).map_err(|e| Error::with_cause(ParseError, e))?;
+        fin.extend(start..=end);
+    }
+
+    fin.sort_unstable();
+    fin.dedup();
+    Ok(fin)
+}
+
+/// Check that `s` is a list like "0-3,7" before it is written, so that a malformed list fails
+/// with `ParseError` and the offending token rather than with the kernel's `EINVAL`.
+fn validate_list(s: &str) -> Result<()> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(());
+    }
+
+    for token in s.split(',') {
+        let valid = match token.split_once('-') {
+            Some((start, end)) => match (start.parse::<u32>(), end.parse::<u32>()) {
+                (Ok(start), Ok(end)) => start <= end,
+                _ => false,
+            },
+            None => token.parse::<u32>().is_ok(),
+        };
+        if !valid {
+            return Err(Error::with_cause(
+                ParseError,
+                Error::from_string(format!("invalid token {:?} in {:?}", token, s)),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Read `cpuset.sched_load_balance` of the control group at `dir`.
+fn read_load_balance(dir: &Path) -> Result<bool> {
+    let path = dir.join("cpuset.sched_load_balance");
+    fs::read_to_string(&path)
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))
+        .and_then(|s| parse_bool(&s))
+}
+
+/// Fail with `OutOfRange` naming the indices in `requested` that the list at `path` lacks.
+fn check_list_allows(path: &Path, requested: &str) -> Result<()> {
+    let allowed = read_list(path)?;
+    let missing = parse_list(requested)?
+        .into_iter()
+        .filter(|x| !allowed.contains(x))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new(OutOfRange(
+            path.display().to_string(),
+            format_list(&missing),
+        )))
+    }
+}
+
+/// Parse the content of `cpuset.cpus.partition`.
+///
+/// When the kernel cannot honour a requested partition it reports the type followed by
+/// `invalid` and, on newer kernels, a parenthesized reason, e.g. `root invalid (...)`. That
+/// state is returned as an error carrying the raw content.
+fn parse_partition(s: &str) -> Result<PartitionType> {
+    let s = s.trim();
+    let mut parts = s.split_whitespace();
+    let partition = match parts.next() {
+        Some("member") => PartitionType::Member,
+        Some("root") => PartitionType::Root,
+        Some("isolated") => PartitionType::Isolated,
+        _ => return Err(Error::new(ParseError)),
+    };
+
+    match parts.next() {
+        None => Ok(partition),
+        Some("invalid") => Err(Error::from_string(format!("invalid partition: {}", s))),
+        Some(_) => Err(Error::new(ParseError)),
+    }
+}
+
+/// Collapse a list of indices like `[0, 1, 2, 5]` into the kernel's range syntax, e.g. "0-2,5".
+fn format_list(list: &[u32]) -> String {
+    let mut list = list.to_vec();
+    list.sort_unstable();
+    list.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for i in list {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == i => *end = i,
+            _ => ranges.push((i, i)),
+        }
+    }
+
+    format_ranges(&ranges)
+}
+
+/// Turn a list of indices into a bitmask with bit `i` set for each index `i`, failing for indices
+/// of 128 and above.
+fn list_to_mask(list: &[u32]) -> Result<u128> {
+    list.iter().try_fold(0, |mask, &i| {
+        1u128
+            .checked_shl(i)
+            .map(|bit| mask | bit)
+            .ok_or_else(|| Error::from_string(format!("CPU {} does not fit in a 128-bit mask", i)))
+    })
+}
+
+/// Turn a bitmask into the sorted list of the indices of its set bits.
+fn mask_to_list(mask: u128) -> Vec<u32> {
+    (0..u128::BITS).filter(|i| mask & (1 << i) != 0).collect()
+}
+
+/// Format `(start, end)` ranges like the ones in `CpuSet` into the kernel's list syntax.
+fn format_ranges<T: std::fmt::Display + PartialEq>(ranges: &[(T, T)]) -> String {
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 impl CpuSetController {
     /// Contructs a new `CpuSetController` with `root` serving as the root of the control group.
     pub fn new(root: PathBuf, v2: bool) -> Self {
@@ -259,152 +641,463 @@ impl CpuSetController {
             base: root.clone(),
             path: root,
             v2,
+            fd_cache: FdCache::default(),
+        }
+    }
+
+    /// Constructs a `CpuSetController` for the root of the `cpuset` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::CpuSet)?;
+        Ok(Self::new(root, v2))
+    }
+
+    /// Reads back the values `apply()` sets from `res.cpu`, see `Cgroup::snapshot()`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        let read = |file_name| {
+            self.open_path(file_name, false)
+                .and_then(read_string_from)
+                .map(|s| s.trim().to_string())
+                .ok()
+        };
+        res.cpu.cpus = read(self.control_file(Knob::Cpus));
+        res.cpu.mems = read(self.control_file(Knob::Mems));
+    }
+
+    /// Returns `true` if this controller points at the root of the cpuset hierarchy.
+    pub fn is_root(&self) -> bool {
+        self.get_path() == self.get_base()
+    }
+
+    /// Keep the control files read by `cpuset()`, `cpuset_checked()`, `cpus_list()` and
+    /// `mems_list()` open between calls, so that polling them does not reopen every file.
+    ///
+    /// The cache is disabled by default. Once the control group is removed, the cached files are
+    /// dropped and the getters fail.
+    pub fn set_fd_cache(&self, enabled: bool) {
+        self.fd_cache.set_enabled(enabled)
+    }
+
+    /// Returns whether `set_fd_cache()` enabled the descriptor cache.
+    pub fn fd_cache_enabled(&self) -> bool {
+        self.fd_cache.is_enabled()
+    }
+
+    /// Returns the file implementing `knob` in the detected cgroup version.
+    fn control_file(&self, knob: Knob) -> &'static str {
+        match (knob, self.v2) {
+            (Knob::Cpus, _) => "cpuset.cpus",
+            (Knob::Mems, _) => "cpuset.mems",
+            (Knob::EffectiveCpus, false) => "cpuset.effective_cpus",
+            (Knob::EffectiveCpus, true) => "cpuset.cpus.effective",
+            (Knob::EffectiveMems, false) => "cpuset.effective_mems",
+            (Knob::EffectiveMems, true) => "cpuset.mems.effective",
+            (Knob::CpuExclusive, _) => "cpuset.cpu_exclusive",
+            (Knob::MemExclusive, _) => "cpuset.mem_exclusive",
+            (Knob::MemHardwall, _) => "cpuset.mem_hardwall",
+            (Knob::MemoryMigrate, _) => "cpuset.memory_migrate",
+            (Knob::MemoryPressure, _) => "cpuset.memory_pressure",
+            (Knob::MemoryPressureEnabled, _) => "cpuset.memory_pressure_enabled",
+            (Knob::MemorySpreadPage, _) => "cpuset.memory_spread_page",
+            (Knob::MemorySpreadSlab, _) => "cpuset.memory_spread_slab",
+            (Knob::SchedLoadBalance, _) => "cpuset.sched_load_balance",
+            (Knob::SchedRelaxDomainLevel, _) => "cpuset.sched_relax_domain_level",
+            (Knob::Partition, _) => "cpuset.cpus.partition",
+            (Knob::ExclusiveCpus, _) => "cpuset.cpus.exclusive",
+            (Knob::EffectiveExclusiveCpus, _) => "cpuset.cpus.exclusive.effective",
+            (Knob::CloneChildren, _) => "cgroup.clone_children",
+        }
+    }
+
+    fn read_cached(&self, f: &str) -> Result<String> {
+        let path = self.control_file_path(f)?;
+        self.fd_cache
+            .read_string(&path, || self.open_path(f, false))
+    }
+
+    /// Opens the control group directory so that `cpuset()` can read all of its files relative
+    /// to it. Returns `None` when the descriptor cache is enabled, which serves the reads instead.
+    fn control_dir(&self) -> Option<ControlDir> {
+        if self.fd_cache_enabled() || self.verify_path().is_err() {
+            return None;
+        }
+        ControlDir::open(self.get_path()).ok()
+    }
+
+    fn read_batched(&self, dir: Option<&ControlDir>, f: &str) -> Result<String> {
+        match dir {
+            Some(dir) => dir.read_string(f),
+            None => self.read_cached(f),
         }
     }
 
     /// Returns the statistics gathered by the kernel for this control group. See the struct for
     /// more information on what information this entails.
     pub fn cpuset(&self) -> CpuSet {
+        let dir = self.control_dir();
+        let read = |knob| self.read_batched(dir.as_ref(), self.control_file(knob));
+        let flag = |knob| read(knob).and_then(|s| parse_bool(&s));
+
         CpuSet {
-            cpu_exclusive: {
-                self.open_path("cpuset.cpu_exclusive", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
-            cpus: {
-                self.open_path("cpuset.cpus", false)
-                    .and_then(read_string_from)
-                    .and_then(parse_range)
-                    .unwrap_or_default()
-            },
+            cpu_exclusive: { flag(Knob::CpuExclusive).unwrap_or(false) },
+            cpus: { read(Knob::Cpus).and_then(parse_range).unwrap_or_default() },
             effective_cpus: {
-                self.open_path("cpuset.effective_cpus", false)
-                    .and_then(read_string_from)
+                read(Knob::EffectiveCpus)
                     .and_then(parse_range)
                     .unwrap_or_default()
             },
             effective_mems: {
-                self.open_path("cpuset.effective_mems", false)
-                    .and_then(read_string_from)
+                read(Knob::EffectiveMems)
                     .and_then(parse_range)
                     .unwrap_or_default()
             },
-            mem_exclusive: {
-                self.open_path("cpuset.mem_exclusive", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
-            mem_hardwall: {
-                self.open_path("cpuset.mem_hardwall", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
-            memory_migrate: {
-                self.open_path("cpuset.memory_migrate", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
+            mem_exclusive: { flag(Knob::MemExclusive).unwrap_or(false) },
+            mem_hardwall: { flag(Knob::MemHardwall).unwrap_or(false) },
+            memory_migrate: { flag(Knob::MemoryMigrate).unwrap_or(false) },
             memory_pressure: {
-                self.open_path("cpuset.memory_pressure", false)
-                    .and_then(read_u64_from)
+                read(Knob::MemoryPressure)
+                    .and_then(|s| parse_from(&s))
                     .unwrap_or(0)
             },
-            memory_pressure_enabled: {
-                self.open_path("cpuset.memory_pressure_enabled", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .ok()
-            },
-            memory_spread_page: {
-                self.open_path("cpuset.memory_spread_page", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
-            memory_spread_slab: {
-                self.open_path("cpuset.memory_spread_slab", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
-            mems: {
-                self.open_path("cpuset.mems", false)
-                    .and_then(read_string_from)
-                    .and_then(parse_range)
-                    .unwrap_or_default()
-            },
-            sched_load_balance: {
-                self.open_path("cpuset.sched_load_balance", false)
-                    .and_then(read_u64_from)
-                    .map(|x| x == 1)
-                    .unwrap_or(false)
-            },
+            memory_pressure_enabled: { flag(Knob::MemoryPressureEnabled).ok() },
+            memory_spread_page: { flag(Knob::MemorySpreadPage).unwrap_or(false) },
+            memory_spread_slab: { flag(Knob::MemorySpreadSlab).unwrap_or(false) },
+            mems: { read(Knob::Mems).and_then(parse_range).unwrap_or_default() },
+            sched_load_balance: { flag(Knob::SchedLoadBalance).unwrap_or(false) },
             sched_relax_domain_level: {
-                self.open_path("cpuset.sched_relax_domain_level", false)
-                    .and_then(read_u64_from)
-                    .unwrap_or(0)
+                read(Knob::SchedRelaxDomainLevel)
+                    .and_then(|s| parse_relax_domain_level(&s))
+                    .unwrap_or_default()
             },
         }
     }
 
+    /// Same as `cpuset()`, but returns the first error encountered instead of substituting a
+    /// default value, e.g. when the `cpuset` controller is not mounted or a file cannot be read.
+    ///
+    /// An empty `cpus` or `mems` file is valid and yields an empty list. Only
+    /// `memory_pressure_enabled`, which exists solely in the root control group, stays lenient.
+    pub fn cpuset_checked(&self) -> Result<CpuSet> {
+        let dir = self.control_dir();
+        let read = |knob| self.read_batched(dir.as_ref(), self.control_file(knob));
+        let flag = |knob| read(knob).and_then(|s| parse_bool(&s));
+        let read_range = |knob| read(knob).and_then(parse_range);
+
+        Ok(CpuSet {
+            cpu_exclusive: flag(Knob::CpuExclusive)?,
+            cpus: read_range(Knob::Cpus)?,
+            effective_cpus: read_range(Knob::EffectiveCpus)?,
+            effective_mems: read_range(Knob::EffectiveMems)?,
+            mem_exclusive: flag(Knob::MemExclusive)?,
+            mem_hardwall: flag(Knob::MemHardwall)?,
+            memory_migrate: flag(Knob::MemoryMigrate)?,
+            memory_pressure: read(Knob::MemoryPressure).and_then(|s| parse_from(&s))?,
+            memory_pressure_enabled: flag(Knob::MemoryPressureEnabled).ok(),
+            memory_spread_page: flag(Knob::MemorySpreadPage)?,
+            memory_spread_slab: flag(Knob::MemorySpreadSlab)?,
+            mems: read_range(Knob::Mems)?,
+            sched_load_balance: flag(Knob::SchedLoadBalance)?,
+            sched_relax_domain_level: read(Knob::SchedRelaxDomainLevel)
+                .and_then(|s| parse_relax_domain_level(&s))?,
+        })
+    }
+
+    /// Same as `cpuset()`, but reads the control files through `tokio::fs` so that the async
+    /// runtime is not blocked.
+    #[cfg(feature = "tokio")]
+    pub async fn cpuset_async(&self) -> CpuSet {
+        let range = |s: &str| parse_range(s.to_string());
+        CpuSet {
+            cpu_exclusive: self
+                .read_async(self.control_file(Knob::CpuExclusive), parse_bool)
+                .await
+                .unwrap_or(false),
+            cpus: self
+                .read_async(self.control_file(Knob::Cpus), range)
+                .await
+                .unwrap_or_default(),
+            effective_cpus: self
+                .read_async(self.control_file(Knob::EffectiveCpus), range)
+                .await
+                .unwrap_or_default(),
+            effective_mems: self
+                .read_async(self.control_file(Knob::EffectiveMems), range)
+                .await
+                .unwrap_or_default(),
+            mem_exclusive: self
+                .read_async(self.control_file(Knob::MemExclusive), parse_bool)
+                .await
+                .unwrap_or(false),
+            mem_hardwall: self
+                .read_async(self.control_file(Knob::MemHardwall), parse_bool)
+                .await
+                .unwrap_or(false),
+            memory_migrate: self
+                .read_async(self.control_file(Knob::MemoryMigrate), parse_bool)
+                .await
+                .unwrap_or(false),
+            memory_pressure: self
+                .read_async(self.control_file(Knob::MemoryPressure), parse_from)
+                .await
+                .unwrap_or(0),
+            memory_pressure_enabled: self
+                .read_async(self.control_file(Knob::MemoryPressureEnabled), parse_bool)
+                .await
+                .ok(),
+            memory_spread_page: self
+                .read_async(self.control_file(Knob::MemorySpreadPage), parse_bool)
+                .await
+                .unwrap_or(false),
+            memory_spread_slab: self
+                .read_async(self.control_file(Knob::MemorySpreadSlab), parse_bool)
+                .await
+                .unwrap_or(false),
+            mems: self
+                .read_async(self.control_file(Knob::Mems), range)
+                .await
+                .unwrap_or_default(),
+            sched_load_balance: self
+                .read_async(self.control_file(Knob::SchedLoadBalance), parse_bool)
+                .await
+                .unwrap_or(false),
+            sched_relax_domain_level: self
+                .read_async(
+                    self.control_file(Knob::SchedRelaxDomainLevel),
+                    parse_relax_domain_level,
+                )
+                .await
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns the CPUs that the tasks in this control group can run on, expanded into a sorted
+    /// list of CPU indices.
+    pub fn cpus_list(&self) -> Result<Vec<u32>> {
+        self.read_cached(self.control_file(Knob::Cpus))
+            .and_then(|s| parse_list(&s))
+    }
+
+    /// Returns the CPUs of the control group as a bitmask, bit `i` being set if CPU `i` is
+    /// allowed.
+    ///
+    /// A mask can only hold CPUs 0 to 127, the call fails if a higher one is allowed. Use
+    /// `cpus_list()` on larger machines.
+    pub fn cpus_mask(&self) -> Result<u128> {
+        self.cpus_list().and_then(|list| list_to_mask(&list))
+    }
+
+    /// Returns the memory nodes that the tasks in this control group can use, expanded into a
+    /// sorted list of node indices.
+    pub fn mems_list(&self) -> Result<Vec<u32>> {
+        self.read_cached(self.control_file(Knob::Mems))
+            .and_then(|s| parse_list(&s))
+    }
+
+    /// Watch the CPUs that the tasks in this control group can actually run on, i.e.
+    /// `cpuset.effective_cpus` (`cpuset.cpus.effective` on cgroup v2).
+    ///
+    /// The returned iterator blocks until the effective CPUs change and yields the new list. It
+    /// ends when the control group is removed.
+    pub fn watch_effective_cpus(&self) -> Result<EffectiveCpuWatcher> {
+        let file_name = self.control_file(Knob::EffectiveCpus);
+        let path = self.get_path().join(file_name);
+        let last = read_list(&path)?;
+
+        Ok(EffectiveCpuWatcher {
+            watch: FileWatch::new(path)?,
+            last,
+        })
+    }
+
+    /// Same as `set_cpus()`, but first checks that the parent control group allows every
+    /// requested CPU. Otherwise `OutOfRange` names the missing CPUs, where the kernel would only
+    /// return `EINVAL`.
+    pub fn set_cpus_checked(&self, cpus: &str) -> Result<()> {
+        let file_name = self.control_file(Knob::EffectiveCpus);
+        self.check_parent_allows(file_name, cpus)?;
+        self.set_cpus(cpus)
+    }
+
+    /// Same as `set_mems()`, but first checks that the parent control group allows every
+    /// requested memory node, see `set_cpus_checked()`.
+    pub fn set_mems_checked(&self, mems: &str) -> Result<()> {
+        let file_name = self.control_file(Knob::EffectiveMems);
+        self.check_parent_allows(file_name, mems)?;
+        self.set_mems(mems)
+    }
+
+    fn check_parent_allows(&self, file_name: &str, requested: &str) -> Result<()> {
+        let parent = match self.get_path().parent() {
+            Some(parent) if !self.is_root() => parent,
+            // The root control group has every CPU and memory node.
+            _ => return Ok(()),
+        };
+        check_list_allows(&parent.join(file_name), requested)
+    }
+
+    /// Set both the CPUs and the memory nodes of the control group, so that tasks can be attached
+    /// to it right away.
+    ///
+    /// Both lists are checked against the parent before anything is written, as with
+    /// `set_cpus_checked()` and `set_mems_checked()`. The memory nodes are written first; if
+    /// writing the CPUs then fails they are restored. Finally the effective CPUs and memory
+    /// nodes are read back, and an empty one fails with `InvalidOperation` naming its file.
+    pub fn configure(&self, cpus: &str, mems: &str) -> Result<()> {
+        let cpus_file = self.control_file(Knob::EffectiveCpus);
+        let mems_file = self.control_file(Knob::EffectiveMems);
+        self.check_parent_allows(cpus_file, cpus)?;
+        self.check_parent_allows(mems_file, mems)?;
+
+        let old_mems = self
+            .open_path(self.control_file(Knob::Mems), false)
+            .and_then(read_string_from)?;
+        self.set_mems(mems)?;
+        if let Err(e) = self.set_cpus(cpus) {
+            if let Err(restore) = self.set_mems(&old_mems) {
+                warn!(
+                    "failed to restore cpuset.mems to {:?}: {}",
+                    old_mems, restore
+                );
+            }
+            return Err(e);
+        }
+
+        for file_name in [cpus_file, mems_file] {
+            let path = self.get_path().join(file_name);
+            if read_list(&path)?.is_empty() {
+                return Err(Error::new(InvalidOperation).with_path(path));
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as `set_cpus()`, but takes a list of CPU indices which is collapsed into the range
+    /// syntax before writing, i.e. `[0, 1, 2, 5]` is written as `0-2,5`.
+    pub fn set_cpus_from_list(&self, cpus: &[u32]) -> Result<()> {
+        self.set_cpus(&format_list(cpus))
+    }
+
+    /// Same as `set_cpus()`, but takes a bitmask of CPUs as returned by `cpus_mask()`, and so is
+    /// limited to CPUs 0 to 127. Use `set_cpus_from_list()` on larger machines.
+    pub fn set_cpus_mask(&self, mask: u128) -> Result<()> {
+        self.set_cpus_from_list(&mask_to_list(mask))
+    }
+
+    /// Same as `set_mems()`, but takes a list of memory node indices.
+    pub fn set_mems_from_list(&self, mems: &[u32]) -> Result<()> {
+        self.set_mems(&format_list(mems))
+    }
+
+    /// Returns the partition type of this control group.
+    ///
+    /// This is only available on cgroup v2. An invalid partition, i.e. one the kernel could not
+    /// set up as requested, is reported as an error.
+    pub fn partition(&self) -> Result<PartitionType> {
+        self.open_path(self.control_file(Knob::Partition), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_partition(&s))
+    }
+
+    /// Set the partition type of this control group.
+    ///
+    /// Note that the kernel accepts the write even when the partition cannot be honoured, use
+    /// `partition()` to check that the requested type is in effect.
+    pub fn set_partition(&self, partition: PartitionType) -> Result<()> {
+        let value = partition.as_str();
+        let file_name = self.control_file(Knob::Partition);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, value).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), value.to_string()), e)
+            })
+        })
+    }
+
+    /// Request the CPUs that this control group wants to own exclusively, i.e. the CPUs it may
+    /// take when it becomes a `root` or `isolated` partition.
+    ///
+    /// Syntax is the same as with `set_cpus()`. Fails with `NotSupported` where
+    /// `cpuset.cpus.exclusive` is missing, as on cgroup v1 and older v2 kernels.
+    pub fn set_exclusive_cpus(&self, cpus: &str) -> Result<()> {
+        let file_name = self.control_file(Knob::ExclusiveCpus);
+        validate_list(cpus)?;
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, cpus).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), cpus.to_string()), e)
+            })
+        })
+    }
+
+    /// Returns the CPUs requested via `set_exclusive_cpus()`.
+    pub fn exclusive_cpus(&self) -> Result<Vec<(u64, u64)>> {
+        self.open_path(self.control_file(Knob::ExclusiveCpus), false)
+            .and_then(read_string_from)
+            .and_then(parse_range)
+    }
+
+    /// Returns the CPUs the kernel actually granted this control group for exclusive use, found
+    /// in `cpuset.cpus.exclusive.effective`.
+    pub fn effective_exclusive_cpus(&self) -> Result<Vec<(u64, u64)>> {
+        self.open_path(self.control_file(Knob::EffectiveExclusiveCpus), false)
+            .and_then(read_string_from)
+            .and_then(parse_range)
+    }
+
     /// Control whether the CPUs selected via `set_cpus()` should be exclusive to this control
     /// group or not.
     pub fn set_cpu_exclusive(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.cpu_exclusive", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.cpu_exclusive".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.cpu_exclusive".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::CpuExclusive), b)
+    }
+
+    async_twin! {
+        /// Same as `set_cpu_exclusive()`, but writes through `tokio::fs` so that the async runtime
+        /// is not blocked.
+        set_cpu_exclusive_async(&self, b: bool),
+        write self.control_file(Knob::CpuExclusive), if b { "1" } else { "0" }
     }
 
     /// Control whether the memory nodes selected via `set_memss()` should be exclusive to this control
     /// group or not.
     pub fn set_mem_exclusive(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.mem_exclusive", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.mem_exclusive".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.mem_exclusive".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::MemExclusive), b)
+    }
+
+    async_twin! {
+        /// Same as `set_mem_exclusive()`, but writes through `tokio::fs` so that the async runtime
+        /// is not blocked.
+        set_mem_exclusive_async(&self, b: bool),
+        write self.control_file(Knob::MemExclusive), if b { "1" } else { "0" }
+    }
+
+    /// Control whether new children of this control group start with a copy of its `cpus` and
+    /// `mems`, instead of empty ones.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn set_clone_children(&self, b: bool) -> Result<()> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.write_bool(self.control_file(Knob::CloneChildren), b)
+    }
+
+    /// Returns whether new children of this control group inherit its `cpus` and `mems`.
+    pub fn clone_children(&self) -> Result<bool> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.read_bool(self.control_file(Knob::CloneChildren))
     }
 
     /// Set the CPUs that the tasks in this control group can run on.
     ///
     /// Syntax is a comma separated list of CPUs, with an additional extension that ranges can
-    /// be represented via dashes.
+    /// be represented via dashes. A malformed list fails with `ParseError` before anything is
+    /// written.
     pub fn set_cpus(&self, cpus: &str) -> Result<()> {
-        self.open_path("cpuset.cpus", true).and_then(|mut file| {
-            file.write_all(cpus.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("cpuset.cpus".to_string(), cpus.to_string()), e)
+        validate_list(cpus)?;
+        let file_name = self.control_file(Knob::Cpus);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, cpus).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), cpus.to_string()), e)
             })
         })
     }
@@ -413,185 +1106,209 @@ impl CpuSetController {
     ///
     /// Syntax is the same as with `set_cpus()`.
     pub fn set_mems(&self, mems: &str) -> Result<()> {
-        self.open_path("cpuset.mems", true).and_then(|mut file| {
-            file.write_all(mems.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("cpuset.mems".to_string(), mems.to_string()), e)
+        let file_name = self.control_file(Knob::Mems);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, mems).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), mems.to_string()), e)
             })
         })
     }
 
+    async_twin! {
+        /// Same as `set_cpus()`, but writes through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        set_cpus_async(&self, cpus: &str),
+        write self.control_file(Knob::Cpus), {
+            validate_list(cpus)?;
+            cpus
+        }
+    }
+
+    async_twin! {
+        /// Same as `set_mems()`, but writes through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        set_mems_async(&self, mems: &str),
+        write self.control_file(Knob::Mems), mems
+    }
+
     /// Controls whether the control group should be "hardwalled", i.e., whether kernel allocations
     /// should exclusively use the memory nodes set via `set_mems()`.
     ///
     /// Note that some kernel allocations, most notably those that are made in interrupt handlers
     /// may disregard this.
     pub fn set_hardwall(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.mem_hardwall", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.mem_hardwall".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.mem_hardwall".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::MemHardwall), b)
+    }
+
+    async_twin! {
+        /// Same as `set_hardwall()`, but writes through `tokio::fs` so that the async runtime is
+        /// not blocked.
+        set_hardwall_async(&self, b: bool),
+        write self.control_file(Knob::MemHardwall), if b { "1" } else { "0" }
     }
 
     /// Controls whether the kernel should attempt to rebalance the load between the CPUs specified in the
     /// `cpus` field of this control group.
+    ///
+    /// Fails with `NotSupported` where `cpuset.sched_load_balance` is missing, as on cgroup v2.
+    ///
+    /// Note that a control group inherits `0` from a parent with load balancing disabled. The
+    /// kernel still accepts enabling it here, which is how a machine is split into separate
+    /// scheduling domains.
     pub fn set_load_balancing(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.sched_load_balance", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.sched_load_balance".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.sched_load_balance".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::SchedLoadBalance), b)
+    }
+
+    async_twin! {
+        /// Same as `set_load_balancing()`, but writes through `tokio::fs` so that the async runtime
+        /// is not blocked.
+        set_load_balancing_async(&self, b: bool),
+        write self.control_file(Knob::SchedLoadBalance), if b { "1" } else { "0" }
     }
 
-    /// Contorl how much effort the kernel should invest in rebalacing the control group.
+    /// Control how much effort the kernel should invest in rebalancing the control group.
     ///
-    /// See @CpuSet 's similar field for more information.
-    pub fn set_rebalance_relax_domain_level(&self, i: i64) -> Result<()> {
-        self.open_path("cpuset.sched_relax_domain_level", true)
-            .and_then(|mut file| {
-                file.write_all(i.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("cpuset.sched_relax_domain_level".to_string(), i.to_string()),
-                        e,
-                    )
-                })
+    /// See `RelaxDomainLevel` for the meaning of each level. This fails like
+    /// `set_load_balancing()`, and with `OutOfRange` if the level is deeper than the scheduling
+    /// domains of the machine.
+    pub fn set_rebalance_relax_domain_level(&self, level: RelaxDomainLevel) -> Result<()> {
+        let file_name = self.control_file(Knob::SchedRelaxDomainLevel);
+        let i = i64::from(level);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &i.to_string()).map_err(|e| match e.raw_os_error() {
+                Some(libc::EINVAL) => Error::with_cause(
+                    OutOfRange(
+                        self.get_path().join(file_name).display().to_string(),
+                        i.to_string(),
+                    ),
+                    e,
+                ),
+                _ => Error::with_cause(WriteFailed(file_name.to_string(), i.to_string()), e),
             })
+        })
+    }
+
+    /// Enable load balancing for this control group, after checking that its ancestors let it
+    /// take effect.
+    ///
+    /// The ancestors are walked up to the nearest one with load balancing enabled, without
+    /// changing any of them. The walk stops below the root of the hierarchy, which may have load
+    /// balancing disabled to let its children define the scheduling domains. If the parent has
+    /// it disabled, this fails with `InvalidOperation` naming `cpuset.sched_load_balance` of the
+    /// topmost disabled ancestor below that one, the first that has to be enabled for the
+    /// setting to be inherited again.
+    pub fn enable_load_balancing_recursive(&self) -> Result<()> {
+        let mut blocking = None;
+        for dir in self.get_path().ancestors().skip(1) {
+            if dir == self.get_base()
+                || !dir.starts_with(self.get_base())
+                || read_load_balance(dir)?
+            {
+                break;
+            }
+            blocking = Some(dir);
+        }
+        if let Some(dir) = blocking {
+            return Err(Error::new(InvalidOperation)
+                .with_path(dir.join(self.control_file(Knob::SchedLoadBalance))));
+        }
+        self.set_load_balancing(true)
     }
 
     /// Control whether when using `set_mems()` the existing memory used by the tasks should be
     /// migrated over to the now-selected nodes.
     pub fn set_memory_migration(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.memory_migrate", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.memory_migrate".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.memory_migrate".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::MemoryMigrate), b)
+    }
+
+    async_twin! {
+        /// Same as `set_memory_migration()`, but writes through `tokio::fs` so that the async
+        /// runtime is not blocked.
+        set_memory_migration_async(&self, b: bool),
+        write self.control_file(Knob::MemoryMigrate), if b { "1" } else { "0" }
+    }
+
+    /// Move the control group to the memory nodes `mems`, migrating the memory its tasks already
+    /// use along.
+    ///
+    /// On cgroup v1 this enables `cpuset.memory_migrate` first, cgroup v2 always migrates. The
+    /// effective memory nodes are read back afterwards, and if they lack any of the requested
+    /// nodes `OutOfRange` names the missing ones.
+    pub fn migrate_mems(&self, mems: &str) -> Result<()> {
+        let file_name = self.control_file(Knob::EffectiveMems);
+        if !self.v2 {
+            self.set_memory_migration(true)?;
+        }
+        self.set_mems(mems)?;
+        check_list_allows(&self.get_path().join(file_name), mems)
     }
 
     /// Control whether filesystem buffers should be evenly split across the nodes selected via
     /// `set_mems()`.
     pub fn set_memory_spread_page(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.memory_spread_page", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.memory_spread_page".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.memory_spread_page".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::MemorySpreadPage), b)
+    }
+
+    async_twin! {
+        /// Same as `set_memory_spread_page()`, but writes through `tokio::fs` so that the async
+        /// runtime is not blocked.
+        set_memory_spread_page_async(&self, b: bool),
+        write self.control_file(Knob::MemorySpreadPage), if b { "1" } else { "0" }
     }
 
     /// Control whether the kernel's slab cache for file I/O should be evenly split across the
     /// nodes selected via `set_mems()`.
     pub fn set_memory_spread_slab(&self, b: bool) -> Result<()> {
-        self.open_path("cpuset.memory_spread_slab", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.memory_spread_slab".to_string(), "1".to_string()),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed("cpuset.memory_spread_slab".to_string(), "0".to_string()),
-                            e,
-                        )
-                    })
-                }
-            })
+        self.write_bool(self.control_file(Knob::MemorySpreadSlab), b)
+    }
+
+    async_twin! {
+        /// Same as `set_memory_spread_slab()`, but writes through `tokio::fs` so that the async
+        /// runtime is not blocked.
+        set_memory_spread_slab_async(&self, b: bool),
+        write self.control_file(Knob::MemorySpreadSlab), if b { "1" } else { "0" }
+    }
+
+    /// Write the changes computed by `diff()`, in order, stopping at the first failure.
+    pub fn apply_diff(&self, changes: &[CpuSetChange]) -> Result<()> {
+        changes.iter().try_for_each(|change| match change {
+            CpuSetChange::Cpus(cpus) => self.set_cpus(&format_ranges(cpus)),
+            CpuSetChange::Mems(mems) => self.set_mems(&format_ranges(mems)),
+            CpuSetChange::CpuExclusive(b) => self.set_cpu_exclusive(*b),
+            CpuSetChange::MemExclusive(b) => self.set_mem_exclusive(*b),
+            CpuSetChange::MemHardwall(b) => self.set_hardwall(*b),
+            CpuSetChange::MemoryMigrate(b) => self.set_memory_migration(*b),
+            CpuSetChange::MemoryPressureEnabled(b) => self.set_enable_memory_pressure(*b),
+            CpuSetChange::MemorySpreadPage(b) => self.set_memory_spread_page(*b),
+            CpuSetChange::MemorySpreadSlab(b) => self.set_memory_spread_slab(*b),
+            CpuSetChange::SchedLoadBalance(b) => self.set_load_balancing(*b),
+            CpuSetChange::SchedRelaxDomainLevel(level) => {
+                self.set_rebalance_relax_domain_level(*level)
+            }
+        })
     }
 
     /// Control whether the kernel should collect information to calculate memory pressure for
     /// control groups.
     ///
-    /// Note: This will fail with `InvalidOperation` if the current congrol group is not the root
-    /// control group.
+    /// Note: This will fail with `InvalidOperation` if the current control group is not the root
+    /// control group, and with `NotSupported` where `cpuset.memory_pressure_enabled` is missing,
+    /// as on cgroup v2.
     pub fn set_enable_memory_pressure(&self, b: bool) -> Result<()> {
-        if !self.path_exists("cpuset.memory_pressure_enabled") {
-            return Err(Error::new(InvalidOperation));
-        }
-        self.open_path("cpuset.memory_pressure_enabled", true)
-            .and_then(|mut file| {
-                if b {
-                    file.write_all(b"1").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed(
-                                "cpuset.memory_pressure_enabled".to_string(),
-                                "1".to_string(),
-                            ),
-                            e,
-                        )
-                    })
-                } else {
-                    file.write_all(b"0").map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed(
-                                "cpuset.memory_pressure_enabled".to_string(),
-                                "0".to_string(),
-                            ),
-                            e,
-                        )
-                    })
-                }
-            })
+        let file_name = self.control_file(Knob::MemoryPressureEnabled);
+        if !self.is_root() {
+            return Err(Error::new(InvalidOperation).with_path(self.get_path().join(file_name)));
+        }
+        self.write_bool(file_name, b)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cpuset;
+    use crate::error::ErrorKind;
+    use crate::util::tests::TempDir;
+
     #[test]
     fn test_parse_range() {
         let test_cases = vec![
@@ -602,7 +1319,7 @@ mod tests {
             "1,2,3,4".to_string(),
             "1-5,6-7,8-9".to_string(),
         ];
-        let expecteds = vec![
+        let expecteds = [
             vec![(1, 1), (2, 2), (4, 6), (9, 9)],
             vec![],
             vec![(1, 1)],
@@ -618,4 +1335,466 @@ mod tests {
             assert_eq!(range.unwrap(), expecteds[i]);
         }
     }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(cpuset::parse_list("").unwrap(), Vec::<u32>::new());
+        assert_eq!(cpuset::parse_list("3").unwrap(), vec![3]);
+        assert_eq!(cpuset::parse_list("0-3,7\n").unwrap(), vec![0, 1, 2, 3, 7]);
+        assert_eq!(cpuset::parse_list("0-3,5\n").unwrap(), vec![0, 1, 2, 3, 5]);
+        assert_eq!(cpuset::parse_list("8,0-1,1").unwrap(), vec![0, 1, 8]);
+
+        for case in ["0-", "a-b", "1,,2", "5-3", "0-1-2"] {
+            let err = cpuset::parse_list(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_control_file() {
+        let v1 = cpuset::CpuSetController::new("/sys/fs/cgroup/cpuset".into(), false);
+        let v2 = cpuset::CpuSetController::new("/sys/fs/cgroup".into(), true);
+        assert_eq!(v1.control_file(cpuset::Knob::Cpus), "cpuset.cpus");
+        assert_eq!(v2.control_file(cpuset::Knob::Cpus), "cpuset.cpus");
+        assert_eq!(
+            v1.control_file(cpuset::Knob::EffectiveCpus),
+            "cpuset.effective_cpus"
+        );
+        assert_eq!(
+            v2.control_file(cpuset::Knob::EffectiveCpus),
+            "cpuset.cpus.effective"
+        );
+        assert_eq!(
+            v2.control_file(cpuset::Knob::EffectiveMems),
+            "cpuset.mems.effective"
+        );
+    }
+
+    #[test]
+    fn test_validate_list() {
+        cpuset::validate_list("0-3,5,7-8\n").unwrap();
+        cpuset::validate_list("").unwrap();
+
+        for (case, token) in [
+            ("0--3", "0--3"),
+            ("0,", ""),
+            (",1", ""),
+            ("0-", "0-"),
+            ("1,a", "a"),
+            ("3-1", "3-1"),
+            ("0 1", "0 1"),
+        ] {
+            let err = cpuset::validate_list(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+            let cause = std::error::Error::source(&err).unwrap().to_string();
+            assert!(
+                cause.contains(&format!("invalid token {:?}", token)),
+                "{:?}: {}",
+                case,
+                cause
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_partition() {
+        use cpuset::PartitionType;
+
+        assert_eq!(
+            cpuset::parse_partition("member\n").unwrap(),
+            PartitionType::Member
+        );
+        assert_eq!(
+            cpuset::parse_partition("root").unwrap(),
+            PartitionType::Root
+        );
+        assert_eq!(
+            cpuset::parse_partition("isolated").unwrap(),
+            PartitionType::Isolated
+        );
+
+        for case in [
+            "root invalid",
+            "isolated invalid (Cpu list in cpuset.cpus not exclusive)",
+        ] {
+            let err = cpuset::parse_partition(case).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::Common(_)), "{:?}", case);
+        }
+
+        for case in ["", "ROOT", "root valid"] {
+            let err = cpuset::parse_partition(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_exclusive_cpus() {
+        let dir = TempDir::new("exclusive_cpus");
+        let cpuset = cpuset::CpuSetController::new(dir.to_path_buf(), true);
+
+        let err = cpuset.set_exclusive_cpus("0-1").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+        let err = cpuset.exclusive_cpus().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+        let err = cpuset.effective_exclusive_cpus().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+
+        std::fs::write(dir.join("cpuset.cpus.exclusive"), "\n").unwrap();
+        std::fs::write(dir.join("cpuset.cpus.exclusive.effective"), "\n").unwrap();
+        assert_eq!(cpuset.exclusive_cpus().unwrap(), vec![]);
+        assert_eq!(cpuset.effective_exclusive_cpus().unwrap(), vec![]);
+
+        cpuset.set_exclusive_cpus("0-1,3").unwrap();
+        assert_eq!(cpuset.exclusive_cpus().unwrap(), vec![(0, 1), (3, 3)]);
+        let err = cpuset.set_exclusive_cpus("0-").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+
+        // The kernel grants the requested CPUs once the control group becomes a partition root.
+        std::fs::write(dir.join("cpuset.cpus.exclusive.effective"), "0-1\n").unwrap();
+        assert_eq!(cpuset.effective_exclusive_cpus().unwrap(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_format_list() {
+        assert_eq!(cpuset::format_list(&[]), "");
+        assert_eq!(cpuset::format_list(&[0, 1, 2, 5]), "0-2,5");
+        assert_eq!(cpuset::format_list(&[7, 3, 4, 3]), "3-4,7");
+
+        let list = cpuset::parse_list("0-2,5,9-11").unwrap();
+        assert_eq!(cpuset::format_list(&list), "0-2,5,9-11");
+    }
+
+    #[test]
+    fn test_cpus_mask() {
+        assert_eq!(cpuset::list_to_mask(&[]).unwrap(), 0);
+        assert_eq!(cpuset::mask_to_list(0), Vec::<u32>::new());
+
+        for (list, mask) in [
+            ("0", 0b1),
+            ("0-3,7", 0b1000_1111),
+            ("1,64-65", 1 << 1 | 1 << 64 | 1 << 65),
+            ("127", 1 << 127),
+            ("0-127", u128::MAX),
+        ] {
+            let indices = cpuset::parse_list(list).unwrap();
+            assert_eq!(cpuset::list_to_mask(&indices).unwrap(), mask, "{:?}", list);
+            assert_eq!(
+                cpuset::format_list(&cpuset::mask_to_list(mask)),
+                list,
+                "{:#x}",
+                mask
+            );
+        }
+
+        assert!(cpuset::list_to_mask(&[0, 128]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_cpuset_serde_round_trip() {
+        let set = cpuset::CpuSet {
+            cpu_exclusive: true,
+            cpus: vec![(0, 3), (6, 6)],
+            effective_cpus: vec![(0, 3)],
+            effective_mems: vec![(0, 0)],
+            mem_exclusive: false,
+            mem_hardwall: false,
+            memory_migrate: true,
+            memory_pressure: 0,
+            memory_pressure_enabled: None,
+            memory_spread_page: false,
+            memory_spread_slab: false,
+            mems: vec![(0, 0)],
+            sched_load_balance: true,
+            sched_relax_domain_level: cpuset::RelaxDomainLevel::SameCore,
+        };
+
+        let json = serde_json::to_string(&set).unwrap();
+        let back: cpuset::CpuSet = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        assert_eq!(back.cpus, vec![(0, 3), (6, 6)]);
+    }
+
+    #[test]
+    fn test_enable_memory_pressure_missing_file() {
+        let dir = TempDir::new("enable_memory_pressure_missing_file");
+        let root = cpuset::CpuSetController::new(dir.to_path_buf(), true);
+        let err = root.set_enable_memory_pressure(true).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+    }
+
+    #[test]
+    fn test_load_balance_under_disabled_parent() {
+        use crate::{Controller, ControllerInternal};
+        use cpuset::RelaxDomainLevel;
+
+        let dir = TempDir::new("load_balance_under_disabled_parent");
+        let root = cpuset::CpuSetController::new(dir.to_path_buf(), false);
+        let parent = root.nested("parent").unwrap();
+        let child = parent.nested("child").unwrap();
+        std::fs::create_dir_all(child.get_path()).unwrap();
+
+        // Nothing to write to, as on cgroup v2.
+        let err = child.set_load_balancing(true).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+
+        for cpuset in [&root, &parent, &child] {
+            std::fs::write(cpuset.get_path().join("cpuset.sched_load_balance"), "1\n").unwrap();
+        }
+        root.set_load_balancing(false).unwrap();
+        parent.set_load_balancing(false).unwrap();
+
+        // Enabling it below a parent with load balancing disabled is how the machine is
+        // partitioned, so it is not refused.
+        child.set_load_balancing(true).unwrap();
+        assert!(child.read_bool("cpuset.sched_load_balance").unwrap());
+        std::fs::write(
+            child.get_path().join("cpuset.sched_relax_domain_level"),
+            "-1\n",
+        )
+        .unwrap();
+        child
+            .set_rebalance_relax_domain_level(RelaxDomainLevel::SameCore)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_enable_load_balancing_recursive() {
+        use crate::{Controller, ControllerInternal};
+
+        let dir = TempDir::new("enable_load_balancing_recursive");
+        let root = cpuset::CpuSetController::new(dir.to_path_buf(), false);
+        let a = root.nested("a").unwrap();
+        let b = a.nested("b").unwrap();
+        let c = b.nested("c").unwrap();
+        let child = c.nested("child").unwrap();
+        std::fs::create_dir_all(child.get_path()).unwrap();
+        for (cpuset, flag) in [(&root, "0"), (&a, "0"), (&b, "1"), (&c, "0"), (&child, "0")] {
+            std::fs::write(cpuset.get_path().join("cpuset.sched_load_balance"), flag).unwrap();
+        }
+        let read =
+            |cpuset: &cpuset::CpuSetController| cpuset.read_bool("cpuset.sched_load_balance");
+
+        // The walk stops at the nearest ancestor with load balancing enabled, `b`, and names
+        // the topmost disabled one below it. Nothing is written.
+        let err = child.enable_load_balancing_recursive().unwrap_err();
+        let flag = c.get_path().join("cpuset.sched_load_balance");
+        assert_eq!(err.kind(), &ErrorKind::InvalidOperation);
+        assert_eq!(err.path(), Some(flag.as_path()));
+        assert!(!read(&child).unwrap());
+        assert!(!read(&c).unwrap());
+
+        // The root of the hierarchy is not part of the walk.
+        std::fs::write(b.get_path().join("cpuset.sched_load_balance"), "0").unwrap();
+        let err = child.enable_load_balancing_recursive().unwrap_err();
+        let flag = a.get_path().join("cpuset.sched_load_balance");
+        assert_eq!(err.path(), Some(flag.as_path()));
+
+        // A direct child of a disabled root may enable load balancing.
+        a.enable_load_balancing_recursive().unwrap();
+        assert!(read(&a).unwrap());
+        assert!(!read(&root).unwrap());
+
+        // With the parent enabled, disabled ancestors further up do not matter.
+        std::fs::write(c.get_path().join("cpuset.sched_load_balance"), "1").unwrap();
+        child.enable_load_balancing_recursive().unwrap();
+        assert!(read(&child).unwrap());
+        assert!(!read(&b).unwrap());
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_writes_are_logged() {
+        use std::sync::Mutex;
+
+        static RECORDS: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+        struct TestLogger;
+        impl log::Log for TestLogger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                let message = record.args().to_string();
+                RECORDS.lock().unwrap().push((record.level(), message));
+            }
+            fn flush(&self) {}
+        }
+        static LOGGER: TestLogger = TestLogger;
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let dir = TempDir::new("writes_are_logged");
+        let path = dir.join("cpuset.cpus");
+        std::fs::write(&path, "").unwrap();
+        let cpuset = cpuset::CpuSetController::new(dir.to_path_buf(), false);
+        cpuset.set_cpus("0-1").unwrap();
+
+        // A write that fails after the file could be opened.
+        let full = dir.join("full");
+        std::fs::create_dir_all(&full).unwrap();
+        std::os::unix::fs::symlink("/dev/full", full.join("cpuset.cpus")).unwrap();
+        let cpuset = cpuset::CpuSetController::new(full, false);
+        cpuset.set_cpus("0").unwrap_err();
+
+        let records = RECORDS.lock().unwrap();
+        let expected = format!("wrote \"0-1\" to {}", path.display());
+        assert!(
+            records.contains(&(log::Level::Debug, expected)),
+            "{:?}",
+            records
+        );
+        assert!(
+            records
+                .iter()
+                .any(|(level, message)| *level == log::Level::Warn
+                    && message.starts_with("failed to write \"0\" to /dev/full")),
+            "{:?}",
+            records
+        );
+    }
+
+    #[test]
+    fn test_fd_cache_reuses_descriptors() {
+        let dir = TempDir::new("fd_cache_reuses_descriptors");
+        std::fs::write(dir.join("cpuset.cpus"), "0-1\n").unwrap();
+        let cpuset = cpuset::CpuSetController::new(dir.to_path_buf(), false);
+        let path = dir.join("cpuset.cpus");
+
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![0, 1]);
+        assert_eq!(cpuset.fd_cache.cached_fd(&path), None);
+
+        cpuset.set_fd_cache(true);
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![0, 1]);
+        let fd = cpuset.fd_cache.cached_fd(&path).unwrap();
+        for _ in 0..1000 {
+            assert_eq!(cpuset.cpus_list().unwrap(), vec![0, 1]);
+        }
+        assert_eq!(cpuset.fd_cache.cached_fd(&path), Some(fd));
+
+        // The cached descriptor sees content written after it was opened.
+        std::fs::write(dir.join("cpuset.cpus"), "3\n").unwrap();
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![3]);
+        assert_eq!(cpuset.fd_cache.cached_fd(&path), Some(fd));
+
+        // A clone does not share the descriptors.
+        assert!(!cpuset.clone().fd_cache_enabled());
+
+        // A failed read drops the descriptor. Reading offset 0 of /proc/self/mem fails with EIO.
+        let mems = dir.join("cpuset.mems");
+        std::os::unix::fs::symlink("/proc/self/mem", &mems).unwrap();
+        let err = cpuset.mems_list().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::ReadFailed(mems.display().to_string())
+        );
+        assert_eq!(cpuset.fd_cache.cached_fd(&mems), None);
+        assert_eq!(cpuset.fd_cache.cached_fd(&path), Some(fd));
+    }
+
+    #[test]
+    fn test_online_cpus() {
+        assert!(cpuset::online_cpus().unwrap().contains(&0));
+        if std::path::Path::new("/sys/devices/system/node/online").exists() {
+            assert!(cpuset::online_mems().unwrap().contains(&0));
+        }
+    }
+
+    #[test]
+    fn test_cpuset_eq_and_default() {
+        let default = cpuset::CpuSet::default();
+        assert!(default.cpus.is_empty());
+        assert!(default.mems.is_empty());
+        assert!(!default.cpu_exclusive);
+        assert_eq!(default.memory_pressure_enabled, None);
+
+        let set = cpuset::CpuSet {
+            cpus: vec![(0, 3)],
+            mems: vec![(0, 0)],
+            sched_load_balance: true,
+            ..Default::default()
+        };
+        assert_eq!(set, set.clone());
+        assert_ne!(set, default);
+
+        let mut other = set.clone();
+        other.cpus = vec![(0, 1)];
+        assert_ne!(set, other);
+    }
+
+    #[test]
+    fn test_cpuset_diff() {
+        let current = cpuset::CpuSet {
+            cpus: vec![(0, 3)],
+            mems: vec![(0, 0)],
+            sched_load_balance: true,
+            ..Default::default()
+        };
+        let mut desired = current.clone();
+        assert!(cpuset::diff(&current, &desired).is_empty());
+
+        desired.cpus = vec![(0, 1), (3, 3)];
+        desired.effective_cpus = vec![(0, 0)];
+        assert_eq!(
+            cpuset::diff(&current, &desired),
+            vec![cpuset::CpuSetChange::Cpus(vec![(0, 1), (3, 3)])]
+        );
+
+        desired.sched_load_balance = false;
+        desired.memory_pressure_enabled = Some(true);
+        assert_eq!(
+            cpuset::diff(&current, &desired),
+            vec![
+                cpuset::CpuSetChange::Cpus(vec![(0, 1), (3, 3)]),
+                cpuset::CpuSetChange::SchedLoadBalance(false),
+                cpuset::CpuSetChange::MemoryPressureEnabled(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cpuset_apply_diff() {
+        let dir = TempDir::new("cpuset_apply_diff");
+        std::fs::write(dir.join("cpuset.cpus"), "").unwrap();
+        std::fs::write(dir.join("cpuset.cpu_exclusive"), "0\n").unwrap();
+        let cpuset = cpuset::CpuSetController::new(dir.to_path_buf(), false);
+
+        cpuset
+            .apply_diff(&[
+                cpuset::CpuSetChange::Cpus(vec![(0, 1), (3, 3)]),
+                cpuset::CpuSetChange::CpuExclusive(true),
+            ])
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("cpuset.cpus")).unwrap(),
+            "0-1,3"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("cpuset.cpu_exclusive")).unwrap(),
+            "1"
+        );
+        assert!(!dir.join("cpuset.mems").exists());
+    }
+
+    #[test]
+    fn test_relax_domain_level() {
+        use std::convert::TryFrom;
+
+        for i in -1..=5 {
+            let level = cpuset::RelaxDomainLevel::try_from(i).unwrap();
+            assert_eq!(i64::from(level), i);
+        }
+        assert_eq!(
+            cpuset::RelaxDomainLevel::try_from(-1).unwrap(),
+            cpuset::RelaxDomainLevel::SystemDefault
+        );
+        assert_eq!(i64::from(cpuset::RelaxDomainLevel::default()), -1);
+        for i in [-2, 6, 100].iter() {
+            let err = cpuset::RelaxDomainLevel::try_from(*i).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError);
+        }
+        assert_eq!(
+            cpuset::parse_relax_domain_level("-1\n").unwrap(),
+            cpuset::RelaxDomainLevel::SystemDefault
+        );
+    }
 }
@@ -7,17 +7,21 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/devices.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/devices.txt)
-use std::io::{Read, Write};
+use std::fmt;
+use std::io::Read;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use log::*;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
+use crate::util::write_to;
 
 use crate::{
-    ControllIdentifier, ControllerInternal, Controllers, DeviceResource, DeviceResources,
-    Resources, Subsystem,
+    ControllIdentifier, ControllerInternal, Controllers, DeviceId, DevicePattern, DeviceResource,
+    DeviceResources, Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `devices` subsystem of a Cgroup.
@@ -31,7 +35,7 @@ pub struct DevicesController {
 }
 
 /// An enum holding the different types of devices that can be manipulated using this controller.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[cfg_attr(
     feature = "serde",
     derive(serde::Serialize, serde::Deserialize),
@@ -39,6 +43,7 @@ pub struct DevicesController {
 )]
 pub enum DeviceType {
     /// The rule applies to all devices.
+    #[default]
     All,
     /// The rule only applies to character devices.
     Char,
@@ -46,12 +51,6 @@ pub enum DeviceType {
     Block,
 }
 
-impl Default for DeviceType {
-    fn default() -> Self {
-        DeviceType::All
-    }
-}
-
 impl DeviceType {
     /// Convert a DeviceType into the character that the kernel recognizes.
     #[allow(clippy::should_implement_trait, clippy::wrong_self_convention)]
@@ -150,6 +149,64 @@ impl DevicePermissions {
     }
 }
 
+/// A rule of the `devices` controller, in the format written to `devices.allow` and
+/// `devices.deny` and listed by `devices.list`, e.g. `c 1:3 rwm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceRule {
+    /// The type of the devices the rule applies to.
+    pub devtype: DeviceType,
+    /// The devices the rule applies to, either number may be a wildcard.
+    pub device: DevicePattern,
+    /// The access the rule allows or denies.
+    pub access: Vec<DevicePermissions>,
+}
+
+impl fmt::Display for DeviceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = self
+            .access
+            .iter()
+            .map(DevicePermissions::to_char)
+            .collect::<String>();
+        write!(f, "{} {} {}", self.devtype.to_char(), self.device, access)
+    }
+}
+
+impl FromStr for DeviceRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 || parts[0].len() != 1 || !DevicePermissions::is_valid(parts[2]) {
+            return Err(Error::new(ParseError));
+        }
+
+        Ok(DeviceRule {
+            devtype: DeviceType::from_char(parts[0].chars().next())
+                .ok_or_else(|| Error::new(ParseError))?,
+            device: parts[1].parse()?,
+            access: DevicePermissions::from_str(parts[2])?,
+        })
+    }
+}
+
+/// Convert the `-1` "any" convention of `DeviceResource` into a `DeviceId`.
+fn to_device_id(n: i64) -> DeviceId {
+    if n < 0 {
+        DeviceId::Wildcard
+    } else {
+        DeviceId::Number(n as u64)
+    }
+}
+
+fn from_device_id(id: DeviceId) -> i64 {
+    match id {
+        DeviceId::Wildcard => -1,
+        DeviceId::Number(n) => n as i64,
+    }
+}
+
 impl ControllerInternal for DevicesController {
     fn control_type(&self) -> Controllers {
         Controllers::Devices
@@ -170,9 +227,9 @@ impl ControllerInternal for DevicesController {
 
         for i in &res.devices {
             if i.allow {
-                let _ = self.allow_device(i.devtype, i.major, i.minor, &i.access);
+                self.allow_device(i.devtype, i.major, i.minor, &i.access)?;
             } else {
-                let _ = self.deny_device(i.devtype, i.major, i.minor, &i.access);
+                self.deny_device(i.devtype, i.major, i.minor, &i.access)?;
             }
         }
 
@@ -188,15 +245,9 @@ impl ControllIdentifier for DevicesController {
 
 impl<'a> From<&'a Subsystem> for &'a DevicesController {
     fn from(sub: &'a Subsystem) -> &'a DevicesController {
-        unsafe {
-            match sub {
-                Subsystem::Devices(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Devices(c) => c,
+            _ => panic!("Subsystem is not a DevicesController"),
         }
     }
 }
@@ -210,6 +261,22 @@ impl DevicesController {
         }
     }
 
+    /// Constructs a `DevicesController` for the root of the `devices` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn mounted() -> Result<Self> {
+        match hierarchies::controller_root(Controllers::Devices)? {
+            (root, false) => Ok(Self::new(root)),
+            _ => Err(Error::new(CgroupVersion)),
+        }
+    }
+
+    /// Reads the allowed devices back into `res.devices`, see `Cgroup::snapshot()`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        res.devices.devices = self.allowed_devices().unwrap_or_default();
+    }
+
     /// Allow a (possibly, set of) device(s) to be used by the tasks in the control group.
     ///
     /// When `-1` is passed as `major` or `minor`, the kernel interprets that value as "any",
@@ -221,25 +288,13 @@ impl DevicesController {
         minor: i64,
         perm: &[DevicePermissions],
     ) -> Result<()> {
-        let perms = perm
-            .iter()
-            .map(DevicePermissions::to_char)
-            .collect::<String>();
-        let minor = if minor == -1 {
-            "*".to_string()
-        } else {
-            format!("{}", minor)
-        };
-        let major = if major == -1 {
-            "*".to_string()
-        } else {
-            format!("{}", major)
-        };
-        let final_str = format!("{} {}:{} {}", devtype.to_char(), major, minor, perms);
-        self.open_path("devices.allow", true).and_then(|mut file| {
-            file.write_all(final_str.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("devices.allow".to_string(), final_str), e)
-            })
+        self.allow_rule(&DeviceRule {
+            devtype,
+            device: DevicePattern {
+                major: to_device_id(major),
+                minor: to_device_id(minor),
+            },
+            access: perm.to_vec(),
         })
     }
 
@@ -254,71 +309,106 @@ impl DevicesController {
         minor: i64,
         perm: &[DevicePermissions],
     ) -> Result<()> {
-        let perms = perm
-            .iter()
-            .map(DevicePermissions::to_char)
-            .collect::<String>();
-        let minor = if minor == -1 {
-            "*".to_string()
-        } else {
-            format!("{}", minor)
-        };
-        let major = if major == -1 {
-            "*".to_string()
-        } else {
-            format!("{}", major)
-        };
-        let final_str = format!("{} {}:{} {}", devtype.to_char(), major, minor, perms);
-        self.open_path("devices.deny", true).and_then(|mut file| {
-            file.write_all(final_str.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("devices.deny".to_string(), final_str), e)
-            })
+        self.deny_rule(&DeviceRule {
+            devtype,
+            device: DevicePattern {
+                major: to_device_id(major),
+                minor: to_device_id(minor),
+            },
+            access: perm.to_vec(),
         })
     }
 
-    /// Get the current list of allowed devices.
-    pub fn allowed_devices(&self) -> Result<Vec<DeviceResource>> {
+    /// Allow the control group's tasks the access described by `rule`.
+    pub fn allow_rule(&self, rule: &DeviceRule) -> Result<()> {
+        self.write_rule("devices.allow", rule)
+    }
+
+    /// Deny the control group's tasks the access described by `rule`.
+    pub fn deny_rule(&self, rule: &DeviceRule) -> Result<()> {
+        self.write_rule("devices.deny", rule)
+    }
+
+    fn write_rule(&self, file_name: &str, rule: &DeviceRule) -> Result<()> {
+        let content = rule.to_string();
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &content)
+                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), content), e))
+        })
+    }
+
+    /// Get the rules of the devices the control group's tasks are allowed to access, as listed by
+    /// `devices.list`.
+    pub fn rules(&self) -> Result<Vec<DeviceRule>> {
         self.open_path("devices.list", false).and_then(|mut file| {
             let mut s = String::new();
-            let res = file.read_to_string(&mut s);
-            match res {
-                Ok(_) => {
-                    s.lines().fold(Ok(Vec::new()), |acc, line| {
-                        let ls = line.to_string().split(|c| c == ' ' || c == ':').map(|x| x.to_string()).collect::<Vec<String>>();
-                        if acc.is_err() || ls.len() != 4 {
-                            error!("allowed_devices: acc: {:?}, ls: {:?}", acc, ls);
-                            Err(Error::new(ParseError))
-                        } else {
-                            let devtype = DeviceType::from_char(ls[0].chars().next());
-                            let mut major = ls[1].parse::<i64>();
-                            let mut minor = ls[2].parse::<i64>();
-                            if major.is_err() && ls[1] == "*" {
-                                major = Ok(-1);
-                            }
-                            if minor.is_err() && ls[2] == "*" {
-                                minor = Ok(-1);
-                            }
-                            if devtype.is_none() || major.is_err() || minor.is_err() || !DevicePermissions::is_valid(&ls[3]) {
-                                error!("allowed_devices: acc: {:?}, ls: {:?}, devtype: {:?}, major {:?} minor {:?} ls3 {:?}",
-                                         acc, ls, devtype, major, minor, &ls[3]);
-                                Err(Error::new(ParseError))
-                            } else {
-                                let access = DevicePermissions::from_str(&ls[3])?;
-                                let mut acc = acc.unwrap();
-                                acc.push(DeviceResource {
-                                    allow: true,
-                                    devtype: devtype.unwrap(),
-                                    major: major.unwrap(),
-                                    minor: minor.unwrap(),
-                                    access,
-                                });
-                                Ok(acc)
-                            }
-                        }
-                    })
-                },
-                Err(e) => Err(Error::with_cause(ReadFailed("devices.list".to_string()), e)),
-            }
+            file.read_to_string(&mut s)
+                .map_err(|e| Error::with_cause(ReadFailed("devices.list".to_string()), e))?;
+
+            s.lines()
+                .map(|line| {
+                    line.parse::<DeviceRule>()
+                        .inspect_err(|_| error!("rules: unable to parse {:?}", line))
+                })
+                .collect()
         })
     }
+
+    /// Get the current list of allowed devices.
+    pub fn allowed_devices(&self) -> Result<Vec<DeviceResource>> {
+        Ok(self
+            .rules()?
+            .into_iter()
+            .map(|rule| DeviceResource {
+                allow: true,
+                devtype: rule.devtype,
+                major: from_device_id(rule.device.major),
+                minor: from_device_id(rule.device.minor),
+                access: rule.access,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices::{DevicePermissions, DeviceRule, DeviceType};
+    use crate::error::ErrorKind;
+    use crate::{DeviceId, DeviceNumber, DevicePattern};
+
+    #[test]
+    fn test_device_rule() {
+        let rule = "c 1:3 rwm".parse::<DeviceRule>().unwrap();
+        assert_eq!(
+            rule,
+            DeviceRule {
+                devtype: DeviceType::Char,
+                device: DeviceNumber::new(1, 3).into(),
+                access: DevicePermissions::all(),
+            }
+        );
+
+        for case in ["c 1:3 rwm", "a *:* rwm", "c 136:* rw", "b *:0 m"] {
+            assert_eq!(case.parse::<DeviceRule>().unwrap().to_string(), case);
+        }
+        assert_eq!(
+            "c 136:* rw".parse::<DeviceRule>().unwrap().device,
+            DevicePattern {
+                major: DeviceId::Number(136),
+                minor: DeviceId::Wildcard,
+            }
+        );
+
+        for case in [
+            "",
+            "c 1:3",
+            "x 1:3 rwm",
+            "cc 1:3 rwm",
+            "c 1:3 rwx",
+            "c 1 rwm",
+        ] {
+            let err = case.parse::<DeviceRule>().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
 }
@@ -6,6 +6,7 @@
 
 use std::error::Error as StdError;
 use std::fmt;
+use std::path::{Path, PathBuf};
 
 /// The different types of errors that can occur while manipulating control groups.
 #[derive(thiserror::Error, Debug, Eq, PartialEq)]
@@ -24,10 +25,25 @@ pub enum ErrorKind {
     #[error("unable to read a control group file {0}")]
     ReadFailed(String),
 
+    /// The control file could not be opened because of missing privileges or a read-only mount.
+    #[error(
+        "permission denied on {0}, root privileges or a delegated control group may be required"
+    )]
+    PermissionDenied(String),
+
     /// An error occured while trying to remove a control group.
     #[error("unable to remove a control group")]
     RemoveFailed,
 
+    /// The control group could not be removed because tasks are still attached to it.
+    #[error("control group {0} is busy, tasks are still attached")]
+    Busy(String),
+
+    /// A value, the second field, outside the range accepted by the control file named by the
+    /// first, such as CPUs or memory nodes not allowed by the parent control group.
+    #[error("{1} is outside of the range allowed by {0}")]
+    OutOfRange(String, String),
+
     /// An error occured while trying to parse a value from a control group file.
     ///
     /// In the future, there will be some information attached to this field.
@@ -63,6 +79,15 @@ pub enum ErrorKind {
     #[error("subsystems is empty")]
     SubsystemsEmpty,
 
+    /// The kernel does not support the requested control file, e.g. because it was built without
+    /// the option providing it.
+    #[error("the control file is not supported by the kernel")]
+    NotSupported,
+
+    /// The subsystem does not hold the requested controller.
+    #[error("the subsystem is not the requested controller")]
+    WrongSubsystem,
+
     /// An unknown error has occured.
     #[error("an unknown error")]
     Other,
@@ -72,20 +97,24 @@ pub enum ErrorKind {
 pub struct Error {
     kind: ErrorKind,
     cause: Option<Box<dyn StdError + Send + Sync>>,
+    path: Option<PathBuf>,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.kind)?;
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path.display())?;
+        }
         if let Some(cause) = &self.cause {
-            write!(f, "{} caused by: {:?}", &self.kind, cause)
-        } else {
-            write!(f, "{}", &self.kind)
+            write!(f, " caused by: {:?}", cause)?;
         }
+        Ok(())
     }
 }
 
 impl StdError for Error {
-    fn cause(&self) -> Option<&dyn StdError> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
         #[allow(clippy::manual_map)]
         match self.cause {
             Some(ref x) => Some(&**x),
@@ -99,10 +128,15 @@ impl Error {
         Self {
             kind: ErrorKind::Common(s),
             cause: None,
+            path: None,
         }
     }
     pub(crate) fn new(kind: ErrorKind) -> Self {
-        Self { kind, cause: None }
+        Self {
+            kind,
+            cause: None,
+            path: None,
+        }
     }
 
     pub(crate) fn with_cause<E>(kind: ErrorKind, cause: E) -> Self
@@ -112,12 +146,87 @@ impl Error {
         Self {
             kind,
             cause: Some(Box::new(cause)),
+            path: None,
         }
     }
 
+    /// Records the control file the error is about, for kinds that do not name it themselves.
+    pub(crate) fn with_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+
+    /// Returns the control file the error is about, if known.
+    ///
+    /// For `ReadFailed`, `WriteFailed` and `PermissionDenied` this is the file named by the kind.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.kind {
+            ErrorKind::ReadFailed(path)
+            | ErrorKind::WriteFailed(path, _)
+            | ErrorKind::PermissionDenied(path) => Some(Path::new(path)),
+            _ => self.path.as_deref(),
+        }
+    }
+
+    /// Returns the OS error code of the underlying I/O error, if any.
+    pub(crate) fn raw_os_error(&self) -> Option<i32> {
+        self.cause
+            .as_ref()
+            .and_then(|c| c.downcast_ref::<std::io::Error>())
+            .and_then(|e| e.raw_os_error())
+    }
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_error_as_std_error() {
+        let io_err = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let err: Box<dyn StdError> = Box::new(Error::with_cause(
+            ErrorKind::ReadFailed("cpu.shares".to_string()),
+            io_err,
+        ));
+
+        assert!(err
+            .to_string()
+            .starts_with("unable to read a control group file cpu.shares caused by"));
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        let err: Box<dyn StdError> = Box::new(Error::new(ErrorKind::NotSupported));
+        assert_eq!(
+            err.to_string(),
+            "the control file is not supported by the kernel"
+        );
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_error_path() {
+        let err = Error::new(ErrorKind::ParseError).with_path("/sys/fs/cgroup/cpu/cpu.shares");
+        assert_eq!(
+            err.to_string(),
+            "unable to parse control group file (/sys/fs/cgroup/cpu/cpu.shares)"
+        );
+        assert_eq!(err.path(), Some(Path::new("/sys/fs/cgroup/cpu/cpu.shares")));
+
+        let err = Error::new(ErrorKind::WriteFailed(
+            "pids.max".to_string(),
+            "x".to_string(),
+        ));
+        assert_eq!(err.path(), Some(Path::new("pids.max")));
+        assert_eq!(Error::new(ErrorKind::NotSupported).path(), None);
+    }
+}
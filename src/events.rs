@@ -39,6 +39,12 @@ pub fn notify_memory_pressure(key: &str, dir: &Path, level: &str) -> Result<Rece
     register_memory_event(key, dir, "memory.pressure_level", level)
 }
 
+// notify_usage_threshold returns channel on which you can expect an event each time the
+// memory usage of the control group crosses threshold, in either direction.
+pub fn notify_usage_threshold(key: &str, dir: &Path, threshold: u64) -> Result<Receiver<String>> {
+    register_memory_event(key, dir, "memory.usage_in_bytes", &threshold.to_string())
+}
+
 fn register_memory_event(
     key: &str,
     cg_dir: &Path,
@@ -8,13 +8,17 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/freezer-subsystem.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/freezer-subsystem.txt)
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
+use crate::util::write_to;
 
-use crate::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
+use crate::{
+    ControllIdentifier, Controller, ControllerInternal, Controllers, Resources, Subsystem,
+};
 
 /// A controller that allows controlling the `freezer` subsystem of a Cgroup.
 ///
@@ -32,6 +36,7 @@ pub struct FreezerController {
 }
 
 /// The current state of the control group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FreezerState {
     /// The processes in the control group are _not_ frozen.
@@ -56,6 +61,10 @@ impl ControllerInternal for FreezerController {
         &self.base
     }
 
+    fn is_v2(&self) -> bool {
+        self.v2
+    }
+
     fn apply(&self, _res: &Resources) -> Result<()> {
         Ok(())
     }
@@ -69,15 +78,9 @@ impl ControllIdentifier for FreezerController {
 
 impl<'a> From<&'a Subsystem> for &'a FreezerController {
     fn from(sub: &'a Subsystem) -> &'a FreezerController {
-        unsafe {
-            match sub {
-                Subsystem::Freezer(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Freezer(c) => c,
+            _ => panic!("Subsystem is not a FreezerController"),
         }
     }
 }
@@ -92,6 +95,13 @@ impl FreezerController {
         }
     }
 
+    /// Constructs a `FreezerController` for the root of the `freezer` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::Freezer)?;
+        Ok(Self::new(root, v2))
+    }
+
     /// Freezes the processes in the control group.
     pub fn freeze(&self) -> Result<()> {
         let mut file_name = "freezer.state";
@@ -102,7 +112,7 @@ impl FreezerController {
         }
 
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref())
+            write_to(&mut file, &content)
                 .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), content), e))
         })
     }
@@ -116,31 +126,110 @@ impl FreezerController {
             content = "0".to_string();
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(content.as_ref())
+            write_to(&mut file, &content)
                 .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), content), e))
         })
     }
 
     /// Retrieve the state of processes in the control group.
+    ///
+    /// On cgroup v2, `cgroup.freeze` only holds the requested state, so the `frozen` key of
+    /// `cgroup.events` tells whether the freeze has completed. A control group that was asked to
+    /// freeze but is not frozen yet is reported as `Freezing`.
     pub fn state(&self) -> Result<FreezerState> {
-        let mut file_name = "freezer.state";
         if self.v2 {
-            file_name = "cgroup.freeze";
+            return self.state_v2();
         }
-        self.open_path(file_name, false).and_then(|mut file| {
+        self.open_path("freezer.state", false).and_then(|mut file| {
             let mut s = String::new();
             let res = file.read_to_string(&mut s);
             match res {
                 Ok(_) => match s.trim() {
                     "FROZEN" => Ok(FreezerState::Frozen),
                     "THAWED" => Ok(FreezerState::Thawed),
-                    "1" => Ok(FreezerState::Frozen),
-                    "0" => Ok(FreezerState::Thawed),
                     "FREEZING" => Ok(FreezerState::Freezing),
                     _ => Err(Error::new(ParseError)),
                 },
-                Err(e) => Err(Error::with_cause(ReadFailed(file_name.to_string()), e)),
+                Err(e) => Err(Error::with_cause(
+                    ReadFailed("freezer.state".to_string()),
+                    e,
+                )),
             }
         })
     }
+
+    /// Returns whether this control group itself was asked to freeze, i.e. `freeze()` was called
+    /// on it, regardless of the state of its ancestors.
+    ///
+    /// This is only available on cgroup v1, and fails with `NotSupported` on kernels without
+    /// `freezer.self_freezing`.
+    pub fn self_freezing(&self) -> Result<bool> {
+        self.read_freezing("freezer.self_freezing")
+    }
+
+    /// Returns whether the control group is frozen because one of its ancestors was frozen. Such
+    /// a control group stays frozen until that ancestor is thawed, whatever `thaw()` is called on.
+    ///
+    /// This is only available on cgroup v1, and fails with `NotSupported` on kernels without
+    /// `freezer.parent_freezing`.
+    pub fn parent_freezing(&self) -> Result<bool> {
+        self.read_freezing("freezer.parent_freezing")
+    }
+
+    fn read_freezing(&self, file_name: &str) -> Result<bool> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.read_bool(file_name)
+    }
+
+    fn state_v2(&self) -> Result<FreezerState> {
+        if self.events()?.frozen {
+            // Also the case when an ancestor is frozen, whatever `cgroup.freeze` says.
+            return Ok(FreezerState::Frozen);
+        }
+        match self.read_bool("cgroup.freeze")? {
+            true => Ok(FreezerState::Freezing),
+            false => Ok(FreezerState::Thawed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::freezer::{FreezerController, FreezerState};
+    use crate::util::tests::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_freezing_missing() {
+        let dir = TempDir::new("freezing_missing");
+        let freezer = FreezerController::new(dir.to_path_buf(), false);
+
+        let err = freezer.parent_freezing().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+
+        fs::write(dir.join("freezer.parent_freezing"), "1\n").unwrap();
+        assert!(freezer.parent_freezing().unwrap());
+    }
+
+    #[test]
+    fn test_freezer_state_v2() {
+        let dir = TempDir::new("freezer_state_v2");
+        let freezer = FreezerController::new(dir.to_path_buf(), true);
+
+        let cases = [
+            ("0", "populated 1\nfrozen 0\n", FreezerState::Thawed),
+            ("1", "populated 1\nfrozen 0\n", FreezerState::Freezing),
+            ("1", "populated 1\nfrozen 1\n", FreezerState::Frozen),
+            // Frozen through an ancestor.
+            ("0", "populated 1\nfrozen 1\n", FreezerState::Frozen),
+        ];
+        for (freeze, events, state) in cases.iter() {
+            fs::write(dir.join("cgroup.freeze"), freeze).unwrap();
+            fs::write(dir.join("cgroup.events"), events).unwrap();
+            assert_eq!(freezer.state().unwrap(), *state, "{} {:?}", freeze, events);
+        }
+    }
 }
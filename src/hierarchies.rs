@@ -9,6 +9,7 @@
 //! Currently, we only support the cgroupv1 hierarchy, but in the future we will add support for
 //! the Unified Hierarchy.
 
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -19,6 +20,8 @@ use crate::cpu::CpuController;
 use crate::cpuacct::CpuAcctController;
 use crate::cpuset::CpuSetController;
 use crate::devices::DevicesController;
+use crate::error::ErrorKind::*;
+use crate::error::*;
 use crate::freezer::FreezerController;
 use crate::hugetlb::HugeTlbController;
 use crate::memory::MemController;
@@ -101,6 +104,145 @@ pub fn mountinfo_self() -> Vec<Mountinfo> {
     }
 }
 
+/// Returns where each cgroup v1 subsystem is mounted for the current process.
+///
+/// Co-mounted subsystems, e.g. `cpu,cpuacct`, all map to the shared mount point.
+pub fn mountpoints() -> Result<HashMap<Controllers, PathBuf>> {
+    let path = "/proc/self/mountinfo";
+    fs::read_to_string(path)
+        .map(|content| parse_mountpoints(&content))
+        .map_err(|e| Error::with_cause(ReadFailed(path.to_string()), e))
+}
+
+fn parse_mountpoints(content: &str) -> HashMap<Controllers, PathBuf> {
+    let mut h = HashMap::new();
+    for mi in content.lines().filter_map(parse_mountinfo_for_line) {
+        if mi.fs_type.0 != "cgroup" {
+            continue;
+        }
+        for c in mi
+            .super_opts
+            .iter()
+            .filter_map(|o| o.parse::<Controllers>().ok())
+        {
+            h.entry(c).or_insert_with(|| mi.mount_point.clone());
+        }
+    }
+    h
+}
+
+/// Returns the root a controller of type `controller` should be constructed with, and whether
+/// it is a cgroup v2 one.
+///
+/// That is the mount point of the cgroup v1 hierarchy the controller is attached to, falling back
+/// to `UNIFIED_MOUNTPOINT` when cgroup v2 is mounted there.
+pub fn controller_root(controller: Controllers) -> Result<(PathBuf, bool)> {
+    let path = "/proc/self/mountinfo";
+    let content =
+        fs::read_to_string(path).map_err(|e| Error::with_cause(ReadFailed(path.to_string()), e))?;
+    find_controller_root(&content, &controller)
+        .ok_or_else(|| Error::from_string(format!("{} controller is not mounted", controller)))
+}
+
+fn find_controller_root(content: &str, controller: &Controllers) -> Option<(PathBuf, bool)> {
+    if let Some(root) = parse_mountpoints(content).remove(controller) {
+        return Some((root, false));
+    }
+    content
+        .lines()
+        .filter_map(parse_mountinfo_for_line)
+        .find(|mi| mi.fs_type.0 == "cgroup2" && mi.mount_point == Path::new(UNIFIED_MOUNTPOINT))
+        .map(|mi| (mi.mount_point, true))
+}
+
+/// Constructs one controller per cgroup subsystem mounted below `root`, e.g. `/sys/fs/cgroup`,
+/// as listed in `/proc/self/mountinfo`.
+///
+/// Co-mounted cgroup v1 subsystems, e.g. `cpu,cpuacct`, each yield their own `Subsystem` on the
+/// shared mount point. If `root` is a cgroup v2 mount instead, the controllers listed in its
+/// `cgroup.controllers` are returned, along with the freezer.
+pub fn all_mounted(root: &Path) -> Vec<Subsystem> {
+    let content = fs::read_to_string("/proc/self/mountinfo").unwrap_or_default();
+    let unified_controllers = || fs::read_to_string(root.join("cgroup.controllers")).ok();
+    subsystems_from_mountinfo(&content, root, unified_controllers)
+}
+
+fn subsystems_from_mountinfo<F>(
+    content: &str,
+    root: &Path,
+    unified_controllers: F,
+) -> Vec<Subsystem>
+where
+    F: FnOnce() -> Option<String>,
+{
+    let unified = content
+        .lines()
+        .filter_map(parse_mountinfo_for_line)
+        .any(|mi| mi.fs_type.0 == "cgroup2" && mi.mount_point == root);
+    if unified {
+        let controllers = unified_controllers().unwrap_or_default();
+        return controllers
+            .split_whitespace()
+            .chain(std::iter::once("freezer"))
+            .filter_map(|name| match name {
+                "io" => Some(Controllers::BlkIo),
+                name => name.parse().ok(),
+            })
+            .filter_map(|c| new_subsystem(c, root.to_path_buf(), true))
+            .collect();
+    }
+
+    let mut mountpoints = parse_mountpoints(content);
+    mountpoints.retain(|_, mount_point| mount_point.starts_with(root));
+    // Same order as `V1::subsystems()`, blkio has to come before memory.
+    [
+        Controllers::BlkIo,
+        Controllers::Mem,
+        Controllers::Pids,
+        Controllers::CpuSet,
+        Controllers::CpuAcct,
+        Controllers::Cpu,
+        Controllers::Devices,
+        Controllers::Freezer,
+        Controllers::NetCls,
+        Controllers::PerfEvent,
+        Controllers::NetPrio,
+        Controllers::HugeTlb,
+        Controllers::Rdma,
+        Controllers::Systemd,
+    ]
+    .iter()
+    .filter_map(|c| {
+        let mount_point = mountpoints.remove(c)?;
+        new_subsystem(c.clone(), mount_point, false)
+    })
+    .collect()
+}
+
+/// Constructs the controller of type `controller` rooted at `root`, `None` for the ones cgroup v2
+/// does not support.
+fn new_subsystem(controller: Controllers, root: PathBuf, v2: bool) -> Option<Subsystem> {
+    let sub = match controller {
+        Controllers::Pids => Subsystem::Pid(PidController::new(root, v2)),
+        Controllers::Mem => Subsystem::Mem(MemController::new(root, v2)),
+        Controllers::CpuSet => Subsystem::CpuSet(CpuSetController::new(root, v2)),
+        Controllers::Cpu => Subsystem::Cpu(CpuController::new(root, v2)),
+        Controllers::Freezer => Subsystem::Freezer(FreezerController::new(root, v2)),
+        Controllers::BlkIo => Subsystem::BlkIo(BlkIoController::new(root, v2)),
+        Controllers::HugeTlb => Subsystem::HugeTlb(HugeTlbController::new(root, v2)),
+        // The remaining controllers are only supported on cgroup v1.
+        _ if v2 => return None,
+        Controllers::CpuAcct => Subsystem::CpuAcct(CpuAcctController::new(root)),
+        Controllers::Devices => Subsystem::Devices(DevicesController::new(root)),
+        Controllers::NetCls => Subsystem::NetCls(NetClsController::new(root)),
+        Controllers::PerfEvent => Subsystem::PerfEvent(PerfEventController::new(root)),
+        Controllers::NetPrio => Subsystem::NetPrio(NetPrioController::new(root)),
+        Controllers::Rdma => Subsystem::Rdma(RdmaController::new(root)),
+        Controllers::Systemd => Subsystem::Systemd(SystemdController::new(root, false)),
+    };
+    Some(sub)
+}
+
 /// The standard, original cgroup implementation. Often referred to as "cgroupv1".
 #[derive(Debug, Clone)]
 pub struct V1 {
@@ -387,4 +529,108 @@ mod tests {
             assert_eq!(info, mi.1)
         }
     }
+
+    #[test]
+    fn test_parse_mountpoints() {
+        let mountinfo = "\
+25 30 0:23 / /sys/fs/cgroup ro,nosuid,nodev,noexec shared:9 - tmpfs tmpfs ro,mode=755
+26 25 0:24 / /sys/fs/cgroup/unified rw,nosuid,nodev,noexec,relatime shared:10 - cgroup2 cgroup2 rw,nsdelegate
+27 25 0:25 / /sys/fs/cgroup/systemd rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,xattr,name=systemd
+30 25 0:28 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:14 - cgroup cgroup rw,cpu,cpuacct
+31 25 0:29 / /sys/fs/cgroup/memory rw,nosuid,nodev,noexec,relatime shared:15 - cgroup cgroup rw,memory
+32 25 0:30 / /sys/fs/cgroup/net_cls,net_prio rw,nosuid,nodev,noexec,relatime shared:16 - cgroup cgroup rw,net_cls,net_prio
+";
+        let h = parse_mountpoints(mountinfo);
+        assert_eq!(h.len(), 6);
+        assert_eq!(
+            h[&Controllers::Cpu],
+            PathBuf::from("/sys/fs/cgroup/cpu,cpuacct")
+        );
+        assert_eq!(h[&Controllers::CpuAcct], h[&Controllers::Cpu]);
+        assert_eq!(h[&Controllers::Mem], PathBuf::from("/sys/fs/cgroup/memory"));
+        assert_eq!(h[&Controllers::NetPrio], h[&Controllers::NetCls]);
+        assert_eq!(
+            h[&Controllers::Systemd],
+            PathBuf::from("/sys/fs/cgroup/systemd")
+        );
+        assert!(!h.contains_key(&Controllers::CpuSet));
+    }
+
+    #[test]
+    fn test_subsystems_from_mountinfo() {
+        let mountinfo = "\
+25 30 0:23 / /sys/fs/cgroup ro,nosuid,nodev,noexec shared:9 - tmpfs tmpfs ro,mode=755
+30 25 0:28 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:14 - cgroup cgroup rw,cpu,cpuacct
+41 40 0:29 / /mnt/memory rw,nosuid,nodev,noexec,relatime shared:15 - cgroup cgroup rw,memory
+";
+        let no_controllers = || -> Option<String> { unreachable!() };
+        let subs =
+            subsystems_from_mountinfo(mountinfo, Path::new("/sys/fs/cgroup"), no_controllers);
+        let subs = subs
+            .iter()
+            .map(|sub| {
+                let c = sub.to_controller();
+                (c.control_type(), c.path().to_path_buf(), c.v2())
+            })
+            .collect::<Vec<_>>();
+        let shared = PathBuf::from("/sys/fs/cgroup/cpu,cpuacct");
+        assert_eq!(
+            subs,
+            vec![
+                (Controllers::CpuAcct, shared.clone(), false),
+                (Controllers::Cpu, shared, false),
+            ]
+        );
+
+        let unified = "\
+25 30 0:23 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:4 - cgroup2 cgroup2 rw,nsdelegate
+";
+        let controllers = || Some("cpuset cpu io memory pids rdma\n".to_string());
+        let subs = subsystems_from_mountinfo(unified, Path::new("/sys/fs/cgroup"), controllers);
+        let types = subs
+            .iter()
+            .map(|sub| sub.to_controller().control_type())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                Controllers::CpuSet,
+                Controllers::Cpu,
+                Controllers::BlkIo,
+                Controllers::Mem,
+                Controllers::Pids,
+                Controllers::Freezer,
+            ]
+        );
+        assert!(subs.iter().all(|sub| sub.to_controller().v2()));
+    }
+
+    #[test]
+    fn test_find_controller_root() {
+        let hybrid = "\
+25 30 0:23 / /sys/fs/cgroup ro,nosuid,nodev,noexec shared:9 - tmpfs tmpfs ro,mode=755
+26 25 0:24 / /sys/fs/cgroup/unified rw,nosuid,nodev,noexec,relatime shared:10 - cgroup2 cgroup2 rw,nsdelegate
+30 25 0:28 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:14 - cgroup cgroup rw,cpu,cpuacct
+33 25 0:31 / /sys/fs/cgroup/cpuset rw,nosuid,nodev,noexec,relatime shared:17 - cgroup cgroup rw,cpuset
+";
+        assert_eq!(
+            find_controller_root(hybrid, &Controllers::CpuSet),
+            Some((PathBuf::from("/sys/fs/cgroup/cpuset"), false))
+        );
+        assert_eq!(
+            find_controller_root(hybrid, &Controllers::CpuAcct),
+            Some((PathBuf::from("/sys/fs/cgroup/cpu,cpuacct"), false))
+        );
+        // cgroup v2 mounted elsewhere than /sys/fs/cgroup is not used as a fallback.
+        assert_eq!(find_controller_root(hybrid, &Controllers::Mem), None);
+
+        let unified = "\
+25 30 0:23 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:4 - cgroup2 cgroup2 rw,nsdelegate
+";
+        assert_eq!(
+            find_controller_root(unified, &Controllers::CpuSet),
+            Some((PathBuf::from("/sys/fs/cgroup"), true))
+        );
+        assert_eq!(find_controller_root("", &Controllers::CpuSet), None);
+    }
 }
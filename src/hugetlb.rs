@@ -9,15 +9,17 @@
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/hugetlb.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/hugetlb.txt)
 use log::warn;
-use std::io::Write;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
-use crate::{flat_keyed_to_vec, read_u64_from};
+use crate::flat_keyed_to_vec;
+use crate::hierarchies;
+use crate::util::{read_u64_from, write_to};
 
 use crate::{
-    ControllIdentifier, ControllerInternal, Controllers, HugePageResources, Resources, Subsystem,
+    ControllIdentifier, ControllerInternal, Controllers, HugePageResource, HugePageResources,
+    Resources, Subsystem,
 };
 
 /// A controller that allows controlling the `hugetlb` subsystem of a Cgroup.
@@ -55,7 +57,7 @@ impl ControllerInternal for HugeTlbController {
         let res: &HugePageResources = &res.hugepages;
 
         for i in &res.limits {
-            let _ = self.set_limit_in_bytes(&i.size, i.limit);
+            self.set_limit_in_bytes(&i.size, i.limit)?;
             if self.limit_in_bytes(&i.size)? != i.limit {
                 return Err(Error::new(Other));
             }
@@ -73,15 +75,9 @@ impl ControllIdentifier for HugeTlbController {
 
 impl<'a> From<&'a Subsystem> for &'a HugeTlbController {
     fn from(sub: &'a Subsystem) -> &'a HugeTlbController {
-        unsafe {
-            match sub {
-                Subsystem::HugeTlb(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::HugeTlb(c) => c,
+            _ => panic!("Subsystem is not a HugeTlbController"),
         }
     }
 }
@@ -98,6 +94,26 @@ impl HugeTlbController {
         }
     }
 
+    /// Constructs a `HugeTlbController` for the root of the `hugetlb` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::HugeTlb)?;
+        Ok(Self::new(root, v2))
+    }
+
+    /// Reads the limit of every hugepage size the controller exposes back into `res.hugepages`,
+    /// see `Cgroup::snapshot()`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        res.hugepages.limits = self
+            .sizes()
+            .into_iter()
+            .filter_map(|size| {
+                let limit = self.limit_in_bytes(&size).ok()?;
+                Some(HugePageResource { size, limit })
+            })
+            .collect();
+    }
+
     /// Whether the system supports `hugetlb_size` hugepages.
     pub fn size_supported(&self, hugetlb_size: &str) -> bool {
         for s in &self.sizes {
@@ -112,6 +128,34 @@ impl HugeTlbController {
         self.sizes.clone()
     }
 
+    /// Discover the hugepage sizes this control group can limit by scanning its directory for
+    /// `hugetlb.<size>.limit_in_bytes` (or `hugetlb.<size>.max` on cgroup v2) files.
+    ///
+    /// Unlike `get_sizes()`, which lists the sizes the system supports, this reflects what the
+    /// controller actually exposes. The sizes are sorted, e.g. `["1GB", "2MB"]`.
+    pub fn sizes(&self) -> Vec<String> {
+        let suffix = if self.v2 { ".max" } else { ".limit_in_bytes" };
+        let mut sizes = match fs::read_dir(self.get_path()) {
+            Ok(dir) => dir
+                .filter_map(|e| {
+                    let name = e.ok()?.file_name().into_string().ok()?;
+                    let size = name.strip_prefix("hugetlb.")?.strip_suffix(suffix)?;
+                    // skip e.g. hugetlb.2MB.rsvd.max
+                    if size.contains('.') {
+                        return None;
+                    }
+                    Some(size.to_string())
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("readdir error: {:?}", e);
+                Vec::new()
+            }
+        };
+        sizes.sort();
+        sizes
+    }
+
     fn failcnt_v2(&self, hugetlb_size: &str) -> Result<u64> {
         self.open_path(&format!("hugetlb.{}.events", hugetlb_size), false)
             .and_then(flat_keyed_to_vec)
@@ -170,7 +214,7 @@ impl HugeTlbController {
             file_name = format!("hugetlb.{}.max", hugetlb_size);
         }
         self.open_path(&file_name, true).and_then(|mut file| {
-            file.write_all(limit.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &limit.to_string()).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
             })
         })
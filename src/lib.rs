@@ -4,13 +4,26 @@
 // SPDX-License-Identifier: Apache-2.0 or MIT
 //
 
+//! Native Rust library for managing control groups under Linux, both v1 and v2.
+//!
+//! # Async API
+//!
+//! With the `tokio` feature, the controllers gain `*_async` twins of the getters and setters that
+//! read or write a single control file, e.g. `CpuSetController::set_cpus_async()` or
+//! `MemController::set_limit_async()`, which go through `tokio::fs` instead of blocking the
+//! runtime. `CpuSetController::cpuset_async()` reads the whole `cpuset` state.
+//!
+//! Twins for the remaining getters and setters are a follow-up: those whose control file or format
+//! differs between cgroup v1 and v2, such as `CpuController::set_cfs_quota()`, those touching
+//! several files, such as `CpuSetController::migrate_mems()`, and those of the `blkio`,
+//! `devices`, `freezer`, `hugetlb`, `net_prio` and `rdma` controllers.
+
 #![allow(clippy::unnecessary_unwrap)]
-use log::*;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Read, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
@@ -28,7 +41,27 @@ macro_rules! update_and_test {
 macro_rules! update {
     ($self: ident, $set_func:ident, $value:expr) => {
         if let Some(v) = $value {
-            let _ = $self.$set_func(v);
+            $self.$set_func(v)?;
+        }
+    };
+}
+
+/// Defines the async twin of a getter or setter, which reads or writes the control file `$file`
+/// through `ControllerInternal::read_async()` or `write_async()`. Only built with the `tokio`
+/// feature.
+macro_rules! async_twin {
+    ($(#[$attr:meta])* $name:ident(&$self:ident) -> $ty:ty, read $file:expr, $parse:expr) => {
+        $(#[$attr])*
+        #[cfg(feature = "tokio")]
+        pub async fn $name(&$self) -> Result<$ty> {
+            $self.read_async($file, $parse).await
+        }
+    };
+    ($(#[$attr:meta])* $name:ident(&$self:ident, $arg:ident: $arg_ty:ty), write $file:expr, $value:expr) => {
+        $(#[$attr])*
+        #[cfg(feature = "tokio")]
+        pub async fn $name(&$self, $arg: $arg_ty) -> Result<()> {
+            $self.write_async($file, $value).await
         }
     };
 }
@@ -52,6 +85,7 @@ pub mod perf_event;
 pub mod pid;
 pub mod rdma;
 pub mod systemd;
+mod util;
 
 use crate::blkio::BlkIoController;
 use crate::cpu::CpuController;
@@ -69,6 +103,7 @@ use crate::perf_event::PerfEventController;
 use crate::pid::PidController;
 use crate::rdma::RdmaController;
 use crate::systemd::SystemdController;
+use crate::util::{read_bool_from, read_string_from, write_bool_to, write_to};
 
 #[doc(inline)]
 pub use crate::cgroup::Cgroup;
@@ -107,7 +142,7 @@ pub enum Subsystem {
 }
 
 #[doc(hidden)]
-#[derive(Eq, PartialEq, Debug, Clone)]
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
 pub enum Controllers {
     Pids,
     Mem,
@@ -146,6 +181,114 @@ impl fmt::Display for Controllers {
     }
 }
 
+impl FromStr for Controllers {
+    type Err = Error;
+
+    /// Parses the name the kernel uses for a subsystem, e.g. in the mount options of a cgroup v1
+    /// hierarchy. This is the inverse of the `Display` implementation.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pids" => Ok(Controllers::Pids),
+            "memory" => Ok(Controllers::Mem),
+            "cpuset" => Ok(Controllers::CpuSet),
+            "cpuacct" => Ok(Controllers::CpuAcct),
+            "cpu" => Ok(Controllers::Cpu),
+            "devices" => Ok(Controllers::Devices),
+            "freezer" => Ok(Controllers::Freezer),
+            "net_cls" => Ok(Controllers::NetCls),
+            "blkio" => Ok(Controllers::BlkIo),
+            "perf_event" => Ok(Controllers::PerfEvent),
+            "net_prio" => Ok(Controllers::NetPrio),
+            "hugetlb" => Ok(Controllers::HugeTlb),
+            "rdma" => Ok(Controllers::Rdma),
+            "name=systemd" => Ok(Controllers::Systemd),
+            _ => Err(Error::new(ParseError)),
+        }
+    }
+}
+
+/// How `Controller::open_control_file` opens a control file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FileMode {
+    /// Open the file for reading.
+    Read,
+    /// Open the file for writing, truncating it first. This is what single-value knobs such as
+    /// `cpuset.cpus` expect, so that a shorter value does not leave a tail of the previous one.
+    /// A missing file is not created.
+    Write,
+    /// Open the file for writing with `O_APPEND`, without truncating it. This is what `tasks`,
+    /// `cgroup.procs` and `cgroup.threads` expect, as each write adds a task.
+    Append,
+}
+
+impl FileMode {
+    /// The mode `ControllerInternal::open_path` uses for a read (`w == false`) or a write of the
+    /// control file `p`.
+    fn for_file(p: &str, w: bool) -> Self {
+        match p {
+            _ if !w => FileMode::Read,
+            "tasks" | "cgroup.procs" | "cgroup.threads" => FileMode::Append,
+            _ => FileMode::Write,
+        }
+    }
+}
+
+/// Reads the tasks listed in `tasks`, `cgroup.procs` or `cgroup.threads` one line at a time, see
+/// `Controller::tasks_iter()`.
+#[derive(Debug)]
+pub struct TasksFile {
+    lines: std::io::Lines<BufReader<File>>,
+    path: PathBuf,
+}
+
+impl Iterator for TasksFile {
+    type Item = Result<CgroupPid>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in &mut self.lines {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    let path = self.path.display().to_string();
+                    return Some(Err(Error::with_cause(ReadFailed(path), e)));
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Some(
+                util::parse_from::<u64>(&line)
+                    .map(CgroupPid::from)
+                    .map_err(|e| e.with_path(&self.path)),
+            );
+        }
+        None
+    }
+}
+
+/// A depth-first iterator over a control group and its descendants, see `Controller::walk()`.
+#[derive(Debug)]
+pub struct CgroupWalker<T> {
+    stack: Vec<T>,
+}
+
+impl<T: Controller + Clone> Iterator for CgroupWalker<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while let Some(cg) = self.stack.pop() {
+            match cg.children() {
+                Ok(children) => self.stack.extend(children.into_iter().rev()),
+                // Removed since it was listed by its parent.
+                Err(_) if !cg.exists() => continue,
+                Err(_) => {}
+            }
+            return Some(cg);
+        }
+        None
+    }
+}
+
 mod sealed {
     use super::*;
 
@@ -161,6 +304,11 @@ mod sealed {
         /// Hooks running after controller crated, if have
         fn post_create(&self) {}
 
+        /// Zero the resettable counters of the controller, see `Controller::reset_stats()`.
+        fn reset_stats(&self) -> Result<()> {
+            Err(Error::new(ErrorKind::NotSupported))
+        }
+
         fn is_v2(&self) -> bool {
             false
         }
@@ -173,34 +321,70 @@ mod sealed {
             }
         }
 
-        fn open_path(&self, p: &str, w: bool) -> Result<File> {
-            let mut path = self.get_path().clone();
-            path.push(p);
-
+        /// Returns the path of the control file `p`, after checking that the control group
+        /// stays within its hierarchy.
+        fn control_file_path(&self, p: &str) -> Result<PathBuf> {
             self.verify_path()?;
+            Ok(self.get_path().join(p))
+        }
+
+        fn open_path(&self, p: &str, w: bool) -> Result<File> {
+            self.open_file(p, FileMode::for_file(p, w))
+        }
 
-            if w {
-                match File::create(&path) {
-                    Err(e) => Err(Error::with_cause(
-                        ErrorKind::WriteFailed(
-                            path.display().to_string(),
-                            "[CREATE FILE]".to_string(),
-                        ),
+        fn open_file(&self, p: &str, mode: FileMode) -> Result<File> {
+            let path = self.control_file_path(p)?;
+
+            let res = match mode {
+                FileMode::Read => File::open(&path),
+                // Control files are provided by the kernel, never create one.
+                FileMode::Write => OpenOptions::new().write(true).truncate(true).open(&path),
+                FileMode::Append => OpenOptions::new().append(true).open(&path),
+            };
+            match res {
+                Ok(file) => Ok(file),
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::PermissionDenied
+                        || e.raw_os_error() == Some(libc::EROFS) =>
+                {
+                    Err(Error::with_cause(
+                        ErrorKind::PermissionDenied(path.display().to_string()),
                         e,
-                    )),
-                    Ok(file) => Ok(file),
+                    ))
                 }
-            } else {
-                match File::open(&path) {
-                    Err(e) => Err(Error::with_cause(
-                        ErrorKind::ReadFailed(path.display().to_string()),
-                        e,
-                    )),
-                    Ok(file) => Ok(file),
+                // The control group itself exists but the kernel does not
+                // provide this control file.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && self.get_path().is_dir() => {
+                    Err(Error::with_cause(ErrorKind::NotSupported, e).with_path(&path))
                 }
+                Err(e) if mode != FileMode::Read => Err(Error::with_cause(
+                    ErrorKind::WriteFailed(path.display().to_string(), "[CREATE FILE]".to_string()),
+                    e,
+                )),
+                Err(e) => Err(Error::with_cause(
+                    ErrorKind::ReadFailed(path.display().to_string()),
+                    e,
+                )),
             }
         }
 
+        fn open_tasks_file(&self, f: &str) -> Result<TasksFile> {
+            let file = self.open_path(f, false)?;
+            Ok(TasksFile {
+                lines: BufReader::new(file).lines(),
+                path: self.get_path().join(f),
+            })
+        }
+
+        fn read_bool(&self, f: &str) -> Result<bool> {
+            self.open_path(f, false).and_then(read_bool_from)
+        }
+
+        fn write_bool(&self, f: &str, value: bool) -> Result<()> {
+            self.open_path(f, true)
+                .and_then(|file| write_bool_to(file, f, value))
+        }
+
         fn get_max_value(&self, f: &str) -> Result<MaxValue> {
             self.open_path(f, false).and_then(|mut file| {
                 let mut string = String::new();
@@ -212,6 +396,28 @@ mod sealed {
             })
         }
 
+        /// Reads the control file `f` through `tokio::fs` and hands its contents to `parse`, the
+        /// async counterpart of `open_path()` followed by a parser.
+        ///
+        /// The trait is sealed and only called on concrete controllers, whose futures stay `Send`.
+        #[cfg(feature = "tokio")]
+        #[allow(async_fn_in_trait)]
+        async fn read_async<T, F>(&self, f: &str, parse: F) -> Result<T>
+        where
+            F: FnOnce(&str) -> Result<T>,
+        {
+            let path = self.control_file_path(f)?;
+            parse(&util::read_string_async(&path).await?)
+        }
+
+        /// Writes `value` to the control file `f` through `tokio::fs`.
+        #[cfg(feature = "tokio")]
+        #[allow(async_fn_in_trait)]
+        async fn write_async(&self, f: &str, value: &str) -> Result<()> {
+            let path = self.control_file_path(f)?;
+            util::write_async(&path, value).await
+        }
+
         #[doc(hidden)]
         fn path_exists(&self, p: &str) -> bool {
             if self.verify_path().is_err() {
@@ -225,21 +431,11 @@ mod sealed {
     pub trait CustomizedAttribute: ControllerInternal {
         fn set(&self, key: &str, value: &str) -> Result<()> {
             self.open_path(key, true).and_then(|mut file| {
-                file.write_all(value.as_ref()).map_err(|e| {
+                write_to(&mut file, value).map_err(|e| {
                     Error::with_cause(WriteFailed(key.to_string(), value.to_string()), e)
                 })
             })
         }
-
-        fn get(&self, key: &str) -> Result<String> {
-            self.open_path(key, false).and_then(|mut file: File| {
-                let mut string = String::new();
-                match file.read_to_string(&mut string) {
-                    Ok(_) => Ok(string.trim().to_owned()),
-                    Err(e) => Err(Error::with_cause(ReadFailed(key.to_string()), e)),
-                }
-            })
-        }
     }
 }
 
@@ -255,29 +451,64 @@ pub trait Controller {
     /// The file system path to the controller.
     fn path(&self) -> &Path;
 
+    /// The path of the control group relative to the root of its hierarchy, e.g. `a/b`, or an
+    /// empty path for the root itself.
+    fn relative_path(&self) -> PathBuf;
+
     /// Apply a set of resources to the Controller, invoking its internal functions to pass the
     /// kernel the information.
     fn apply(&self, res: &Resources) -> Result<()>;
 
-    /// Create this controller
-    fn create(&self);
+    /// Zero the counters the kernel lets users reset, e.g. `cpuacct.usage` or
+    /// `memory.max_usage_in_bytes`, to establish a baseline for later measurements.
+    ///
+    /// Fails with `NotSupported` for controllers without resettable counters, which includes all
+    /// cgroup v2 ones.
+    fn reset_stats(&self) -> Result<()>;
+
+    /// Create this controller, i.e. the directory backing it.
+    fn create(&self) -> Result<()>;
 
     /// Does this controller already exist?
     fn exists(&self) -> bool;
 
+    /// Open the control file `name` of this control group for raw access.
+    ///
+    /// Use `FileMode::Append` for the files listing tasks (`tasks`, `cgroup.procs`,
+    /// `cgroup.threads`) and `FileMode::Write` for single-value knobs. The typed accessors of the
+    /// controllers should be preferred where they exist.
+    ///
+    /// `name` must be a single path component, otherwise `InvalidPath` is returned.
+    fn open_control_file(&self, name: &str, mode: FileMode) -> Result<File>;
+
     /// Set notify_on_release
     fn set_notify_on_release(&self, enable: bool) -> Result<()>;
 
+    /// Get notify_on_release
+    fn notify_on_release(&self) -> Result<bool>;
+
     /// Set release_agent
+    ///
+    /// The `release_agent` file exists only at the root of a v1 hierarchy.
     fn set_release_agent(&self, path: &str) -> Result<()>;
 
+    /// Get release_agent
+    ///
+    /// The `release_agent` file exists only at the root of a v1 hierarchy.
+    fn release_agent(&self) -> Result<String>;
+
     /// Delete the controller.
+    ///
+    /// Fails with `Busy` if tasks are still attached to the control group.
     fn delete(&self) -> Result<()>;
 
     /// Attach a task to this controller.
     fn add_task(&self, pid: &CgroupPid) -> Result<()>;
 
-    /// Attach a task to this controller.
+    /// Attach a process to this controller by thread group id.
+    ///
+    /// Unlike `add_task()`, which moves a single thread, this writes to `cgroup.procs` and thus
+    /// migrates every thread of the process.
     fn add_task_by_tgid(&self, pid: &CgroupPid) -> Result<()>;
 
     /// set cgroup type.
@@ -286,13 +517,58 @@ pub trait Controller {
     /// get cgroup type.
     fn get_cgroup_type(&self) -> Result<String>;
 
+    /// Get the state reported in `cgroup.events`.
+    ///
+    /// Only supported for v2 cgroups.
+    fn events(&self) -> Result<CgroupEvents>;
+
     /// Get the list of tasks that this controller has.
     fn tasks(&self) -> Vec<CgroupPid>;
 
+    /// Same as `tasks()`, but reads the list lazily instead of collecting it, so that large
+    /// control groups do not need to be held in memory at once.
+    fn tasks_iter(&self) -> Result<TasksFile>;
+
+    /// Same as `procs()`, but reads the list lazily instead of collecting it.
+    fn procs_iter(&self) -> Result<TasksFile>;
+
     /// Get the list of procs that this controller has.
     fn procs(&self) -> Vec<CgroupPid>;
 
     fn v2(&self) -> bool;
+
+    /// Returns a handle to the child control group `name`, which does not necessarily exist
+    /// until `create()` is called on it.
+    ///
+    /// `name` must be a single path component, otherwise `InvalidPath` is returned.
+    fn nested(&self, name: &str) -> Result<Self>
+    where
+        Self: Sized + Clone;
+
+    /// Returns handles to the direct child control groups, i.e. the subdirectories of `path()`,
+    /// sorted by name.
+    fn children(&self) -> Result<Vec<Self>>
+    where
+        Self: Sized + Clone;
+
+    /// Returns an iterator over this control group and all its descendants, depth-first with a
+    /// parent visited before its children, which are visited in the order of `children()`.
+    ///
+    /// Control groups removed while the walk is in progress are skipped.
+    fn walk(&self) -> CgroupWalker<Self>
+    where
+        Self: Sized + Clone;
+
+    /// Move every process of this control group to `dest`, returning how many distinct processes
+    /// were moved.
+    ///
+    /// Processes exiting during the migration are skipped. The source is drained until it is
+    /// empty, so that children forked in the meantime are moved as well. Fails with
+    /// `InvalidOperation` if `dest` is this control group, or if a pass over the remaining
+    /// processes moves none of them.
+    fn move_all_tasks_to(&self, dest: &Self) -> Result<usize>
+    where
+        Self: Sized;
 }
 
 impl<T> Controller for T
@@ -307,21 +583,30 @@ where
         self.get_path()
     }
 
+    fn relative_path(&self) -> PathBuf {
+        self.get_path()
+            .strip_prefix(self.get_base())
+            .unwrap_or_else(|_| self.get_path())
+            .to_path_buf()
+    }
+
     /// Apply a set of resources to the Controller, invoking its internal functions to pass the
     /// kernel the information.
     fn apply(&self, res: &Resources) -> Result<()> {
         ControllerInternal::apply(self, res)
     }
 
+    fn reset_stats(&self) -> Result<()> {
+        ControllerInternal::reset_stats(self)
+    }
+
     /// Create this controller
-    fn create(&self) {
-        self.verify_path()
-            .unwrap_or_else(|_| panic!("path should be valid: {:?}", self.path()));
+    fn create(&self) -> Result<()> {
+        self.verify_path()?;
 
-        match ::std::fs::create_dir_all(self.get_path()) {
-            Ok(_) => self.post_create(),
-            Err(e) => warn!("error create_dir: {:?} error: {:?}", self.get_path(), e),
-        }
+        ::std::fs::create_dir_all(self.get_path()).map_err(|e| Error::with_cause(FsError, e))?;
+        self.post_create();
+        Ok(())
     }
 
     /// Set notify_on_release
@@ -329,15 +614,15 @@ where
         if self.is_v2() {
             return Err(Error::new(ErrorKind::CgroupVersion));
         }
-        self.open_path("notify_on_release", true)
-            .and_then(|mut file| {
-                write!(file, "{}", enable as i32).map_err(|e| {
-                    Error::with_cause(
-                        ErrorKind::WriteFailed("notify_on_release".to_string(), enable.to_string()),
-                        e,
-                    )
-                })
-            })
+        self.write_bool("notify_on_release", enable)
+    }
+
+    /// Get notify_on_release
+    fn notify_on_release(&self) -> Result<bool> {
+        if self.is_v2() {
+            return Err(Error::new(ErrorKind::CgroupVersion));
+        }
+        self.read_bool("notify_on_release")
     }
 
     /// Set release_agent
@@ -346,7 +631,7 @@ where
             return Err(Error::new(ErrorKind::CgroupVersion));
         }
         self.open_path("release_agent", true).and_then(|mut file| {
-            file.write_all(path.as_bytes()).map_err(|e| {
+            write_to(&mut file, path).map_err(|e| {
                 Error::with_cause(
                     ErrorKind::WriteFailed("release_agent".to_string(), path.to_string()),
                     e,
@@ -354,9 +639,66 @@ where
             })
         })
     }
+
+    /// Get release_agent
+    fn release_agent(&self) -> Result<String> {
+        if self.is_v2() {
+            return Err(Error::new(ErrorKind::CgroupVersion));
+        }
+        self.open_path("release_agent", false)
+            .and_then(read_string_from)
+    }
     /// Does this controller already exist?
     fn exists(&self) -> bool {
-        self.get_path().exists()
+        self.get_path().is_dir()
+    }
+
+    fn open_control_file(&self, name: &str, mode: FileMode) -> Result<File> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(Error::new(ErrorKind::InvalidPath));
+        }
+        self.open_file(name, mode)
+    }
+
+    fn nested(&self, name: &str) -> Result<Self>
+    where
+        Self: Sized + Clone,
+    {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(Error::new(ErrorKind::InvalidPath));
+        }
+        let mut child = self.clone();
+        child.get_path_mut().push(name);
+        Ok(child)
+    }
+
+    fn children(&self) -> Result<Vec<Self>>
+    where
+        Self: Sized + Clone,
+    {
+        let path = self.get_path();
+        let read_failed = |e| Error::with_cause(ReadFailed(path.display().to_string()), e);
+        let mut names = Vec::new();
+        for entry in fs::read_dir(path).map_err(read_failed)? {
+            let entry = entry.map_err(read_failed)?;
+            if entry.file_type().map_err(read_failed)?.is_dir() {
+                names.push(entry.file_name());
+            }
+        }
+        names.sort();
+        names
+            .iter()
+            .map(|name| self.nested(&name.to_string_lossy()))
+            .collect()
+    }
+
+    fn walk(&self) -> CgroupWalker<Self>
+    where
+        Self: Sized + Clone,
+    {
+        CgroupWalker {
+            stack: vec![self.clone()],
+        }
     }
 
     /// Delete the controller.
@@ -391,7 +733,7 @@ where
             file_name = "cgroup.threads";
         }
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(pid.pid.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &pid.pid.to_string()).map_err(|e| {
                 Error::with_cause(
                     ErrorKind::WriteFailed(file_name.to_string(), pid.pid.to_string()),
                     e,
@@ -404,7 +746,7 @@ where
     fn add_task_by_tgid(&self, pid: &CgroupPid) -> Result<()> {
         let file_name = "cgroup.procs";
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(pid.pid.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &pid.pid.to_string()).map_err(|e| {
                 Error::with_cause(
                     ErrorKind::WriteFailed(file_name.to_string(), pid.pid.to_string()),
                     e,
@@ -413,49 +755,66 @@ where
         })
     }
 
-    /// Get the list of procs that this controller has.
-    fn procs(&self) -> Vec<CgroupPid> {
-        let file_name = "cgroup.procs";
-        self.open_path(file_name, false)
-            .map(|file| {
-                let bf = BufReader::new(file);
-                let mut v = Vec::new();
-                for line in bf.lines() {
-                    match line {
-                        Ok(line) => {
-                            let n = line.trim().parse().unwrap_or(0u64);
-                            v.push(n);
-                        }
-                        Err(_) => break,
+    fn move_all_tasks_to(&self, dest: &Self) -> Result<usize>
+    where
+        Self: Sized,
+    {
+        if dest.get_path() == self.get_path() {
+            return Err(Error::new(InvalidOperation).with_path(self.get_path()));
+        }
+        let mut moved = HashSet::new();
+        let mut exited = HashSet::new();
+        loop {
+            let procs = self.procs();
+            if procs.is_empty() {
+                return Ok(moved.len());
+            }
+            // Processes listed again after being moved or having exited are no progress.
+            let mut progress = false;
+            for pid in &procs {
+                match dest.add_task_by_tgid(pid) {
+                    Ok(()) => progress |= moved.insert(pid.pid),
+                    // The process exited in the meantime.
+                    Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {
+                        progress |= exited.insert(pid.pid)
                     }
+                    Err(e) => return Err(e),
                 }
-                v.into_iter().map(CgroupPid::from).collect()
-            })
+            }
+            if !progress {
+                return Err(Error::with_cause(
+                    InvalidOperation,
+                    Error::from_string(format!("{} processes were not moved", procs.len())),
+                )
+                .with_path(self.get_path()));
+            }
+        }
+    }
+
+    /// Get the list of procs that this controller has.
+    fn procs(&self) -> Vec<CgroupPid> {
+        self.procs_iter()
+            .map(|procs| procs.map_while(Result::ok).collect())
             .unwrap_or_default()
     }
 
     /// Get the list of tasks that this controller has.
     fn tasks(&self) -> Vec<CgroupPid> {
+        self.tasks_iter()
+            .map(|tasks| tasks.map_while(Result::ok).collect())
+            .unwrap_or_default()
+    }
+
+    fn tasks_iter(&self) -> Result<TasksFile> {
         let mut file_name = "tasks";
         if self.is_v2() {
             file_name = "cgroup.threads";
         }
-        self.open_path(file_name, false)
-            .map(|file| {
-                let bf = BufReader::new(file);
-                let mut v = Vec::new();
-                for line in bf.lines() {
-                    match line {
-                        Ok(line) => {
-                            let n = line.trim().parse().unwrap_or(0u64);
-                            v.push(n);
-                        }
-                        Err(_) => break,
-                    }
-                }
-                v.into_iter().map(CgroupPid::from).collect()
-            })
-            .unwrap_or_default()
+        self.open_tasks_file(file_name)
+    }
+
+    fn procs_iter(&self) -> Result<TasksFile> {
+        self.open_tasks_file("cgroup.procs")
     }
 
     /// set cgroup.type
@@ -465,7 +824,7 @@ where
         }
         let file_name = "cgroup.type";
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(cgroup_type.as_bytes()).map_err(|e| {
+            write_to(&mut file, cgroup_type).map_err(|e| {
                 Error::with_cause(
                     ErrorKind::WriteFailed(file_name.to_string(), cgroup_type.to_string()),
                     e,
@@ -492,6 +851,16 @@ where
         })
     }
 
+    /// Get the state reported in `cgroup.events`.
+    fn events(&self) -> Result<CgroupEvents> {
+        if !self.is_v2() {
+            return Err(Error::new(ErrorKind::CgroupVersion));
+        }
+        self.open_path("cgroup.events", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_cgroup_events(&s))
+    }
+
     fn v2(&self) -> bool {
         self.is_v2()
     }
@@ -516,7 +885,10 @@ fn remove_dir(dir: &Path) -> Result<()> {
                 remove_dir(&path)?;
             }
         }
-        fs::remove_dir(dir).map_err(|e| Error::with_cause(RemoveFailed, e))?;
+        fs::remove_dir(dir).map_err(|e| match e.raw_os_error() {
+            Some(libc::EBUSY) => Error::with_cause(Busy(dir.display().to_string()), e),
+            _ => Error::with_cause(RemoveFailed, e),
+        })?;
     }
 
     Ok(())
@@ -777,7 +1149,7 @@ impl From<u64> for CgroupPid {
     }
 }
 
-impl<'a> From<&'a std::process::Child> for CgroupPid {
+impl From<&std::process::Child> for CgroupPid {
     fn from(u: &std::process::Child) -> CgroupPid {
         CgroupPid { pid: u.id() as u64 }
     }
@@ -867,24 +1239,43 @@ impl Subsystem {
     pub fn controller_name(&self) -> String {
         self.to_controller().control_type().to_string()
     }
+
+    /// Retrieve the controller held by this subsystem based on type inference.
+    ///
+    /// Unlike the `From<&Subsystem>` conversions, which panic on a mismatch, this returns
+    /// `WrongSubsystem` if the subsystem holds a different controller.
+    ///
+    /// ## Example:
+    ///
+    /// ```text
+    /// let cpu: &CpuController = subsystem.try_as_controller()?;
+    /// ```
+    pub fn try_as_controller<'a, T>(&'a self) -> Result<&'a T>
+    where
+        &'a T: From<&'a Subsystem>,
+        T: Controller + ControllIdentifier,
+    {
+        if self.to_controller().control_type() == T::controller_type() {
+            Ok(self.into())
+        } else {
+            Err(Error::new(WrongSubsystem))
+        }
+    }
 }
 
 /// The values for `memory.hight` or `pids.max`
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+///
+/// With the `serde` feature, `Max` is serialized as the string `"max"` and `Value` as the bare
+/// number, like the kernel writes them.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
 pub enum MaxValue {
     /// This value is returned when the text is `"max"`.
+    #[default]
     Max,
     /// When the value is a numerical value, they are returned via this enum field.
     Value(i64),
 }
 
-impl Default for MaxValue {
-    fn default() -> Self {
-        MaxValue::Max
-    }
-}
-
 impl MaxValue {
     #[allow(clippy::should_implement_trait, clippy::wrong_self_convention)]
     fn to_i64(&self) -> i64 {
@@ -904,23 +1295,392 @@ impl fmt::Display for MaxValue {
     }
 }
 
+impl FromStr for MaxValue {
+    type Err = Error;
+
+    /// Parses `"max"` or a non-negative number, surrounding whitespace is ignored.
+    ///
+    /// The knobs using this format are all unsigned, so a negative number is a `ParseError`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s == "max" {
+            return Ok(MaxValue::Max);
+        }
+        match s.parse::<i64>() {
+            Ok(val) if val >= 0 => Ok(MaxValue::Value(val)),
+            Ok(_) => Err(Error::new(ParseError)),
+            Err(e) => Err(Error::with_cause(ParseError, e)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MaxValue {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            MaxValue::Max => serializer.serialize_str("max"),
+            MaxValue::Value(num) => serializer.serialize_i64(*num),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MaxValue {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use std::convert::TryFrom;
+
+        struct MaxValueVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for MaxValueVisitor {
+            type Value = MaxValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "\"max\" or a non-negative integer")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<MaxValue, E> {
+                if v == "max" {
+                    Ok(MaxValue::Max)
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Str(v), &self))
+                }
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> std::result::Result<MaxValue, E> {
+                if v < 0 {
+                    return Err(E::invalid_value(serde::de::Unexpected::Signed(v), &self));
+                }
+                Ok(MaxValue::Value(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> std::result::Result<MaxValue, E> {
+                i64::try_from(v)
+                    .map(MaxValue::Value)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(MaxValueVisitor)
+    }
+}
+
 pub fn parse_max_value(s: &str) -> Result<MaxValue> {
-    if s.trim() == "max" {
-        return Ok(MaxValue::Max);
+    s.parse()
+}
+
+/// The number identifying a device, written by the kernel as `major:minor`, e.g. `8:0`.
+///
+/// With the `serde` feature it is serialized in the same `major:minor` form, so that it can key
+/// maps such as `IoWeight::devices`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceNumber {
+    /// The major number of the device.
+    pub major: u64,
+    /// The minor number of the device.
+    pub minor: u64,
+}
+
+impl DeviceNumber {
+    /// Returns the device number `major:minor`, e.g. `DeviceNumber::new(8, 0)` for `/dev/sda`.
+    pub fn new(major: u64, minor: u64) -> Self {
+        Self { major, minor }
     }
-    match s.trim().parse() {
-        Ok(val) => Ok(MaxValue::Value(val)),
-        Err(e) => Err(Error::with_cause(ParseError, e)),
+}
+
+/// Returns the number of the device at `path`, or, if `path` is not a device node, the number of
+/// the device holding the filesystem it is on.
+///
+/// This gives e.g. the number to pass to `BlkIoController::throttle_read_bps_for_device()` for
+/// `/dev/sda`, or for the disk backing a data directory.
+pub fn device_number_for_path<P: AsRef<Path>>(path: P) -> Result<DeviceNumber> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let path = path.as_ref();
+    let metadata = fs::metadata(path).map_err(|e| Error::with_cause(FsError, e).with_path(path))?;
+    let file_type = metadata.file_type();
+    let dev = if file_type.is_block_device() || file_type.is_char_device() {
+        metadata.rdev()
+    } else {
+        metadata.dev()
+    };
+    Ok(DeviceNumber::new(
+        libc::major(dev) as u64,
+        libc::minor(dev) as u64,
+    ))
+}
+
+impl fmt::Display for DeviceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for DeviceNumber {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (major, minor) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| Error::new(ParseError))?;
+        Ok(DeviceNumber {
+            major: major
+                .parse()
+                .map_err(|e| Error::with_cause(ParseError, e))?,
+            minor: minor
+                .parse()
+                .map_err(|e| Error::with_cause(ParseError, e))?,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceNumber {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceNumber {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct DeviceNumberVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for DeviceNumberVisitor {
+            type Value = DeviceNumber;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a device number such as \"8:0\"")
+            }
+
+            fn visit_str<E: serde::de::Error>(
+                self,
+                v: &str,
+            ) -> std::result::Result<DeviceNumber, E> {
+                v.parse()
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(DeviceNumberVisitor)
+    }
+}
+
+/// The major or minor number of a `devices` rule, where `*` matches any number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceId {
+    /// Matches any number, written as `*`.
+    Wildcard,
+    /// Matches exactly this number.
+    Number(u64),
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceId::Wildcard => write!(f, "*"),
+            DeviceId::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "*" => Ok(DeviceId::Wildcard),
+            _ => s
+                .parse()
+                .map(DeviceId::Number)
+                .map_err(|e| Error::with_cause(ParseError, e)),
+        }
+    }
+}
+
+/// A set of devices as matched by a `devices` rule, written as `major:minor` where either number
+/// may be `*`, e.g. `136:*` matches every pseudo terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DevicePattern {
+    /// The major number of the matched devices.
+    pub major: DeviceId,
+    /// The minor number of the matched devices.
+    pub minor: DeviceId,
+}
+
+impl DevicePattern {
+    /// A pattern matching every device, i.e. `*:*`.
+    pub const ALL: DevicePattern = DevicePattern {
+        major: DeviceId::Wildcard,
+        minor: DeviceId::Wildcard,
+    };
+}
+
+impl From<DeviceNumber> for DevicePattern {
+    fn from(dev: DeviceNumber) -> Self {
+        DevicePattern {
+            major: DeviceId::Number(dev.major),
+            minor: DeviceId::Number(dev.minor),
+        }
+    }
+}
+
+impl fmt::Display for DevicePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.major, self.minor)
     }
 }
 
+impl FromStr for DevicePattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (major, minor) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| Error::new(ParseError))?;
+        Ok(DevicePattern {
+            major: major.parse()?,
+            minor: minor.parse()?,
+        })
+    }
+}
+
+/// Pressure stall information for one kind of stall, as found in the `*.pressure` files.
+///
+/// The averages are the share of wall time, in percent, over the last 10, 60 and 300 seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PsiMetrics {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    /// The total stall time, in microseconds.
+    pub total: u64,
+}
+
+/// Pressure stall information of a control group.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pressure {
+    /// Some of the tasks were stalled on the resource.
+    pub some: PsiMetrics,
+    /// All non-idle tasks were stalled on the resource at the same time. Older kernels do not
+    /// report it for the CPU.
+    pub full: Option<PsiMetrics>,
+}
+
+// Pressure
+//  some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n
+//  full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n
+pub(crate) fn parse_pressure(s: &str) -> Result<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let kind = match fields.next() {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        let mut m = PsiMetrics::default();
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::new(ParseError))?;
+            match key {
+                "avg10" => {
+                    m.avg10 = value
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?
+                }
+                "avg60" => {
+                    m.avg60 = value
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?
+                }
+                "avg300" => {
+                    m.avg300 = value
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?
+                }
+                "total" => {
+                    m.total = value
+                        .parse()
+                        .map_err(|e| Error::with_cause(ParseError, e))?
+                }
+                _ => {}
+            }
+        }
+
+        match kind {
+            "some" => some = Some(m),
+            "full" => full = Some(m),
+            _ => return Err(Error::new(ParseError)),
+        }
+    }
+
+    Ok(Pressure {
+        some: some.ok_or_else(|| Error::new(ParseError))?,
+        full,
+    })
+}
+
+/// The state of a cgroup v2 control group, as found in `cgroup.events`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CgroupEvents {
+    /// Whether the control group or any of its descendants has live processes.
+    pub populated: bool,
+    /// Whether the control group is frozen. Kernels older than 5.2 do not report it, in which
+    /// case it is `false`.
+    pub frozen: bool,
+}
+
+// cgroup.events
+//  populated 1\n
+//  frozen 0\n
+pub(crate) fn parse_cgroup_events(s: &str) -> Result<CgroupEvents> {
+    let mut populated = None;
+    let mut frozen = false;
+    for line in s.lines() {
+        let (key, value) = match line.split_once(' ') {
+            Some(kv) => kv,
+            None if line.trim().is_empty() => continue,
+            None => return Err(Error::new(ParseError)),
+        };
+        match key {
+            "populated" => populated = Some(util::parse_bool(value)?),
+            "frozen" => frozen = util::parse_bool(value)?,
+            _ => {}
+        }
+    }
+
+    Ok(CgroupEvents {
+        populated: populated.ok_or_else(|| Error::new(ParseError))?,
+        frozen,
+    })
+}
+
 // Flat keyed
 //  KEY0 VAL0\n
 //  KEY1 VAL1\n
 pub fn flat_keyed_to_vec(mut file: File) -> Result<Vec<(String, i64)>> {
     let mut content = String::new();
     file.read_to_string(&mut content)
-        .map_err(|e| Error::with_cause(ReadFailed("FIXME: read_string_from".to_string()), e))?;
+        .map_err(|e| Error::with_cause(ReadFailed(util::file_path(&file)), e))?;
 
     let mut v = Vec::new();
     for line in content.lines() {
@@ -940,7 +1700,7 @@ pub fn flat_keyed_to_vec(mut file: File) -> Result<Vec<(String, i64)>> {
 pub fn flat_keyed_to_hashmap(mut file: File) -> Result<HashMap<String, i64>> {
     let mut content = String::new();
     file.read_to_string(&mut content)
-        .map_err(|e| Error::with_cause(ReadFailed("FIXME: read_string_from".to_string()), e))?;
+        .map_err(|e| Error::with_cause(ReadFailed(util::file_path(&file)), e))?;
 
     let mut h = HashMap::new();
     for line in content.lines() {
@@ -960,7 +1720,7 @@ pub fn flat_keyed_to_hashmap(mut file: File) -> Result<HashMap<String, i64>> {
 pub fn nested_keyed_to_hashmap(mut file: File) -> Result<HashMap<String, HashMap<String, i64>>> {
     let mut content = String::new();
     file.read_to_string(&mut content)
-        .map_err(|e| Error::with_cause(ReadFailed("FIXME: read_string_from".to_string()), e))?;
+        .map_err(|e| Error::with_cause(ReadFailed(util::file_path(&file)), e))?;
 
     let mut h = HashMap::new();
     for line in content.lines() {
@@ -983,41 +1743,303 @@ pub fn nested_keyed_to_hashmap(mut file: File) -> Result<HashMap<String, HashMap
     Ok(h)
 }
 
-fn read_from<T>(mut file: File) -> Result<T>
-where
-    T: FromStr,
-    <T as FromStr>::Err: 'static + Send + Sync + std::error::Error,
-{
-    let mut string = String::new();
-    match file.read_to_string(&mut string) {
-        Ok(_) => string
-            .trim()
-            .parse::<T>()
-            .map_err(|e| Error::with_cause(ParseError, e)),
-        Err(e) => Err(Error::with_cause(
-            ReadFailed("FIXME: can't get path in fn read_from".to_string()),
-            e,
-        )),
+#[cfg(test)]
+mod tests {
+    use crate::cpuset::CpuSetController;
+    use crate::error::ErrorKind;
+    use crate::pid::PidController;
+    use crate::util::tests::TempDir;
+    use crate::{
+        device_number_for_path, parse_cgroup_events, parse_pressure, CgroupEvents, CgroupPid,
+        Controller, ControllerInternal, Controllers, DeviceNumber, DevicePattern, FileMode,
+        MaxValue, PsiMetrics,
+    };
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_move_all_tasks_to_stalled() {
+        let dir = TempDir::new("move_all_tasks_to_stalled");
+        let src = PidController::new(dir.join("src"), false);
+        let dest = PidController::new(dir.join("dest"), false);
+        for (cg, procs) in [(&src, "1\n2\n"), (&dest, "")] {
+            fs::create_dir(cg.path()).unwrap();
+            fs::write(cg.path().join("cgroup.procs"), procs).unwrap();
+        }
+
+        let err = src.move_all_tasks_to(&src).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidOperation);
+
+        // The writes succeed, but the processes stay listed in the source. The second pass
+        // moves nothing new and gives up.
+        let err = src.move_all_tasks_to(&dest).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidOperation);
+        assert_eq!(err.path(), Some(src.path()));
+        assert_eq!(
+            fs::read_to_string(dest.path().join("cgroup.procs")).unwrap(),
+            "1212"
+        );
     }
-}
 
-fn read_string_from(mut file: File) -> Result<String> {
-    let mut string = String::new();
-    match file.read_to_string(&mut string) {
-        Ok(_) => Ok(string.trim().to_string()),
-        Err(e) => Err(Error::with_cause(
-            ReadFailed("FIXME: can't get path in fn read_string_from".to_string()),
-            e,
-        )),
+    #[test]
+    fn test_max_value_round_trip() {
+        for value in [MaxValue::Max, MaxValue::Value(42), MaxValue::Value(0)] {
+            assert_eq!(value.to_string().parse::<MaxValue>().unwrap(), value);
+        }
+        assert_eq!("max\n".parse::<MaxValue>().unwrap(), MaxValue::Max);
+        assert_eq!(MaxValue::Value(42).to_string(), "42");
+
+        for case in ["-1", "", "Max", "12a"] {
+            let err = case.parse::<MaxValue>().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
     }
-}
 
-/// read and parse an u64 data
-fn read_u64_from(file: File) -> Result<u64> {
-    read_from::<u64>(file)
-}
+    #[test]
+    fn test_controllers_round_trip() {
+        let all = [
+            Controllers::Pids,
+            Controllers::Mem,
+            Controllers::CpuSet,
+            Controllers::CpuAcct,
+            Controllers::Cpu,
+            Controllers::Devices,
+            Controllers::Freezer,
+            Controllers::NetCls,
+            Controllers::BlkIo,
+            Controllers::PerfEvent,
+            Controllers::NetPrio,
+            Controllers::HugeTlb,
+            Controllers::Rdma,
+            Controllers::Systemd,
+        ];
+        for c in all.iter() {
+            assert_eq!(&c.to_string().parse::<Controllers>().unwrap(), c);
+        }
+
+        assert_eq!("memory".parse::<Controllers>().unwrap(), Controllers::Mem);
+        assert_eq!("blkio".parse::<Controllers>().unwrap(), Controllers::BlkIo);
+        for name in ["", "mem", "io", "Memory", "systemd", "cpu,cpuacct"].iter() {
+            let err = name.parse::<Controllers>().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError);
+        }
+    }
+
+    #[test]
+    fn test_parse_cgroup_events() {
+        assert_eq!(
+            parse_cgroup_events("populated 1\nfrozen 0\n").unwrap(),
+            CgroupEvents {
+                populated: true,
+                frozen: false
+            }
+        );
+        assert_eq!(
+            parse_cgroup_events("populated 0\nfrozen 1\n").unwrap(),
+            CgroupEvents {
+                populated: false,
+                frozen: true
+            }
+        );
+        // Kernels before 5.2 only report `populated`.
+        assert_eq!(
+            parse_cgroup_events("populated 1\n").unwrap(),
+            CgroupEvents {
+                populated: true,
+                frozen: false
+            }
+        );
+
+        for case in ["", "frozen 1\n", "populated\n", "populated 2\n"].iter() {
+            let err = parse_cgroup_events(case).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_device_number() {
+        let dev = "8:0".parse::<DeviceNumber>().unwrap();
+        assert_eq!(dev, DeviceNumber::new(8, 0));
+        assert_eq!(dev.to_string(), "8:0");
+        assert_eq!(DeviceNumber::new(259, 1048575).to_string(), "259:1048575");
+
+        for case in ["8", "8:0:1", "*:*", "8:*", "a:0", ":0", "-1:0"] {
+            let err = case.parse::<DeviceNumber>().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_device_number_for_path() {
+        use std::os::unix::fs::MetadataExt;
+
+        assert_eq!(
+            device_number_for_path("/dev/null").unwrap(),
+            DeviceNumber::new(1, 3)
+        );
+
+        // A regular file maps to the device of its filesystem.
+        let dev = fs::metadata("/proc/self/exe").unwrap().dev();
+        let dev = DeviceNumber::new(libc::major(dev) as u64, libc::minor(dev) as u64);
+        assert_eq!(device_number_for_path("/proc/self/exe").unwrap(), dev);
 
-/// read and parse an i64 data
-fn read_i64_from(file: File) -> Result<i64> {
-    read_from::<i64>(file)
+        let err = device_number_for_path("/nonexistent").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::FsError);
+        assert_eq!(err.path(), Some(Path::new("/nonexistent")));
+    }
+
+    #[test]
+    fn test_device_pattern() {
+        for case in ["*:*", "136:*", "*:3", "1:3"] {
+            assert_eq!(case.parse::<DevicePattern>().unwrap().to_string(), case);
+        }
+        assert_eq!("*:*".parse::<DevicePattern>().unwrap(), DevicePattern::ALL);
+        assert_eq!(
+            "1:3".parse::<DevicePattern>().unwrap(),
+            DevicePattern::from(DeviceNumber::new(1, 3))
+        );
+
+        for case in ["*", "1:3:5", "**:1", "1:"] {
+            let err = case.parse::<DevicePattern>().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_parse_pressure() {
+        let pressure = parse_pressure(
+            "some avg10=1.50 avg60=0.25 avg300=0.00 total=123456\n\
+             full avg10=0.75 avg60=0.10 avg300=0.00 total=6543\n",
+        )
+        .unwrap();
+        assert_eq!(
+            pressure.some,
+            PsiMetrics {
+                avg10: 1.5,
+                avg60: 0.25,
+                avg300: 0.0,
+                total: 123456,
+            }
+        );
+        assert_eq!(
+            pressure.full,
+            Some(PsiMetrics {
+                avg10: 0.75,
+                avg60: 0.1,
+                avg300: 0.0,
+                total: 6543,
+            })
+        );
+
+        // Older kernels only report `some` for the CPU.
+        let pressure = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").unwrap();
+        assert_eq!(pressure.some, PsiMetrics::default());
+        assert_eq!(pressure.full, None);
+
+        for bad in [
+            "",
+            "full avg10=0.00 avg60=0.00 avg300=0.00 total=0",
+            "some avg10=abc avg60=0.00 avg300=0.00 total=0",
+            "some avg10",
+            "partial avg10=0.00",
+        ] {
+            let err = parse_pressure(bad).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_max_value_serde() {
+        assert_eq!(serde_json::to_string(&MaxValue::Max).unwrap(), "\"max\"");
+        assert_eq!(serde_json::to_string(&MaxValue::Value(42)).unwrap(), "42");
+        for value in [MaxValue::Max, MaxValue::Value(42)] {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<MaxValue>(&json).unwrap(), value);
+        }
+        assert!(serde_json::from_str::<MaxValue>("\"min\"").is_err());
+        assert!(serde_json::from_str::<MaxValue>("-5").is_err());
+    }
+
+    #[test]
+    fn test_read_write_bool() {
+        let dir = TempDir::new("read_write_bool");
+        fs::write(dir.join("cpuset.cpu_exclusive"), "0\n").unwrap();
+        let cpuset = CpuSetController::new(dir.to_path_buf(), false);
+
+        cpuset.write_bool("cpuset.cpu_exclusive", true).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("cpuset.cpu_exclusive")).unwrap(),
+            "1"
+        );
+        assert!(cpuset.read_bool("cpuset.cpu_exclusive").unwrap());
+
+        cpuset.set_cpu_exclusive(false).unwrap();
+        assert!(!cpuset.read_bool("cpuset.cpu_exclusive").unwrap());
+
+        fs::write(dir.join("cpuset.cpu_exclusive"), "2\n").unwrap();
+        let err = cpuset.read_bool("cpuset.cpu_exclusive").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_open_control_file_modes() {
+        use std::io::Write;
+
+        let dir = TempDir::new("open_control_file_modes");
+        fs::write(dir.join("tasks"), "").unwrap();
+        fs::write(dir.join("cpuset.cpus"), "0-15\n").unwrap();
+        let cpuset = CpuSetController::new(dir.to_path_buf(), false);
+
+        // Writes to `tasks` append, so both PIDs are kept.
+        let mut file = cpuset.open_control_file("tasks", FileMode::Append).unwrap();
+        file.write_all(b"100\n").unwrap();
+        cpuset.add_task(&CgroupPid::from(200)).unwrap();
+        assert_eq!(
+            cpuset.tasks(),
+            vec![CgroupPid::from(100), CgroupPid::from(200)]
+        );
+
+        // Single-value knobs are truncated.
+        cpuset.set_cpus("1").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("cpuset.cpus")).unwrap(), "1");
+
+        let err = cpuset
+            .open_control_file("../tasks", FileMode::Read)
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidPath);
+    }
+
+    #[test]
+    fn test_tasks_iter_is_lazy() {
+        use std::fmt::Write;
+
+        let dir = TempDir::new("tasks_iter_is_lazy");
+        let mut content = String::new();
+        for pid in 1..=100_000 {
+            writeln!(content, "{}", pid).unwrap();
+        }
+        // Only reached by consuming the whole file.
+        content.push_str("garbage\n");
+        fs::write(dir.join("tasks"), &content).unwrap();
+        let cpuset = CpuSetController::new(dir.to_path_buf(), false);
+
+        let first = cpuset
+            .tasks_iter()
+            .unwrap()
+            .take(3)
+            .collect::<crate::error::Result<Vec<_>>>()
+            .unwrap();
+        let expected = (1..=3).map(CgroupPid::from).collect::<Vec<_>>();
+        assert_eq!(first, expected);
+
+        let mut tasks = cpuset.tasks_iter().unwrap();
+        assert_eq!(tasks.by_ref().take(100_000).count(), 100_000);
+        let err = tasks.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+        assert!(tasks.next().is_none());
+
+        // The collecting variant stops at the garbage.
+        assert_eq!(cpuset.tasks().len(), 100_000);
+    }
 }
@@ -10,14 +10,17 @@
 //!  [Documentation/cgroup-v1/memory.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/memory.txt)
 use log::warn;
 use std::collections::HashMap;
-use std::io::Write;
 use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
 use crate::events;
-use crate::{read_i64_from, read_string_from, read_u64_from};
+use crate::hierarchies;
+#[cfg(feature = "tokio")]
+use crate::util::parse_bool;
+use crate::util::{parse_from, read_i64_from, read_string_from, read_u64_from, write_to};
+use crate::{parse_pressure, Pressure};
 
 use crate::flat_keyed_to_hashmap;
 
@@ -26,6 +29,45 @@ use crate::{
     MemoryResources, Resources, Subsystem,
 };
 
+/// The knobs of the `memory` controller, see `MemController::control_file()`.
+#[derive(Debug, Clone, Copy)]
+enum Knob {
+    Usage,
+    MaxUsage,
+    Limit,
+    MemswapLimit,
+    SoftLimit,
+    MemswapUsage,
+    MemswapMaxUsage,
+    Swappiness,
+    ForceEmpty,
+    FailCnt,
+    MemswapFailCnt,
+    High,
+    Low,
+    Max,
+    Min,
+    Stat,
+    Events,
+    EventsLocal,
+    NumaStat,
+    Pressure,
+    MoveChargeAtImmigrate,
+    UseHierarchy,
+    OomControl,
+    OomGroup,
+    SwapEvents,
+    KmemFailCnt,
+    KmemLimit,
+    KmemUsage,
+    KmemMaxUsage,
+    KmemSlabinfo,
+    KmemTcpFailCnt,
+    KmemTcpLimit,
+    KmemTcpUsage,
+    KmemTcpMaxUsage,
+}
+
 /// A controller that allows controlling the `memory` subsystem of a Cgroup.
 ///
 /// In essence, using the memory controller, the user can gather statistics about the memory usage
@@ -38,7 +80,7 @@ pub struct MemController {
     v2: bool,
 }
 
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetMemory {
     pub low: Option<MaxValue>,
@@ -48,7 +90,7 @@ pub struct SetMemory {
 }
 
 /// Controls statistics and controls about the OOM killer operating in this control group.
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OomControl {
     /// If true, the OOM killer has been disabled for the tasks in this control group.
@@ -59,37 +101,84 @@ pub struct OomControl {
     pub oom_kill: u64,
 }
 
-#[allow(clippy::unnecessary_wraps)]
+/// Parse the `key value` lines of `memory.oom_control`.
+///
+/// Missing keys default to zero: `oom_kill`, for instance, only exists on Linux 4.13 and newer.
 fn parse_oom_control(s: String) -> Result<OomControl> {
-    let spl = s.split_whitespace().collect::<Vec<_>>();
+    let mut oom = OomControl::default();
+
+    for line in s.lines() {
+        let mut spl = line.split_whitespace();
+        let (key, value) = match (spl.next(), spl.next()) {
+            (Some(k), Some(v)) => (k, v),
+            _ => continue,
+        };
+        let value = value
+            .parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
 
-    let oom_kill_disable = if spl.len() > 1 {
-        spl[1].parse::<u64>().unwrap() == 1
-    } else {
-        false
-    };
+        match key {
+            "oom_kill_disable" => oom.oom_kill_disable = value == 1,
+            "under_oom" => oom.under_oom = value == 1,
+            "oom_kill" => oom.oom_kill = value,
+            _ => {}
+        }
+    }
 
-    let under_oom = if spl.len() > 3 {
-        spl[3].parse::<u64>().unwrap() == 1
-    } else {
-        false
-    };
+    Ok(oom)
+}
 
-    let oom_kill = if spl.len() > 5 {
-        spl[5].parse::<u64>().unwrap()
-    } else {
-        0
-    };
+/// The counters of `memory.events` or `memory.events.local`, each counting how many times the
+/// event happened.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryEvents {
+    /// The control group was reclaimed from while below its `memory.low` protection.
+    pub low: u64,
+    /// The usage exceeded `memory.high` and the tasks were throttled.
+    pub high: u64,
+    /// The usage was about to exceed `memory.max`.
+    pub max: u64,
+    /// The control group hit its limit and allocations failed.
+    pub oom: u64,
+    /// A task of the control group was killed by the OOM killer.
+    pub oom_kill: u64,
+    /// The whole control group was killed by the OOM killer, reported by Linux 5.17 and newer.
+    pub oom_group_kill: u64,
+}
 
-    Ok(OomControl {
-        oom_kill_disable,
-        under_oom,
-        oom_kill,
-    })
+/// Parse the `key value` lines of `memory.events` and `memory.events.local`.
+///
+/// Missing and unknown keys are ignored, so that older and newer kernels are supported alike.
+fn parse_memory_events(s: &str) -> Result<MemoryEvents> {
+    let mut events = MemoryEvents::default();
+
+    for line in s.lines() {
+        let mut spl = line.split_whitespace();
+        let (key, value) = match (spl.next(), spl.next()) {
+            (Some(k), Some(v)) => (k, v),
+            _ => continue,
+        };
+        let value = value
+            .parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
+
+        match key {
+            "low" => events.low = value,
+            "high" => events.high = value,
+            "max" => events.max = value,
+            "oom" => events.oom = value,
+            "oom_kill" => events.oom_kill = value,
+            "oom_group_kill" => events.oom_group_kill = value,
+            _ => {}
+        }
+    }
+
+    Ok(events)
 }
 
 /// Contains statistics about the NUMA locality of the control group's tasks.
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NumaStat {
     /// Total amount of pages used by the control group.
@@ -127,6 +216,53 @@ pub struct NumaStat {
     pub hierarchical_unevictable_pages_per_node: Vec<u64>,
 }
 
+/// One category of `memory.numa_stat`, such as `total` or `anon`, broken down by NUMA node.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NumaNodeStat {
+    /// The amount summed over all nodes.
+    ///
+    /// cgroup v2 does not report a total, so there it is the sum of `nodes`.
+    pub total: u64,
+    /// The amount on each NUMA node, keyed by the node id (`N0` is `0`).
+    pub nodes: HashMap<u32, u64>,
+}
+
+/// Parses `memory.numa_stat` into a map from category name to its per-node breakdown.
+///
+/// cgroup v1 lines look like `total=N N0=x N1=y` and v2 lines like `anon N0=x N1=y`; any number
+/// of nodes is accepted. Categories are keyed by their name as it appears in the file, so the
+/// v1 hierarchical lines show up as `hierarchical_total` and friends.
+fn parse_numa_stat_map(s: &str) -> Result<HashMap<String, NumaNodeStat>> {
+    let mut stats = HashMap::new();
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let (name, total) = match fields.next() {
+            Some(field) => match field.split_once('=') {
+                Some((name, total)) => (name, Some(parse_from(total)?)),
+                None => (field, None),
+            },
+            None => continue,
+        };
+
+        let mut nodes = HashMap::new();
+        for field in fields {
+            let (node, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::new(ParseError))?;
+            let node = node
+                .strip_prefix('N')
+                .ok_or_else(|| Error::new(ParseError))?;
+            nodes.insert(parse_from(node)?, parse_from(value)?);
+        }
+
+        let total = total.unwrap_or_else(|| nodes.values().sum());
+        stats.insert(name.to_string(), NumaNodeStat { total, nodes });
+    }
+
+    Ok(stats)
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn parse_numa_stat(s: String) -> Result<NumaStat> {
     // Parse the number of nodes
@@ -142,9 +278,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
     let hier_unevict_line = ls.next().unwrap_or_default();
 
     Ok(NumaStat {
-        total_pages: total_line
-            .split(|x| x == ' ' || x == '=')
-            .collect::<Vec<_>>()[1]
+        total_pages: total_line.split([' ', '=']).collect::<Vec<_>>()[1]
             .parse::<u64>()
             .unwrap_or(0),
         total_pages_per_node: {
@@ -157,9 +291,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
                 })
                 .collect()
         },
-        file_pages: file_line
-            .split(|x| x == ' ' || x == '=')
-            .collect::<Vec<_>>()[1]
+        file_pages: file_line.split([' ', '=']).collect::<Vec<_>>()[1]
             .parse::<u64>()
             .unwrap_or(0),
         file_pages_per_node: {
@@ -172,9 +304,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
                 })
                 .collect()
         },
-        anon_pages: anon_line
-            .split(|x| x == ' ' || x == '=')
-            .collect::<Vec<_>>()[1]
+        anon_pages: anon_line.split([' ', '=']).collect::<Vec<_>>()[1]
             .parse::<u64>()
             .unwrap_or(0),
         anon_pages_per_node: {
@@ -187,9 +317,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
                 })
                 .collect()
         },
-        unevictable_pages: unevict_line
-            .split(|x| x == ' ' || x == '=')
-            .collect::<Vec<_>>()[1]
+        unevictable_pages: unevict_line.split([' ', '=']).collect::<Vec<_>>()[1]
             .parse::<u64>()
             .unwrap_or(0),
         unevictable_pages_per_node: {
@@ -204,9 +332,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
         },
         hierarchical_total_pages: {
             if !hier_total_line.is_empty() {
-                hier_total_line
-                    .split(|x| x == ' ' || x == '=')
-                    .collect::<Vec<_>>()[1]
+                hier_total_line.split([' ', '=']).collect::<Vec<_>>()[1]
                     .parse::<u64>()
                     .unwrap_or(0)
             } else {
@@ -229,9 +355,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
         },
         hierarchical_file_pages: {
             if !hier_file_line.is_empty() {
-                hier_file_line
-                    .split(|x| x == ' ' || x == '=')
-                    .collect::<Vec<_>>()[1]
+                hier_file_line.split([' ', '=']).collect::<Vec<_>>()[1]
                     .parse::<u64>()
                     .unwrap_or(0)
             } else {
@@ -254,9 +378,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
         },
         hierarchical_anon_pages: {
             if !hier_anon_line.is_empty() {
-                hier_anon_line
-                    .split(|x| x == ' ' || x == '=')
-                    .collect::<Vec<_>>()[1]
+                hier_anon_line.split([' ', '=']).collect::<Vec<_>>()[1]
                     .parse::<u64>()
                     .unwrap_or(0)
             } else {
@@ -279,9 +401,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
         },
         hierarchical_unevictable_pages: {
             if !hier_unevict_line.is_empty() {
-                hier_unevict_line
-                    .split(|x| x == ' ' || x == '=')
-                    .collect::<Vec<_>>()[1]
+                hier_unevict_line.split([' ', '=']).collect::<Vec<_>>()[1]
                     .parse::<u64>()
                     .unwrap_or(0)
             } else {
@@ -305,7 +425,7 @@ fn parse_numa_stat(s: String) -> Result<NumaStat> {
     })
 }
 
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryStat {
     pub cache: u64,
@@ -352,7 +472,7 @@ fn parse_memory_stat(s: String) -> Result<MemoryStat> {
     let mut raw = HashMap::new();
 
     for l in s.lines() {
-        let t: Vec<&str> = l.split(' ').collect();
+        let t: Vec<&str> = l.split_whitespace().collect();
         if t.len() != 2 {
             continue;
         }
@@ -407,7 +527,7 @@ fn parse_memory_stat(s: String) -> Result<MemoryStat> {
 
 /// Contains statistics about the current usage of memory and swap (together, not seperately) by
 /// the control group's tasks.
-#[derive(Debug)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemSwap {
     /// How many times the limit has been hit.
@@ -422,7 +542,7 @@ pub struct MemSwap {
 
 /// State of and statistics gathered by the kernel about the memory usage of the control group's
 /// tasks.
-#[derive(Debug)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Memory {
     /// How many times the limit has been hit.
@@ -467,7 +587,7 @@ pub struct Memory {
 
 /// The current state of and gathered statistics about the kernel's memory usage for TCP-related
 /// data structures.
-#[derive(Debug)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tcp {
     /// How many times the limit has been hit.
@@ -486,7 +606,7 @@ pub struct Tcp {
 /// this is per-cgroup, so the kernel can of course use more memory, but it will fail operations by
 /// these tasks if it would think that the limits here would be violated. It's important to note
 /// that interrupts in particular might not be able to enforce these limits.
-#[derive(Debug)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kmem {
     /// How many times the limit has been hit.
@@ -530,12 +650,22 @@ impl ControllerInternal for MemController {
         update!(self, set_tcp_limit, memres.kernel_tcp_memory_limit);
         update!(self, set_swappiness, memres.swappiness);
 
-        memres.attrs.iter().for_each(|(k, v)| {
-            let _ = self.set(k, v);
-        });
+        memres.attrs.iter().try_for_each(|(k, v)| self.set(k, v))?;
 
         Ok(())
     }
+
+    fn reset_stats(&self) -> Result<()> {
+        if self.v2 {
+            return Err(Error::new(NotSupported));
+        }
+        self.reset_max_usage()?;
+        match self.reset_memswap_max_usage() {
+            // The kernel was built without swap accounting.
+            Err(e) if e.kind() == &NotSupported => Ok(()),
+            r => r,
+        }
+    }
 }
 
 impl MemController {
@@ -548,13 +678,96 @@ impl MemController {
         }
     }
 
+    /// Constructs a `MemController` for the root of the `memory` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::Mem)?;
+        Ok(Self::new(root, v2))
+    }
+
+    /// Returns the file implementing `knob` in the detected cgroup version.
+    ///
+    /// Knobs without a cgroup v2 counterpart map to their cgroup v1 file either way, so that
+    /// using them on cgroup v2 fails with `NotSupported`.
+    fn control_file(&self, knob: Knob) -> &'static str {
+        match (knob, self.v2) {
+            (Knob::Usage, false) => "memory.usage_in_bytes",
+            (Knob::Usage, true) => "memory.current",
+            (Knob::MaxUsage, false) => "memory.max_usage_in_bytes",
+            (Knob::MaxUsage, true) => "memory.peak",
+            (Knob::Limit, false) => "memory.limit_in_bytes",
+            (Knob::Limit, true) => "memory.max",
+            (Knob::MemswapLimit, false) => "memory.memsw.limit_in_bytes",
+            (Knob::MemswapLimit, true) => "memory.swap.max",
+            (Knob::SoftLimit, false) => "memory.soft_limit_in_bytes",
+            (Knob::SoftLimit, true) => "memory.low",
+            (Knob::MemswapUsage, false) => "memory.memsw.usage_in_bytes",
+            (Knob::MemswapUsage, true) => "memory.swap.current",
+            (Knob::Swappiness, _) => "memory.swappiness",
+            (Knob::ForceEmpty, false) => "memory.force_empty",
+            (Knob::ForceEmpty, true) => "memory.reclaim",
+            (Knob::MemswapMaxUsage, _) => "memory.memsw.max_usage_in_bytes",
+            (Knob::FailCnt, _) => "memory.failcnt",
+            (Knob::MemswapFailCnt, _) => "memory.memsw.failcnt",
+            (Knob::High, _) => "memory.high",
+            (Knob::Low, _) => "memory.low",
+            (Knob::Max, _) => "memory.max",
+            (Knob::Min, _) => "memory.min",
+            (Knob::Stat, _) => "memory.stat",
+            (Knob::Events, _) => "memory.events",
+            (Knob::EventsLocal, _) => "memory.events.local",
+            (Knob::NumaStat, _) => "memory.numa_stat",
+            (Knob::Pressure, _) => "memory.pressure",
+            (Knob::MoveChargeAtImmigrate, _) => "memory.move_charge_at_immigrate",
+            (Knob::UseHierarchy, _) => "memory.use_hierarchy",
+            (Knob::OomControl, _) => "memory.oom_control",
+            (Knob::OomGroup, _) => "memory.oom.group",
+            (Knob::SwapEvents, _) => "memory.swap.events",
+            (Knob::KmemFailCnt, _) => "memory.kmem.failcnt",
+            (Knob::KmemLimit, _) => "memory.kmem.limit_in_bytes",
+            (Knob::KmemUsage, _) => "memory.kmem.usage_in_bytes",
+            (Knob::KmemMaxUsage, _) => "memory.kmem.max_usage_in_bytes",
+            (Knob::KmemSlabinfo, _) => "memory.kmem.slabinfo",
+            (Knob::KmemTcpFailCnt, _) => "memory.kmem.tcp.failcnt",
+            (Knob::KmemTcpLimit, _) => "memory.kmem.tcp.limit_in_bytes",
+            (Knob::KmemTcpUsage, _) => "memory.kmem.tcp.usage_in_bytes",
+            (Knob::KmemTcpMaxUsage, _) => "memory.kmem.tcp.max_usage_in_bytes",
+        }
+    }
+
+    /// Reads back the values `apply()` sets from `res.memory`, see `Cgroup::snapshot()`.
+    ///
+    /// On cgroup v2 there is no kernel memory accounting and no swappiness, so those stay `None`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        let res = &mut res.memory;
+        if self.v2 {
+            let read = |file_name| self.get_max_value(file_name).map(|x| x.to_i64()).ok();
+            res.memory_hard_limit = read(self.control_file(Knob::Limit));
+            res.memory_soft_limit = read(self.control_file(Knob::SoftLimit));
+            res.memory_swap_limit = read(self.control_file(Knob::MemswapLimit));
+            return;
+        }
+
+        let read = |file_name| {
+            self.open_path(file_name, false)
+                .and_then(read_i64_from)
+                .ok()
+        };
+        res.memory_hard_limit = read(self.control_file(Knob::Limit));
+        res.memory_soft_limit = read(self.control_file(Knob::SoftLimit));
+        res.memory_swap_limit = read(self.control_file(Knob::MemswapLimit));
+        res.kernel_memory_limit = read(self.control_file(Knob::KmemLimit));
+        res.kernel_tcp_memory_limit = read(self.control_file(Knob::KmemTcpLimit));
+        res.swappiness = self.swappiness().ok();
+    }
+
     // for v2
     pub fn set_mem(&self, m: SetMemory) -> Result<()> {
         let values = vec![
-            (m.high, "memory.high"),
-            (m.low, "memory.low"),
-            (m.max, "memory.max"),
-            (m.min, "memory.min"),
+            (m.high, self.control_file(Knob::High)),
+            (m.low, self.control_file(Knob::Low)),
+            (m.max, self.control_file(Knob::Max)),
+            (m.min, self.control_file(Knob::Min)),
         ];
         for value in values {
             let v = value.0;
@@ -562,7 +775,7 @@ impl MemController {
             if let Some(v) = v {
                 let v = v.to_string();
                 self.open_path(f, true).and_then(|mut file| {
-                    file.write_all(v.as_ref()).map_err(|e| {
+                    write_to(&mut file, &v).map_err(|e| {
                         Error::with_cause(WriteFailed(f.to_string(), format!("{:?}", v)), e)
                     })
                 })?;
@@ -571,14 +784,78 @@ impl MemController {
         Ok(())
     }
 
+    /// Set the best-effort memory protection of the control group, `memory.low`.
+    ///
+    /// This is only available on cgroup v2, see `set_soft_limit()` for cgroup v1.
+    pub fn set_low(&self, low: MaxValue) -> Result<()> {
+        self.set_watermark(SetMemory {
+            low: Some(low),
+            ..Default::default()
+        })
+    }
+
+    /// Set the throttling limit of the control group, `memory.high`. Usage above it is reclaimed
+    /// aggressively, but the OOM killer is not invoked.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn set_high(&self, high: MaxValue) -> Result<()> {
+        self.set_watermark(SetMemory {
+            high: Some(high),
+            ..Default::default()
+        })
+    }
+
+    /// Set `memory.high` to `ratio` times the current `memory.max`, e.g. `0.9` to start
+    /// throttling the control group at 90% of its hard limit. If `memory.max` is unlimited,
+    /// `memory.high` is set to `max` too.
+    ///
+    /// `ratio` must be greater than 0 and at most 1, otherwise this fails with `OutOfRange`. This
+    /// is only available on cgroup v2.
+    pub fn set_high_ratio(&self, ratio: f64) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        if !(ratio > 0.0 && ratio <= 1.0) {
+            return Err(Error::new(OutOfRange(
+                self.control_file(Knob::High).to_string(),
+                ratio.to_string(),
+            )));
+        }
+        let high = match self.get_max_value(self.control_file(Knob::Max))? {
+            MaxValue::Max => MaxValue::Max,
+            MaxValue::Value(max) => MaxValue::Value((max as f64 * ratio) as i64),
+        };
+        self.set_high(high)
+    }
+
+    /// Set the hard memory protection of the control group, `memory.min`.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn set_min(&self, min: MaxValue) -> Result<()> {
+        self.set_watermark(SetMemory {
+            min: Some(min),
+            ..Default::default()
+        })
+    }
+
+    fn set_watermark(&self, m: SetMemory) -> Result<()> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.set_mem(m)
+    }
+
     // for v2
     pub fn get_mem(&self) -> Result<SetMemory> {
         let mut m: SetMemory = Default::default();
-        self.get_max_value("memory.high")
+        self.get_max_value(self.control_file(Knob::High))
             .map(|x| m.high = Some(x))?;
-        self.get_max_value("memory.low").map(|x| m.low = Some(x))?;
-        self.get_max_value("memory.max").map(|x| m.max = Some(x))?;
-        self.get_max_value("memory.min").map(|x| m.min = Some(x))?;
+        self.get_max_value(self.control_file(Knob::Low))
+            .map(|x| m.low = Some(x))?;
+        self.get_max_value(self.control_file(Knob::Max))
+            .map(|x| m.max = Some(x))?;
+        self.get_max_value(self.control_file(Knob::Min))
+            .map(|x| m.min = Some(x))?;
 
         Ok(m)
     }
@@ -590,7 +867,7 @@ impl MemController {
             fail_cnt: 0,
             limit_in_bytes: set.max.unwrap().to_i64(),
             usage_in_bytes: self
-                .open_path("memory.current", false)
+                .open_path(self.control_file(Knob::Usage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             max_usage_in_bytes: 0,
@@ -599,12 +876,12 @@ impl MemController {
             oom_control: OomControl::default(),
             soft_limit_in_bytes: set.low.unwrap().to_i64(),
             stat: self
-                .open_path("memory.stat", false)
+                .open_path(self.control_file(Knob::Stat), false)
                 .and_then(read_string_from)
                 .and_then(parse_memory_stat)
                 .unwrap_or_default(),
             swappiness: self
-                .open_path("memory.swap.current", false)
+                .open_path(self.control_file(Knob::MemswapUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             use_hierarchy: 0,
@@ -623,76 +900,156 @@ impl MemController {
 
         Memory {
             fail_cnt: self
-                .open_path("memory.failcnt", false)
+                .open_path(self.control_file(Knob::FailCnt), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             limit_in_bytes: self
-                .open_path("memory.limit_in_bytes", false)
+                .open_path(self.control_file(Knob::Limit), false)
                 .and_then(read_i64_from)
                 .unwrap_or(0),
             usage_in_bytes: self
-                .open_path("memory.usage_in_bytes", false)
+                .open_path(self.control_file(Knob::Usage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             max_usage_in_bytes: self
-                .open_path("memory.max_usage_in_bytes", false)
+                .open_path(self.control_file(Knob::MaxUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             move_charge_at_immigrate: self
-                .open_path("memory.move_charge_at_immigrate", false)
+                .open_path(self.control_file(Knob::MoveChargeAtImmigrate), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             numa_stat: self
-                .open_path("memory.numa_stat", false)
+                .open_path(self.control_file(Knob::NumaStat), false)
                 .and_then(read_string_from)
                 .and_then(parse_numa_stat)
                 .unwrap_or_default(),
             oom_control: self
-                .open_path("memory.oom_control", false)
+                .open_path(self.control_file(Knob::OomControl), false)
                 .and_then(read_string_from)
                 .and_then(parse_oom_control)
                 .unwrap_or_default(),
             soft_limit_in_bytes: self
-                .open_path("memory.soft_limit_in_bytes", false)
+                .open_path(self.control_file(Knob::SoftLimit), false)
                 .and_then(read_i64_from)
                 .unwrap_or(0),
             stat: self
-                .open_path("memory.stat", false)
+                .open_path(self.control_file(Knob::Stat), false)
                 .and_then(read_string_from)
                 .and_then(parse_memory_stat)
                 .unwrap_or_default(),
             swappiness: self
-                .open_path("memory.swappiness", false)
+                .open_path(self.control_file(Knob::Swappiness), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             use_hierarchy: self
-                .open_path("memory.use_hierarchy", false)
+                .open_path(self.control_file(Knob::UseHierarchy), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
         }
     }
 
+    /// Returns the current memory usage of the control group's tasks, in bytes.
+    pub fn usage_in_bytes(&self) -> Result<u64> {
+        let file_name = self.control_file(Knob::Usage);
+        self.open_path(file_name, false).and_then(read_u64_from)
+    }
+
+    async_twin! {
+        /// Same as `usage_in_bytes()`, but reads through `tokio::fs` so that the async runtime is
+        /// not blocked.
+        usage_in_bytes_async(&self) -> u64,
+        read self.control_file(Knob::Usage), parse_from
+    }
+
+    /// Returns the maximum observed memory usage of the control group's tasks, in bytes.
+    ///
+    /// On cgroup v2 this reads `memory.peak`, which is only available on Linux 5.19 and newer.
+    pub fn max_usage_in_bytes(&self) -> Result<u64> {
+        let file_name = self.control_file(Knob::MaxUsage);
+        self.open_path(file_name, false).and_then(read_u64_from)
+    }
+
+    async_twin! {
+        /// Same as `max_usage_in_bytes()`, but reads through `tokio::fs` so that the async runtime
+        /// is not blocked.
+        max_usage_in_bytes_async(&self) -> u64,
+        read self.control_file(Knob::MaxUsage), parse_from
+    }
+
+    /// Returns the statistics found in `memory.stat`.
+    ///
+    /// Unlike the `stat` field of `memory_stat()`, this reports read and parse errors.
+    pub fn stat(&self) -> Result<MemoryStat> {
+        self.open_path(self.control_file(Knob::Stat), false)
+            .and_then(read_string_from)
+            .and_then(parse_memory_stat)
+    }
+
+    /// Returns the event counters found in `memory.events`, which include the events of the
+    /// descendant control groups.
+    ///
+    /// This is only available on cgroup v2.
+    pub fn mem_events(&self) -> Result<MemoryEvents> {
+        self.read_memory_events(self.control_file(Knob::Events))
+    }
+
+    /// Same as `mem_events()`, but only counts the events of this control group, as found in
+    /// `memory.events.local` (Linux 5.2 and newer).
+    pub fn mem_events_local(&self) -> Result<MemoryEvents> {
+        self.read_memory_events(self.control_file(Knob::EventsLocal))
+    }
+
+    fn read_memory_events(&self, file_name: &str) -> Result<MemoryEvents> {
+        if !self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path(file_name, false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_memory_events(&s))
+    }
+
+    /// Returns the per-node breakdown found in `memory.numa_stat`, keyed by category (`total`,
+    /// `file`, `anon`, `unevictable`, ...).
+    ///
+    /// Unlike the `numa_stat` field of `memory_stat()`, this keeps the node ids and reports read
+    /// and parse errors.
+    pub fn numa_stat(&self) -> Result<HashMap<String, NumaNodeStat>> {
+        self.open_path(self.control_file(Knob::NumaStat), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_numa_stat_map(&s))
+    }
+
+    /// Returns the memory pressure stall information found in `memory.pressure`.
+    ///
+    /// This is only available on cgroup v2 with a kernel built with `CONFIG_PSI`.
+    pub fn pressure(&self) -> Result<Pressure> {
+        self.open_path(self.control_file(Knob::Pressure), false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pressure(&s))
+    }
+
     /// Gathers information about the kernel memory usage of the control group's tasks.
     pub fn kmem_stat(&self) -> Kmem {
         Kmem {
             fail_cnt: self
-                .open_path("memory.kmem.failcnt", false)
+                .open_path(self.control_file(Knob::KmemFailCnt), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             limit_in_bytes: self
-                .open_path("memory.kmem.limit_in_bytes", false)
+                .open_path(self.control_file(Knob::KmemLimit), false)
                 .and_then(read_i64_from)
                 .unwrap_or(-1),
             usage_in_bytes: self
-                .open_path("memory.kmem.usage_in_bytes", false)
+                .open_path(self.control_file(Knob::KmemUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             max_usage_in_bytes: self
-                .open_path("memory.kmem.max_usage_in_bytes", false)
+                .open_path(self.control_file(Knob::KmemMaxUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             slabinfo: self
-                .open_path("memory.kmem.slabinfo", false)
+                .open_path(self.control_file(Knob::KmemSlabinfo), false)
                 .and_then(read_string_from)
                 .unwrap_or_default(),
         }
@@ -703,37 +1060,70 @@ impl MemController {
     pub fn kmem_tcp_stat(&self) -> Tcp {
         Tcp {
             fail_cnt: self
-                .open_path("memory.kmem.tcp.failcnt", false)
+                .open_path(self.control_file(Knob::KmemTcpFailCnt), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             limit_in_bytes: self
-                .open_path("memory.kmem.tcp.limit_in_bytes", false)
+                .open_path(self.control_file(Knob::KmemTcpLimit), false)
                 .and_then(read_i64_from)
                 .unwrap_or(0),
             usage_in_bytes: self
-                .open_path("memory.kmem.tcp.usage_in_bytes", false)
+                .open_path(self.control_file(Knob::KmemTcpUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             max_usage_in_bytes: self
-                .open_path("memory.kmem.tcp.max_usage_in_bytes", false)
+                .open_path(self.control_file(Knob::KmemTcpMaxUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
         }
     }
 
+    /// Returns the kernel memory usage of the control group's tasks, in bytes.
+    ///
+    /// `memory.kmem.*` only exists on cgroup v1 and is missing from kernels built without kmem
+    /// accounting; in both cases this fails with `NotSupported`.
+    pub fn kmem_usage_in_bytes(&self) -> Result<u64> {
+        self.open_path(self.control_file(Knob::KmemUsage), false)
+            .and_then(read_u64_from)
+    }
+
+    /// Returns the maximum observed kernel memory usage of the control group's tasks, in bytes.
+    ///
+    /// Fails with `NotSupported` where `memory.kmem.*` is unavailable.
+    pub fn kmem_max_usage_in_bytes(&self) -> Result<u64> {
+        self.open_path(self.control_file(Knob::KmemMaxUsage), false)
+            .and_then(read_u64_from)
+    }
+
+    /// Returns the limit on kernel memory used for TCP buffers, in bytes.
+    ///
+    /// Fails with `NotSupported` where `memory.kmem.tcp.*` is unavailable.
+    pub fn kmem_tcp_limit_in_bytes(&self) -> Result<i64> {
+        self.open_path(self.control_file(Knob::KmemTcpLimit), false)
+            .and_then(read_i64_from)
+    }
+
+    /// Returns the kernel memory used for TCP buffers, in bytes.
+    ///
+    /// Fails with `NotSupported` where `memory.kmem.tcp.*` is unavailable.
+    pub fn kmem_tcp_usage_in_bytes(&self) -> Result<u64> {
+        self.open_path(self.control_file(Knob::KmemTcpUsage), false)
+            .and_then(read_u64_from)
+    }
+
     pub fn memswap_v2(&self) -> MemSwap {
         MemSwap {
             fail_cnt: self
-                .open_path("memory.swap.events", false)
+                .open_path(self.control_file(Knob::SwapEvents), false)
                 .and_then(flat_keyed_to_hashmap)
                 .map(|x| *x.get("fail").unwrap_or(&0) as u64)
                 .unwrap(),
             limit_in_bytes: self
-                .open_path("memory.swap.max", false)
+                .open_path(self.control_file(Knob::MemswapLimit), false)
                 .and_then(read_i64_from)
                 .unwrap_or(0),
             usage_in_bytes: self
-                .open_path("memory.swap.current", false)
+                .open_path(self.control_file(Knob::MemswapUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             max_usage_in_bytes: 0,
@@ -749,32 +1139,50 @@ impl MemController {
 
         MemSwap {
             fail_cnt: self
-                .open_path("memory.memsw.failcnt", false)
+                .open_path(self.control_file(Knob::MemswapFailCnt), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             limit_in_bytes: self
-                .open_path("memory.memsw.limit_in_bytes", false)
+                .open_path(self.control_file(Knob::MemswapLimit), false)
                 .and_then(read_i64_from)
                 .unwrap_or(0),
             usage_in_bytes: self
-                .open_path("memory.memsw.usage_in_bytes", false)
+                .open_path(self.control_file(Knob::MemswapUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
             max_usage_in_bytes: self
-                .open_path("memory.memsw.max_usage_in_bytes", false)
+                .open_path(self.control_file(Knob::MemswapMaxUsage), false)
                 .and_then(read_u64_from)
                 .unwrap_or(0),
         }
     }
 
+    /// Reclaim as much memory as possible from the control group, e.g. before deleting it.
+    ///
+    /// On cgroup v1 this writes to `memory.force_empty`. On cgroup v2 the current usage is
+    /// written to `memory.reclaim`, which is only available on Linux 5.19 and newer. Fails with
+    /// `NotSupported` if the kernel provides neither.
+    pub fn force_empty(&self) -> Result<()> {
+        let file_name = self.control_file(Knob::ForceEmpty);
+        let mut value = "0".to_string();
+        if self.v2 {
+            value = self.usage_in_bytes()?.to_string();
+        }
+        self.open_path(file_name, true).and_then(|mut file| {
+            match write_to(&mut file, &value) {
+                // memory.reclaim fails with EAGAIN when less than requested could be reclaimed.
+                Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => Ok(()),
+                r => r.map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), value), e)),
+            }
+        })
+    }
+
     /// Reset the fail counter
     pub fn reset_fail_count(&self) -> Result<()> {
-        self.open_path("memory.failcnt", true).and_then(|mut file| {
-            file.write_all("0".to_string().as_ref()).map_err(|e| {
-                Error::with_cause(
-                    WriteFailed("memory.failcnt".to_string(), "0".to_string()),
-                    e,
-                )
+        let file_name = self.control_file(Knob::FailCnt);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, "0").map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), "0".to_string()), e)
             })
         })
     }
@@ -786,15 +1194,12 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.failcnt", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.kmem.failcnt".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::KmemFailCnt);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, "0").map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), "0".to_string()), e)
             })
+        })
     }
 
     /// Reset the TCP related fail counter
@@ -804,56 +1209,67 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.tcp.failcnt", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.kmem.tcp.failcnt".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::KmemTcpFailCnt);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, "0").map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), "0".to_string()), e)
             })
+        })
     }
 
     /// Reset the memory+swap fail counter
     pub fn reset_memswap_fail_count(&self) -> Result<()> {
-        self.open_path("memory.memsw.failcnt", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.memsw.failcnt".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::MemswapFailCnt);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, "0").map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), "0".to_string()), e)
             })
+        })
     }
 
     /// Reset the max memory usage recorded
+    ///
+    /// This is only available on cgroup v1: writing `memory.peak` on cgroup v2 only resets the
+    /// watermark seen through the file descriptor that wrote it, so it fails with `CgroupVersion`.
     pub fn reset_max_usage(&self) -> Result<()> {
-        self.open_path("memory.max_usage_in_bytes", true)
-            .and_then(|mut file| {
-                file.write_all("0".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.max_usage_in_bytes".to_string(), "0".to_string()),
-                        e,
-                    )
-                })
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        let file_name = self.control_file(Knob::MaxUsage);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, "0").map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), "0".to_string()), e)
+            })
+        })
+    }
+
+    /// Reset the max memory+swap usage recorded
+    pub fn reset_memswap_max_usage(&self) -> Result<()> {
+        let file_name = self.control_file(Knob::MemswapMaxUsage);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, "0").map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), "0".to_string()), e)
             })
+        })
     }
 
     /// Set the memory usage limit of the control group, in bytes.
     pub fn set_limit(&self, limit: i64) -> Result<()> {
-        let mut file_name = "memory.limit_in_bytes";
-        if self.v2 {
-            file_name = "memory.max";
-        }
+        let file_name = self.control_file(Knob::Limit);
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(limit.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &limit.to_string()).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
             })
         })
     }
 
+    async_twin! {
+        /// Same as `set_limit()`, but writes through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        set_limit_async(&self, limit: i64),
+        write self.control_file(Knob::Limit), &limit.to_string()
+    }
+
     /// Set the kernel memory limit of the control group, in bytes.
     pub fn set_kmem_limit(&self, limit: i64) -> Result<()> {
         // Ignore kmem because there is no kmem in cgroup v2
@@ -861,36 +1277,56 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.limit_in_bytes", true)
-            .and_then(|mut file| {
-                let r = file.write_all(limit.to_string().as_ref());
-                match r {
-                    Ok(()) => Ok(()),
-                    Err(ref e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => {
-                        warn!("memory.kmem.limit_in_bytes is unsupported by the kernel");
-                        Ok(())
-                    }
-                    Err(e) => Err(Error::with_cause(
-                        WriteFailed("memory.kmem.limit_in_bytes".to_string(), limit.to_string()),
-                        e,
-                    )),
+        let file_name = self.control_file(Knob::KmemLimit);
+        self.open_path(file_name, true).and_then(|mut file| {
+            let r = write_to(&mut file, &limit.to_string());
+            match r {
+                Ok(()) => Ok(()),
+                Err(ref e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+                    warn!("{} is unsupported by the kernel", file_name);
+                    Ok(())
                 }
+                Err(e) => Err(Error::with_cause(
+                    WriteFailed(file_name.to_string(), limit.to_string()),
+                    e,
+                )),
+            }
+        })
+    }
+
+    /// Set the kernel memory limit of the control group, in bytes.
+    ///
+    /// Unlike `set_kmem_limit`, this fails with `NotSupported` instead of succeeding silently
+    /// when `memory.kmem.limit_in_bytes` is missing (as on cgroup v2) or the kernel refuses it.
+    pub fn set_kmem_limit_in_bytes(&self, limit: i64) -> Result<()> {
+        let file_name = self.control_file(Knob::KmemLimit);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &limit.to_string()).map_err(|e| match e.raw_os_error() {
+                Some(libc::EOPNOTSUPP) => Error::with_cause(NotSupported, e),
+                _ => Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e),
             })
+        })
     }
 
     /// Set the memory+swap limit of the control group, in bytes.
+    ///
+    /// Fails with `NotSupported` if the kernel was built without swap accounting.
     pub fn set_memswap_limit(&self, limit: i64) -> Result<()> {
-        let mut file_name = "memory.memsw.limit_in_bytes";
-        if self.v2 {
-            file_name = "memory.swap.max";
-        }
+        let file_name = self.control_file(Knob::MemswapLimit);
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(limit.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &limit.to_string()).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
             })
         })
     }
 
+    async_twin! {
+        /// Same as `set_memswap_limit()`, but writes through `tokio::fs` so that the async runtime
+        /// is not blocked.
+        set_memswap_limit_async(&self, limit: i64),
+        write self.control_file(Knob::MemswapLimit), &limit.to_string()
+    }
+
     /// Set how much kernel memory can be used for TCP-related buffers by the control group.
     pub fn set_tcp_limit(&self, limit: i64) -> Result<()> {
         // Ignore kmem because there is no kmem in cgroup v2
@@ -898,18 +1334,12 @@ impl MemController {
             return Ok(());
         }
 
-        self.open_path("memory.kmem.tcp.limit_in_bytes", true)
-            .and_then(|mut file| {
-                file.write_all(limit.to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed(
-                            "memory.kmem.tcp.limit_in_bytes".to_string(),
-                            limit.to_string(),
-                        ),
-                        e,
-                    )
-                })
+        let file_name = self.control_file(Knob::KmemTcpLimit);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &limit.to_string()).map_err(|e| {
+                Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
             })
+        })
     }
 
     /// Set the soft limit of the control group, in bytes.
@@ -917,44 +1347,119 @@ impl MemController {
     /// This limit is enforced when the system is nearing OOM conditions. Contrast this with the
     /// hard limit, which is _always_ enforced.
     pub fn set_soft_limit(&self, limit: i64) -> Result<()> {
-        let mut file_name = "memory.soft_limit_in_bytes";
-        if self.v2 {
-            file_name = "memory.low"
-        }
+        let file_name = self.control_file(Knob::SoftLimit);
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(limit.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &limit.to_string()).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), limit.to_string()), e)
             })
         })
     }
 
+    async_twin! {
+        /// Same as `set_soft_limit()`, but writes through `tokio::fs` so that the async runtime is
+        /// not blocked.
+        set_soft_limit_async(&self, limit: i64),
+        write self.control_file(Knob::SoftLimit), &limit.to_string()
+    }
+
     /// Set how likely the kernel is to swap out parts of the address space used by the control
-    /// group.
+    /// group, from 0 to 100.
+    ///
+    /// This is only available on cgroup v1, cgroup v2 has no swappiness.
     ///
     /// Note that a value of zero does not imply that the process will not be swapped out.
     pub fn set_swappiness(&self, swp: u64) -> Result<()> {
-        let mut file_name = "memory.swappiness";
         if self.v2 {
-            file_name = "memory.swap.max"
+            return Err(Error::new(CgroupVersion));
+        }
+        let file_name = self.control_file(Knob::Swappiness);
+        if swp > 100 {
+            return Err(Error::new(ParseError));
         }
 
         self.open_path(file_name, true).and_then(|mut file| {
-            file.write_all(swp.to_string().as_ref()).map_err(|e| {
+            write_to(&mut file, &swp.to_string()).map_err(|e| {
                 Error::with_cause(WriteFailed(file_name.to_string(), swp.to_string()), e)
             })
         })
     }
 
+    /// Returns how likely the kernel is to swap out parts of the address space used by the
+    /// control group.
+    pub fn swappiness(&self) -> Result<u64> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path(self.control_file(Knob::Swappiness), false)
+            .and_then(read_u64_from)
+    }
+
+    /// Returns the memory+swap usage of the control group's tasks, in bytes. On cgroup v2 this is
+    /// the swap usage alone.
+    ///
+    /// Fails with `NotSupported` if the kernel was built without swap accounting.
+    pub fn memswap_usage(&self) -> Result<u64> {
+        let file_name = self.control_file(Knob::MemswapUsage);
+        self.open_path(file_name, false).and_then(read_u64_from)
+    }
+
     pub fn disable_oom_killer(&self) -> Result<()> {
-        self.open_path("memory.oom_control", true)
-            .and_then(|mut file| {
-                file.write_all("1".to_string().as_ref()).map_err(|e| {
-                    Error::with_cause(
-                        WriteFailed("memory.oom_control".to_string(), "1".to_string()),
-                        e,
-                    )
-                })
-            })
+        self.set_oom_control(true)
+    }
+
+    /// Enable or disable the OOM killer for the tasks in this control group.
+    ///
+    /// When disabled, tasks that hit the limit hang until memory is freed instead of being
+    /// killed. Only available on cgroup v1.
+    pub fn set_oom_control(&self, disabled: bool) -> Result<()> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        let value = (disabled as i32).to_string();
+        let file_name = self.control_file(Knob::OomControl);
+        self.open_path(file_name, true).and_then(|mut file| {
+            write_to(&mut file, &value)
+                .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), value), e))
+        })
+    }
+
+    /// Returns the state of the OOM killer for this control group. Only available on cgroup v1.
+    pub fn oom_control(&self) -> Result<OomControl> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        self.open_path(self.control_file(Knob::OomControl), false)
+            .and_then(read_string_from)
+            .and_then(parse_oom_control)
+    }
+
+    /// Controls whether the OOM killer kills all the tasks of the control group together,
+    /// instead of picking a single one, by writing `memory.oom.group`.
+    ///
+    /// Fails with `NotSupported` where the file is missing, i.e. on cgroup v1 and before
+    /// Linux 4.19.
+    pub fn set_oom_group(&self, enable: bool) -> Result<()> {
+        self.write_bool(self.control_file(Knob::OomGroup), enable)
+    }
+
+    async_twin! {
+        /// Same as `set_oom_group()`, but writes through `tokio::fs` so that the async runtime is
+        /// not blocked.
+        set_oom_group_async(&self, enable: bool),
+        write self.control_file(Knob::OomGroup), if enable { "1" } else { "0" }
+    }
+
+    /// Returns whether the OOM killer kills all the tasks of the control group together, see
+    /// `set_oom_group()`.
+    pub fn oom_group(&self) -> Result<bool> {
+        self.read_bool(self.control_file(Knob::OomGroup))
+    }
+
+    async_twin! {
+        /// Same as `oom_group()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        oom_group_async(&self) -> bool,
+        read self.control_file(Knob::OomGroup), parse_bool
     }
 
     pub fn register_oom_event(&self, key: &str) -> Result<Receiver<String>> {
@@ -964,6 +1469,18 @@ impl MemController {
             events::notify_on_oom_v1(key, self.get_path())
         }
     }
+
+    /// Register for notifications when the memory usage of the control group crosses
+    /// `threshold` bytes, in either direction.
+    ///
+    /// `key` is sent on the returned channel each time the threshold is crossed. Only available
+    /// on cgroup v1.
+    pub fn register_usage_threshold(&self, key: &str, threshold: u64) -> Result<Receiver<String>> {
+        if self.v2 {
+            return Err(Error::new(CgroupVersion));
+        }
+        events::notify_usage_threshold(key, self.get_path(), threshold)
+    }
 }
 
 impl ControllIdentifier for MemController {
@@ -976,24 +1493,21 @@ impl CustomizedAttribute for MemController {}
 
 impl<'a> From<&'a Subsystem> for &'a MemController {
     fn from(sub: &'a Subsystem) -> &'a MemController {
-        unsafe {
-            match sub {
-                Subsystem::Mem(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Mem(c) => c,
+            _ => panic!("Subsystem is not a MemController"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::error::ErrorKind;
     use crate::memory::{
-        parse_memory_stat, parse_numa_stat, parse_oom_control, MemoryStat, NumaStat, OomControl,
+        parse_memory_events, parse_memory_stat, parse_numa_stat, parse_numa_stat_map,
+        parse_oom_control, Knob, MemController, MemoryEvents, MemoryStat, NumaStat, OomControl,
     };
+    use crate::util::tests::TempDir;
 
     static GOOD_VALUE: &str = "\
 total=51189 N0=51189 N1=123
@@ -1118,6 +1632,138 @@ total_unevictable 81920
         );
     }
 
+    #[test]
+    fn test_parse_numa_stat_map() {
+        let stats = parse_numa_stat_map(GOOD_VALUE).unwrap();
+        assert_eq!(stats.len(), 8);
+        assert_eq!(stats["total"].total, 51189);
+        assert_eq!(stats["total"].nodes[&0], 51189);
+        assert_eq!(stats["total"].nodes[&1], 123);
+        assert_eq!(stats["file"].nodes[&0], 50175);
+        assert_eq!(stats["anon"].nodes[&0], 1014);
+        assert_eq!(stats["unevictable"].nodes.len(), 2);
+        assert_eq!(stats["unevictable"].nodes[&1], 123);
+        assert_eq!(stats["hierarchical_unevictable"].total, 20);
+
+        // cgroup v2 has no total column, and node ids need not be contiguous.
+        let stats = parse_numa_stat_map("anon N0=4096 N3=8192\nfile N0=0\n").unwrap();
+        assert_eq!(stats["anon"].total, 12288);
+        assert_eq!(stats["anon"].nodes[&3], 8192);
+        assert_eq!(stats["file"].nodes.len(), 1);
+
+        let err = parse_numa_stat_map("total=1 X0=1\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+        let err = parse_numa_stat_map("total=1 N0=x\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_parse_memory_events() {
+        // As reported by kernels older than 5.17, without oom_group_kill.
+        let events = parse_memory_events("low 1\nhigh 22\nmax 333\noom 4\noom_kill 5\n").unwrap();
+        assert_eq!(
+            events,
+            MemoryEvents {
+                low: 1,
+                high: 22,
+                max: 333,
+                oom: 4,
+                oom_kill: 5,
+                oom_group_kill: 0,
+            }
+        );
+
+        let events = parse_memory_events("oom_group_kill 2\nsock_throttled 7\n").unwrap();
+        assert_eq!(events.oom_group_kill, 2);
+        assert_eq!(events.max, 0);
+
+        let err = parse_memory_events("max x\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_mem_events() {
+        let dir = TempDir::new("mem_events");
+        std::fs::write(dir.join("memory.events"), "low 0\nhigh 3\nmax 0\noom 0\n").unwrap();
+        std::fs::write(
+            dir.join("memory.events.local"),
+            "low 0\nhigh 1\nmax 0\noom 0\n",
+        )
+        .unwrap();
+
+        let memory = MemController::new(dir.to_path_buf(), true);
+        assert_eq!(memory.mem_events().unwrap().high, 3);
+        assert_eq!(memory.mem_events_local().unwrap().high, 1);
+
+        let memory = MemController::new(dir.to_path_buf(), false);
+        let err = memory.mem_events().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+    }
+
+    #[test]
+    fn test_control_file() {
+        let v1 = MemController::new("/sys/fs/cgroup/memory".into(), false);
+        let v2 = MemController::new("/sys/fs/cgroup".into(), true);
+        assert_eq!(v1.control_file(Knob::Limit), "memory.limit_in_bytes");
+        assert_eq!(v2.control_file(Knob::Limit), "memory.max");
+        assert_eq!(v1.control_file(Knob::Usage), "memory.usage_in_bytes");
+        assert_eq!(v2.control_file(Knob::Usage), "memory.current");
+        assert_eq!(
+            v1.control_file(Knob::MemswapUsage),
+            "memory.memsw.usage_in_bytes"
+        );
+        assert_eq!(v2.control_file(Knob::MemswapUsage), "memory.swap.current");
+        assert_eq!(v1.control_file(Knob::Swappiness), "memory.swappiness");
+        assert_eq!(
+            v2.control_file(Knob::KmemLimit),
+            "memory.kmem.limit_in_bytes"
+        );
+    }
+
+    #[test]
+    fn test_set_high_ratio() {
+        let dir = TempDir::new("set_high_ratio");
+        let high = || std::fs::read_to_string(dir.join("memory.high")).unwrap();
+        let memory = MemController::new(dir.to_path_buf(), true);
+
+        std::fs::write(dir.join("memory.high"), "max\n").unwrap();
+        std::fs::write(dir.join("memory.max"), "1073741824\n").unwrap();
+        memory.set_high_ratio(0.9).unwrap();
+        assert_eq!(high(), "966367641");
+        memory.set_high_ratio(1.0).unwrap();
+        assert_eq!(high(), "1073741824");
+
+        std::fs::write(dir.join("memory.max"), "max\n").unwrap();
+        memory.set_high_ratio(0.5).unwrap();
+        assert_eq!(high(), "max");
+
+        for ratio in [0.0, -0.5, 1.01, f64::NAN] {
+            let err = memory.set_high_ratio(ratio).unwrap_err();
+            let expected = ErrorKind::OutOfRange("memory.high".to_string(), ratio.to_string());
+            assert_eq!(err.kind(), &expected, "{}", ratio);
+        }
+
+        let memory = MemController::new(dir.to_path_buf(), false);
+        let err = memory.set_high_ratio(0.9).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+    }
+
+    #[test]
+    fn test_oom_group() {
+        let dir = TempDir::new("oom_group");
+        let memory = MemController::new(dir.to_path_buf(), true);
+
+        let err = memory.set_oom_group(true).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+        let err = memory.oom_group().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::NotSupported);
+
+        std::fs::write(dir.join("memory.oom.group"), "0\n").unwrap();
+        assert!(!memory.oom_group().unwrap());
+        memory.set_oom_group(true).unwrap();
+        assert!(memory.oom_group().unwrap());
+    }
+
     #[test]
     fn test_parse_oom_control() {
         let ok = parse_oom_control("".to_string()).unwrap();
@@ -1135,7 +1781,7 @@ total_unevictable 81920
             OomControl {
                 oom_kill_disable: false,
                 under_oom: false,
-                oom_kill: 0,
+                oom_kill: 1337,
             }
         );
         let ok = parse_oom_control(GOOD_OOMCONTROL_VAL_2.to_string()).unwrap();
@@ -1156,6 +1802,30 @@ total_unevictable 81920
                 oom_kill: 1337,
             }
         );
+        let err = parse_oom_control("oom_kill_disable x\n".to_string()).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_parse_memory_stat_unknown_keys() {
+        // Keys added by newer kernels end up in `raw`, malformed lines are skipped.
+        let ok = parse_memory_stat(
+            "\
+cache 4096
+total_rss  8192
+workingset_refault_file 42
+bogus
+rss not_a_number
+"
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(ok.cache, 4096);
+        assert_eq!(ok.total_rss, 8192);
+        assert_eq!(ok.rss, 0);
+        assert_eq!(ok.raw.get("workingset_refault_file"), Some(&42));
+        assert_eq!(ok.raw.get("bogus"), None);
+        assert_eq!(ok.raw.get("rss"), None);
     }
 
     #[test]
@@ -7,13 +7,15 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/net_cls.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/net_cls.txt)
-use std::io::Write;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::read_u64_from;
+#[cfg(feature = "tokio")]
+use crate::util::parse_from;
+use crate::util::{read_u64_from, write_to};
 use crate::{
     ControllIdentifier, ControllerInternal, Controllers, NetworkResources, Resources, Subsystem,
 };
@@ -61,15 +63,9 @@ impl ControllIdentifier for NetClsController {
 
 impl<'a> From<&'a Subsystem> for &'a NetClsController {
     fn from(sub: &'a Subsystem) -> &'a NetClsController {
-        unsafe {
-            match sub {
-                Subsystem::NetCls(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::NetCls(c) => c,
+            _ => panic!("Subsystem is not a NetClsController"),
         }
     }
 }
@@ -83,20 +79,72 @@ impl NetClsController {
         }
     }
 
+    /// Constructs a `NetClsController` for the root of the `net_cls` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn mounted() -> Result<Self> {
+        match hierarchies::controller_root(Controllers::NetCls)? {
+            (root, false) => Ok(Self::new(root)),
+            _ => Err(Error::new(CgroupVersion)),
+        }
+    }
+
+    /// Reads back the value `apply()` sets from `res.network`, see `Cgroup::snapshot()`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        res.network.class_id = self.get_class().ok();
+    }
+
     /// Set the network class id of the outgoing packets of the control group's tasks.
     pub fn set_class(&self, class: u64) -> Result<()> {
         self.open_path("net_cls.classid", true)
             .and_then(|mut file| {
                 let s = format!("{:#08X}", class);
-                file.write_all(s.as_ref()).map_err(|e| {
+                write_to(&mut file, &s).map_err(|e| {
                     Error::with_cause(WriteFailed("net_cls.classid".to_string(), s), e)
                 })
             })
     }
 
+    async_twin! {
+        /// Same as `set_class()`, but writes through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        set_class_async(&self, class: u64),
+        write "net_cls.classid", &format!("{:#08X}", class)
+    }
+
+    /// Set the network class id from a traffic control handle `major:minor`, i.e. packed as
+    /// `0xAAAABBBB` where `AAAA` is the major and `BBBB` the minor number.
+    pub fn set_class_handle(&self, major: u16, minor: u16) -> Result<()> {
+        self.set_class(class_id(major, minor))
+    }
+
     /// Get the network class id of the outgoing packets of the control group's tasks.
     pub fn get_class(&self) -> Result<u64> {
         self.open_path("net_cls.classid", false)
             .and_then(read_u64_from)
     }
+
+    async_twin! {
+        /// Same as `get_class()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        get_class_async(&self) -> u64,
+        read "net_cls.classid", parse_from
+    }
+}
+
+fn class_id(major: u16, minor: u16) -> u64 {
+    (u64::from(major) << 16) | u64::from(minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net_cls::class_id;
+
+    #[test]
+    fn test_class_id() {
+        assert_eq!(class_id(0x10, 0x1), 0x0010_0001);
+        assert_eq!(class_id(0, 0), 0);
+        assert_eq!(class_id(0xffff, 0xffff), 0xffff_ffff);
+    }
 }
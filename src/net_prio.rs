@@ -8,13 +8,13 @@
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/net_prio.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/net_prio.txt)
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::read_u64_from;
+use crate::util::{read_string_from, read_u64_from, write_to};
 use crate::{
     ControllIdentifier, ControllerInternal, Controllers, NetworkResources, Resources, Subsystem,
 };
@@ -49,7 +49,7 @@ impl ControllerInternal for NetPrioController {
         let res: &NetworkResources = &res.network;
 
         for i in &res.priorities {
-            let _ = self.set_if_prio(&i.name, i.priority);
+            self.set_if_prio(&i.name, i.priority)?;
         }
 
         Ok(())
@@ -64,15 +64,9 @@ impl ControllIdentifier for NetPrioController {
 
 impl<'a> From<&'a Subsystem> for &'a NetPrioController {
     fn from(sub: &'a Subsystem) -> &'a NetPrioController {
-        unsafe {
-            match sub {
-                Subsystem::NetPrio(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::NetPrio(c) => c,
+            _ => panic!("Subsystem is not a NetPrioController"),
         }
     }
 }
@@ -86,6 +80,17 @@ impl NetPrioController {
         }
     }
 
+    /// Constructs a `NetPrioController` for the root of the `net_prio` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn mounted() -> Result<Self> {
+        match hierarchies::controller_root(Controllers::NetPrio)? {
+            (root, false) => Ok(Self::new(root)),
+            _ => Err(Error::new(CgroupVersion)),
+        }
+    }
+
     /// Retrieves the current priority of the emitted packets.
     pub fn prio_idx(&self) -> u64 {
         self.open_path("net_prio.prioidx", false)
@@ -94,53 +99,59 @@ impl NetPrioController {
     }
 
     /// A map of priorities for each network interface.
-    #[allow(clippy::iter_nth_zero, clippy::unnecessary_unwrap)]
+    ///
+    /// Malformed lines are skipped rather than failing the whole read.
     pub fn ifpriomap(&self) -> Result<HashMap<String, u64>> {
         self.open_path("net_prio.ifpriomap", false)
-            .and_then(|file| {
-                let bf = BufReader::new(file);
-                bf.lines().fold(Ok(HashMap::new()), |acc, line| {
-                    if acc.is_err() {
-                        acc
-                    } else {
-                        let mut acc = acc.unwrap();
-                        let l = line.unwrap();
-                        let mut sp = l.split_whitespace();
-
-                        let ifname = sp.nth(0);
-                        let ifprio = sp.nth(1);
-                        if ifname.is_none() || ifprio.is_none() {
-                            Err(Error::new(ParseError))
-                        } else {
-                            let ifname = ifname.unwrap();
-                            let ifprio = ifprio.unwrap().trim().parse();
-                            match ifprio {
-                                Err(e) => Err(Error::with_cause(ParseError, e)),
-                                Ok(_) => {
-                                    acc.insert(ifname.to_string(), ifprio.unwrap());
-                                    Ok(acc)
-                                }
-                            }
-                        }
-                    }
-                })
-            })
+            .and_then(read_string_from)
+            .map(|s| parse_ifpriomap(&s))
     }
 
     /// Set the priority of the network traffic on `eif` to be `prio`.
     pub fn set_if_prio(&self, eif: &str, prio: u64) -> Result<()> {
         self.open_path("net_prio.ifpriomap", true)
             .and_then(|mut file| {
-                file.write_all(format!("{} {}", eif, prio).as_ref())
-                    .map_err(|e| {
-                        Error::with_cause(
-                            WriteFailed(
-                                "net_prio.ifpriomap".to_string(),
-                                format!("{} {}", eif, prio),
-                            ),
-                            e,
-                        )
-                    })
+                write_to(&mut file, &format!("{} {}", eif, prio)).map_err(|e| {
+                    Error::with_cause(
+                        WriteFailed(
+                            "net_prio.ifpriomap".to_string(),
+                            format!("{} {}", eif, prio),
+                        ),
+                        e,
+                    )
+                })
             })
     }
 }
+
+fn parse_ifpriomap(s: &str) -> HashMap<String, u64> {
+    s.lines()
+        .filter_map(|line| {
+            let mut sp = line.split_whitespace();
+            match (sp.next(), sp.next(), sp.next()) {
+                (Some(ifname), Some(ifprio), None) => {
+                    ifprio.parse().ok().map(|prio| (ifname.to_string(), prio))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net_prio::parse_ifpriomap;
+
+    #[test]
+    fn test_parse_ifpriomap() {
+        let map = parse_ifpriomap("lo 0\neth0 3\n");
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("lo"), Some(&0));
+        assert_eq!(map.get("eth0"), Some(&3));
+
+        let map = parse_ifpriomap("lo 0\nbroken\neth0 x\neth1 1 2\n\neth2 2\n");
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("lo"), Some(&0));
+        assert_eq!(map.get("eth2"), Some(&2));
+    }
+}
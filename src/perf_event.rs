@@ -9,7 +9,9 @@
 //!  [tools/perf/Documentation/perf-record.txt](https://raw.githubusercontent.com/torvalds/linux/master/tools/perf/Documentation/perf-record.txt)
 use std::path::PathBuf;
 
+use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
 use crate::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
 
@@ -50,15 +52,9 @@ impl ControllIdentifier for PerfEventController {
 
 impl<'a> From<&'a Subsystem> for &'a PerfEventController {
     fn from(sub: &'a Subsystem) -> &'a PerfEventController {
-        unsafe {
-            match sub {
-                Subsystem::PerfEvent(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::PerfEvent(c) => c,
+            _ => panic!("Subsystem is not a PerfEventController"),
         }
     }
 }
@@ -71,4 +67,15 @@ impl PerfEventController {
             path: root,
         }
     }
+
+    /// Constructs a `PerfEventController` for the root of the `perf_event` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn mounted() -> Result<Self> {
+        match hierarchies::controller_root(Controllers::PerfEvent)? {
+            (root, false) => Ok(Self::new(root)),
+            _ => Err(Error::new(CgroupVersion)),
+        }
+    }
 }
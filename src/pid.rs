@@ -8,13 +8,16 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroups-v1/pids.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/pids.txt)
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::read_u64_from;
+#[cfg(feature = "tokio")]
+use crate::util::parse_from;
+use crate::util::{read_string_from, read_u64_from, write_to};
 use crate::{
     parse_max_value, ControllIdentifier, ControllerInternal, Controllers, MaxValue, PidResources,
     Resources, Subsystem,
@@ -28,6 +31,41 @@ pub struct PidController {
     v2: bool,
 }
 
+/// The counters of `pids.events`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PidsEvents {
+    /// How many times `fork` failed because the limit of this control group was hit.
+    pub max: u64,
+    /// How many times `fork` failed because the limit of this control group or of one of its
+    /// ancestors was hit, reported by newer kernels as `max.imposed`.
+    pub max_imposed: u64,
+}
+
+/// Parse the `key value` lines of `pids.events`, ignoring missing and unknown keys.
+fn parse_pids_events(s: &str) -> Result<PidsEvents> {
+    let mut events = PidsEvents::default();
+
+    for line in s.lines() {
+        let mut spl = line.split_whitespace();
+        let (key, value) = match (spl.next(), spl.next()) {
+            (Some(k), Some(v)) => (k, v),
+            _ => continue,
+        };
+        let value = value
+            .parse::<u64>()
+            .map_err(|e| Error::with_cause(ParseError, e))?;
+
+        match key {
+            "max" => events.max = value,
+            "max.imposed" => events.max_imposed = value,
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
 impl ControllerInternal for PidController {
     fn control_type(&self) -> Controllers {
         Controllers::Pids
@@ -76,15 +114,9 @@ impl ControllIdentifier for PidController {
 
 impl<'a> From<&'a Subsystem> for &'a PidController {
     fn from(sub: &'a Subsystem) -> &'a PidController {
-        unsafe {
-            match sub {
-                Subsystem::Pid(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Pid(c) => c,
+            _ => panic!("Subsystem is not a PidController"),
         }
     }
 }
@@ -100,6 +132,18 @@ impl PidController {
         }
     }
 
+    /// Constructs a `PidController` for the root of the `pids` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::Pids)?;
+        Ok(Self::new(root, v2))
+    }
+
+    /// Reads back the value `apply()` sets from `res.pid`, see `Cgroup::snapshot()`.
+    pub(crate) fn snapshot(&self, res: &mut Resources) {
+        res.pid.maximum_number_of_processes = self.get_pid_max().ok();
+    }
+
     /// The number of times `fork` failed because the limit was hit.
     pub fn get_pid_events(&self) -> Result<u64> {
         self.open_path("pids.events", false).and_then(|mut file| {
@@ -117,12 +161,29 @@ impl PidController {
         })
     }
 
+    /// The counters found in `pids.events`.
+    ///
+    /// Unlike `get_pid_events()`, which returns the first counter of the file, this picks the
+    /// counters by name.
+    pub fn pid_events(&self) -> Result<PidsEvents> {
+        self.open_path("pids.events", false)
+            .and_then(read_string_from)
+            .and_then(|s| parse_pids_events(&s))
+    }
+
     /// The number of processes currently.
     pub fn get_pid_current(&self) -> Result<u64> {
         self.open_path("pids.current", false)
             .and_then(read_u64_from)
     }
 
+    async_twin! {
+        /// Same as `get_pid_current()`, but reads through `tokio::fs` so that the async runtime is
+        /// not blocked.
+        get_pid_current_async(&self) -> u64,
+        read "pids.current", parse_from
+    }
+
     /// The maximum number of processes that can exist at one time in the control group.
     pub fn get_pid_max(&self) -> Result<MaxValue> {
         self.open_path("pids.max", false).and_then(|mut file| {
@@ -135,6 +196,13 @@ impl PidController {
         })
     }
 
+    async_twin! {
+        /// Same as `get_pid_max()`, but reads through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        get_pid_max_async(&self) -> MaxValue,
+        read "pids.max", parse_max_value
+    }
+
     /// Set the maximum number of processes that can exist in this control group.
     ///
     /// Note that if `get_pid_current()` returns a higher number than what you
@@ -143,7 +211,7 @@ impl PidController {
     pub fn set_pid_max(&self, max_pid: MaxValue) -> Result<()> {
         self.open_path("pids.max", true).and_then(|mut file| {
             let string_to_write = max_pid.to_string();
-            match file.write_all(string_to_write.as_ref()) {
+            match write_to(&mut file, &string_to_write) {
                 Ok(_) => Ok(()),
                 Err(e) => Err(Error::with_cause(
                     WriteFailed("pids.max".to_string(), format!("{:?}", max_pid)),
@@ -152,4 +220,39 @@ impl PidController {
             }
         })
     }
+
+    async_twin! {
+        /// Same as `set_pid_max()`, but writes through `tokio::fs` so that the async runtime is not
+        /// blocked.
+        set_pid_max_async(&self, max_pid: MaxValue),
+        write "pids.max", &max_pid.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+    use crate::pid::{parse_pids_events, PidsEvents};
+
+    #[test]
+    fn test_parse_pids_events() {
+        assert_eq!(
+            parse_pids_events("max 3\n").unwrap(),
+            PidsEvents {
+                max: 3,
+                max_imposed: 0
+            }
+        );
+        assert_eq!(
+            parse_pids_events("max 3\nmax.imposed 7\n").unwrap(),
+            PidsEvents {
+                max: 3,
+                max_imposed: 7
+            }
+        );
+        assert_eq!(parse_pids_events("").unwrap(), PidsEvents::default());
+
+        let err = parse_pids_events("max x\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+    }
 }
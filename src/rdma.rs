@@ -7,14 +7,15 @@
 //!
 //! See the Kernel's documentation for more information about this subsystem, found at:
 //!  [Documentation/cgroup-v1/rdma.txt](https://www.kernel.org/doc/Documentation/cgroup-v1/rdma.txt)
-use std::io::Write;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crate::error::ErrorKind::*;
 use crate::error::*;
+use crate::hierarchies;
 
-use crate::read_string_from;
-use crate::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
+use crate::util::{read_string_from, write_to};
+use crate::{ControllIdentifier, ControllerInternal, Controllers, MaxValue, Resources, Subsystem};
 
 /// A controller that allows controlling the `rdma` subsystem of a Cgroup.
 ///
@@ -53,15 +54,9 @@ impl ControllIdentifier for RdmaController {
 
 impl<'a> From<&'a Subsystem> for &'a RdmaController {
     fn from(sub: &'a Subsystem) -> &'a RdmaController {
-        unsafe {
-            match sub {
-                Subsystem::Rdma(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Rdma(c) => c,
+            _ => panic!("Subsystem is not a RdmaController"),
         }
     }
 }
@@ -75,18 +70,132 @@ impl RdmaController {
         }
     }
 
-    /// Returns the current usage of RDMA/IB specific resources.
-    pub fn current(&self) -> Result<String> {
+    /// Constructs a `RdmaController` for the root of the `rdma` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    ///
+    /// This is only available on cgroup v1.
+    pub fn mounted() -> Result<Self> {
+        match hierarchies::controller_root(Controllers::Rdma)? {
+            (root, false) => Ok(Self::new(root)),
+            _ => Err(Error::new(CgroupVersion)),
+        }
+    }
+
+    /// Returns the current usage of RDMA/IB specific resources, keyed by device name.
+    pub fn current(&self) -> Result<HashMap<String, RdmaStat>> {
         self.open_path("rdma.current", false)
             .and_then(read_string_from)
+            .and_then(|s| parse_rdma_current(&s))
     }
 
-    /// Set a maximum usage for each RDMA/IB resource.
-    pub fn set_max(&self, max: &str) -> Result<()> {
+    /// Set the maximum number of HCA handles and objects that the tasks in this control group
+    /// can use on `device`.
+    pub fn set_max(&self, device: &str, hca_handle: MaxValue, hca_object: MaxValue) -> Result<()> {
+        let max = format!(
+            "{} hca_handle={} hca_object={}",
+            device, hca_handle, hca_object
+        );
         self.open_path("rdma.max", true).and_then(|mut file| {
-            file.write_all(max.as_ref()).map_err(|e| {
-                Error::with_cause(WriteFailed("rdma.max".to_string(), max.to_string()), e)
-            })
+            write_to(&mut file, &max)
+                .map_err(|e| Error::with_cause(WriteFailed("rdma.max".to_string(), max), e))
         })
     }
 }
+
+/// The usage of RDMA/IB specific resources on a single device.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RdmaStat {
+    /// Number of HCA handles in use.
+    pub hca_handle: u64,
+    /// Number of HCA objects in use.
+    pub hca_object: u64,
+}
+
+/// Parses `rdma.current`, which holds one line per device such as
+/// `mlx4_0 hca_handle=2 hca_object=2000`.
+///
+/// The pairs may come in any order and unknown keys are skipped, but a pair without a `=` or with
+/// a value that is not a number is a `ParseError`.
+fn parse_rdma_current(s: &str) -> Result<HashMap<String, RdmaStat>> {
+    let mut devices = HashMap::new();
+    for line in s.lines() {
+        let mut fields = line.split_whitespace();
+        let device = match fields.next() {
+            Some(device) => device,
+            None => continue,
+        };
+
+        let mut stat = RdmaStat::default();
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::new(ParseError))?;
+            let value = value
+                .parse::<u64>()
+                .map_err(|e| Error::with_cause(ParseError, e))?;
+            match key {
+                "hca_handle" => stat.hca_handle = value,
+                "hca_object" => stat.hca_object = value,
+                _ => {}
+            }
+        }
+        devices.insert(device.to_string(), stat);
+    }
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::util::tests::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_parse_rdma_current() {
+        let devices = parse_rdma_current("mlx4_0 hca_handle=2 hca_object=2000\n").unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(
+            devices["mlx4_0"],
+            RdmaStat {
+                hca_handle: 2,
+                hca_object: 2000
+            }
+        );
+
+        let devices =
+            parse_rdma_current("mlx4_0  hca_object=5 future=1 hca_handle=3\n\nocrdma1\n").unwrap();
+        assert_eq!(devices["mlx4_0"].hca_handle, 3);
+        assert_eq!(devices["mlx4_0"].hca_object, 5);
+        assert_eq!(devices["ocrdma1"], RdmaStat::default());
+
+        for bad in ["mlx4_0 hca_handle", "mlx4_0 hca_handle=max"].iter() {
+            let err = parse_rdma_current(bad).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError);
+        }
+    }
+
+    #[test]
+    fn test_rdma_current_and_set_max() {
+        let dir = TempDir::new("rdma_current_and_set_max");
+        fs::write(
+            dir.join("rdma.current"),
+            "mlx4_0 hca_handle=1 hca_object=20\n",
+        )
+        .unwrap();
+        fs::write(dir.join("rdma.max"), "").unwrap();
+        let rdma = RdmaController::new(dir.to_path_buf());
+
+        let current = rdma.current().unwrap();
+        assert_eq!(current["mlx4_0"].hca_handle, 1);
+        assert_eq!(current["mlx4_0"].hca_object, 20);
+
+        rdma.set_max("mlx4_0", MaxValue::Value(2), MaxValue::Max)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("rdma.max")).unwrap(),
+            "mlx4_0 hca_handle=2 hca_object=max"
+        );
+    }
+}
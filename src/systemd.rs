@@ -8,6 +8,7 @@
 use std::path::PathBuf;
 
 use crate::error::*;
+use crate::hierarchies;
 
 use crate::{ControllIdentifier, ControllerInternal, Controllers, Resources, Subsystem};
 
@@ -47,15 +48,9 @@ impl ControllIdentifier for SystemdController {
 
 impl<'a> From<&'a Subsystem> for &'a SystemdController {
     fn from(sub: &'a Subsystem) -> &'a SystemdController {
-        unsafe {
-            match sub {
-                Subsystem::Systemd(c) => c,
-                _ => {
-                    assert_eq!(1, 0);
-                    let v = std::mem::MaybeUninit::uninit();
-                    v.assume_init()
-                }
-            }
+        match sub {
+            Subsystem::Systemd(c) => c,
+            _ => panic!("Subsystem is not a SystemdController"),
         }
     }
 }
@@ -69,4 +64,11 @@ impl SystemdController {
             _v2: v2,
         }
     }
+
+    /// Constructs a `SystemdController` for the root of the `name=systemd` hierarchy found in
+    /// `/proc/self/mountinfo`, see `hierarchies::controller_root()`.
+    pub fn mounted() -> Result<Self> {
+        let (root, v2) = hierarchies::controller_root(Controllers::Systemd)?;
+        Ok(Self::new(root, v2))
+    }
 }
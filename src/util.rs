@@ -0,0 +1,453 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Helpers reading and writing control files, shared by all the controllers.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use nix::fcntl::{self, OFlag};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use nix::sys::stat::Mode;
+
+use crate::error::ErrorKind::*;
+use crate::error::*;
+
+/// parse the trimmed content of a control file
+pub(crate) fn parse_from<T>(string: &str) -> Result<T>
+where
+    T: FromStr,
+    <T as FromStr>::Err: 'static + Send + Sync + std::error::Error,
+{
+    string
+        .trim()
+        .parse::<T>()
+        .map_err(|e| Error::with_cause(ParseError, e))
+}
+
+/// parse a boolean written as `1` or `0`
+pub(crate) fn parse_bool(string: &str) -> Result<bool> {
+    match parse_from::<u64>(string)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Error::new(ParseError)),
+    }
+}
+
+/// The path `file` was opened from, recovered through `/proc/self/fd` so that the readers below
+/// can name the control file in their errors.
+pub(crate) fn file_path(file: &File) -> String {
+    std::fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd()))
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "<unknown>".to_string())
+}
+
+fn read_from<T>(mut file: File) -> Result<T>
+where
+    T: FromStr,
+    <T as FromStr>::Err: 'static + Send + Sync + std::error::Error,
+{
+    let mut string = String::new();
+    match file.read_to_string(&mut string) {
+        Ok(_) => parse_from(&string).map_err(|e| e.with_path(file_path(&file))),
+        Err(e) => Err(Error::with_cause(ReadFailed(file_path(&file)), e)),
+    }
+}
+
+pub(crate) fn read_string_from(mut file: File) -> Result<String> {
+    let mut string = String::new();
+    match file.read_to_string(&mut string) {
+        Ok(_) => Ok(string.trim().to_string()),
+        Err(e) => Err(Error::with_cause(ReadFailed(file_path(&file)), e)),
+    }
+}
+
+/// read and parse an u64 data
+pub(crate) fn read_u64_from(file: File) -> Result<u64> {
+    read_from::<u64>(file)
+}
+
+/// read and parse an i64 data
+pub(crate) fn read_i64_from(file: File) -> Result<i64> {
+    read_from::<i64>(file)
+}
+
+/// read a boolean written as `1` or `0`
+pub(crate) fn read_bool_from(mut file: File) -> Result<bool> {
+    let mut string = String::new();
+    match file.read_to_string(&mut string) {
+        Ok(_) => parse_bool(&string).map_err(|e| e.with_path(file_path(&file))),
+        Err(e) => Err(Error::with_cause(ReadFailed(file_path(&file)), e)),
+    }
+}
+
+/// write an u64 data to the control file `file_name`
+pub(crate) fn write_u64_to(mut file: File, file_name: &str, value: u64) -> Result<()> {
+    let value = value.to_string();
+    write_to(&mut file, &value)
+        .map_err(|e| Error::with_cause(WriteFailed(file_name.to_string(), value), e))
+}
+
+/// write `value` to a control file opened with `open_path()`
+///
+/// With the `log` feature, every write is logged at debug level along with the path of the file,
+/// and failed ones at warn level.
+pub(crate) fn write_to(file: &mut File, value: &str) -> std::io::Result<()> {
+    let res = file.write_all(value.as_bytes());
+    #[cfg(feature = "log")]
+    match &res {
+        Ok(()) => log::debug!("wrote {:?} to {}", value, file_path(file)),
+        Err(e) => log::warn!("failed to write {:?} to {}: {}", value, file_path(file), e),
+    }
+    res
+}
+
+/// write a boolean, as `1` or `0`, to the control file `file_name`
+pub(crate) fn write_bool_to(file: File, file_name: &str, value: bool) -> Result<()> {
+    write_u64_to(file, file_name, value as u64)
+}
+
+/// Keeps control files open between reads, re-reading them from the start with `pread(2)`
+/// instead of opening them again.
+///
+/// The cache starts out disabled, in which case every read opens the file. A clone always starts
+/// with an empty, disabled cache so that the descriptors are never shared between controllers.
+#[derive(Debug, Default)]
+pub(crate) struct FdCache {
+    files: Mutex<Option<HashMap<PathBuf, File>>>,
+}
+
+impl Clone for FdCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl FdCache {
+    /// Enable or disable the cache. Disabling it closes every cached descriptor.
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        let mut files = self.files.lock().unwrap_or_else(PoisonError::into_inner);
+        if !enabled {
+            *files = None;
+        } else if files.is_none() {
+            *files = Some(HashMap::new());
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Read the control file at `path`, reusing its cached descriptor if there is one. `open` is
+    /// called when the file is not cached yet, or on every read if the cache is disabled.
+    ///
+    /// A failed read drops the descriptor, or every cached descriptor if the control group
+    /// directory is gone, since reads from a removed control group fail.
+    pub(crate) fn read_string<F>(&self, path: &Path, open: F) -> Result<String>
+    where
+        F: FnOnce() -> Result<File>,
+    {
+        let mut guard = self.files.lock().unwrap_or_else(PoisonError::into_inner);
+        let files = match guard.as_mut() {
+            Some(files) => files,
+            None => return open().and_then(read_string_from),
+        };
+
+        let file = match files.entry(path.to_path_buf()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(open()?),
+        };
+        match read_at_start(file) {
+            Ok(string) => Ok(string.trim().to_string()),
+            Err(e) => {
+                if path.parent().is_some_and(|dir| dir.is_dir()) {
+                    files.remove(path);
+                } else {
+                    files.clear();
+                }
+                Err(Error::with_cause(ReadFailed(path.display().to_string()), e))
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn cached_fd(&self, path: &Path) -> Option<std::os::unix::io::RawFd> {
+        use std::os::unix::io::AsRawFd;
+
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .and_then(|files| files.get(path))
+            .map(|file| file.as_raw_fd())
+    }
+}
+
+/// A control group directory opened once, so that its control files can be opened relative to it
+/// with `openat(2)` instead of resolving the full path each time.
+#[derive(Debug)]
+pub(crate) struct ControlDir {
+    dir: File,
+    path: PathBuf,
+}
+
+impl ControlDir {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let fd = fcntl::open(
+            path,
+            OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )
+        .map_err(|e| Error::with_cause(ReadFailed(path.display().to_string()), e))?;
+        Ok(Self {
+            dir: unsafe { File::from_raw_fd(fd) },
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Read the control file `name`, mapping errors the same way as `open_path()` does.
+    pub(crate) fn read_string(&self, name: &str) -> Result<String> {
+        let fd = fcntl::openat(
+            self.dir.as_raw_fd(),
+            name,
+            OFlag::O_RDONLY | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )
+        .map_err(|e| {
+            let path = self.path.join(name);
+            let e = std::io::Error::from(e);
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::with_cause(NotSupported, e)
+            } else {
+                Error::with_cause(ReadFailed(path.display().to_string()), e)
+            }
+        })?;
+        read_string_from(unsafe { File::from_raw_fd(fd) })
+    }
+}
+
+/// Waits for a control file to change, for the watchers re-reading it on every change.
+#[derive(Debug)]
+pub(crate) struct FileWatch {
+    inotify: Inotify,
+    path: PathBuf,
+}
+
+impl FileWatch {
+    // The kernel does not send modification events for every cgroup file, so callers also
+    // re-read the file at this interval.
+    const POLL_INTERVAL_MS: libc::c_int = 100;
+
+    pub(crate) fn new(path: PathBuf) -> Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .map_err(|e| Error::with_cause(FsError, e))?;
+        let watch = FileWatch { inotify, path };
+        watch.arm()?;
+        Ok(watch)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn arm(&self) -> Result<()> {
+        self.inotify
+            .add_watch(
+                &self.path,
+                AddWatchFlags::IN_MODIFY | AddWatchFlags::IN_DELETE_SELF,
+            )
+            .map(|_| ())
+            .map_err(|e| Error::with_cause(FsError, e))
+    }
+
+    /// Returns once the file was modified or the poll interval elapsed.
+    pub(crate) fn wait(&self) -> Result<()> {
+        let mut fds = [libc::pollfd {
+            fd: self.inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        unsafe { libc::poll(fds.as_mut_ptr(), 1, Self::POLL_INTERVAL_MS) };
+
+        match self.inotify.read_events() {
+            Ok(events) => {
+                // The file was recreated, watch the new one.
+                if events
+                    .iter()
+                    .any(|e| e.mask.contains(AddWatchFlags::IN_IGNORED))
+                {
+                    self.arm()?;
+                }
+                Ok(())
+            }
+            Err(nix::errno::Errno::EAGAIN) => Ok(()),
+            Err(e) => Err(Error::with_cause(FsError, e)),
+        }
+    }
+}
+
+impl Drop for FileWatch {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.inotify.as_raw_fd()) };
+    }
+}
+
+fn read_at_start(file: &File) -> std::io::Result<String> {
+    let mut content = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = file.read_at(&mut buf, content.len() as u64)?;
+        if n == 0 {
+            break;
+        }
+        content.extend_from_slice(&buf[..n]);
+    }
+    String::from_utf8(content).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Asynchronously read the control file at `path`, mapping errors the same way as
+/// `open_path()` does.
+#[cfg(feature = "tokio")]
+pub(crate) async fn read_string_async(path: &Path) -> Result<String> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(string) => Ok(string.trim().to_string()),
+        Err(e)
+            if e.kind() == std::io::ErrorKind::NotFound
+                && path.parent().is_some_and(|dir| dir.is_dir()) =>
+        {
+            Err(Error::with_cause(NotSupported, e))
+        }
+        Err(e) => Err(Error::with_cause(ReadFailed(path.display().to_string()), e)),
+    }
+}
+
+/// Asynchronously write `value` to the control file at `path`, logged like `write_to()`
+#[cfg(feature = "tokio")]
+pub(crate) async fn write_async(path: &Path, value: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let res = tokio::fs::write(path, value).await;
+    #[cfg(feature = "log")]
+    match &res {
+        Ok(()) => log::debug!("wrote {:?} to {}", value, path.display()),
+        Err(e) => log::warn!("failed to write {:?} to {}: {}", value, path.display(), e),
+    }
+    res.map_err(|e| Error::with_cause(WriteFailed(file_name, value.to_string()), e))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use std::fs;
+    use std::ops::Deref;
+
+    fn temp_file(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("cgroups-rs-util-{}", name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// A fresh directory to stand in for a control group, unique to the test process and removed
+    /// when dropped, even if the test panics.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "cgroups-rs-test_{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_read_u64_from() {
+        let path = temp_file("read_u64_from", "42\n");
+        assert_eq!(read_u64_from(File::open(&path).unwrap()).unwrap(), 42);
+
+        fs::write(&path, "4x2\n").unwrap();
+        let err = read_u64_from(File::open(&path).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+
+        fs::write(&path, "-1\n").unwrap();
+        let err = read_u64_from(File::open(&path).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+        assert_eq!(read_i64_from(File::open(&path).unwrap()).unwrap(), -1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_error_names_file() {
+        let path = temp_file("read_error_names_file", "abc\n");
+        let err = read_u64_from(File::open(&path).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::ParseError);
+        assert_eq!(err.path(), Some(path.as_path()));
+        assert!(err
+            .to_string()
+            .contains("cgroups-rs-util-read_error_names_file"));
+
+        let err = read_bool_from(File::open(&path).unwrap()).unwrap_err();
+        assert_eq!(err.path(), Some(path.as_path()));
+
+        // A write-only descriptor fails the read itself.
+        let err =
+            read_string_from(fs::OpenOptions::new().write(true).open(&path).unwrap()).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::ReadFailed(path.display().to_string())
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_to() {
+        let path = temp_file("write_to", "");
+        write_u64_to(File::create(&path).unwrap(), "test", 1024).unwrap();
+        assert_eq!(
+            read_string_from(File::open(&path).unwrap()).unwrap(),
+            "1024"
+        );
+
+        write_bool_to(File::create(&path).unwrap(), "test", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+        write_bool_to(File::create(&path).unwrap(), "test", false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the blkio subsystem
+use cgroups_rs::blkio::{BlkIoController, BlkIoData, IoMax};
+use cgroups_rs::error::ErrorKind;
+use cgroups_rs::Controller;
+use cgroups_rs::{Cgroup, DeviceNumber};
+
+use std::fs;
+
+/// Returns the device number of a whole disk, if there is any.
+fn find_disk() -> Option<DeviceNumber> {
+    fs::read_dir("/sys/block").ok()?.find_map(|entry| {
+        let entry = entry.ok()?;
+        let name = entry.file_name().into_string().ok()?;
+        if name.starts_with("loop") || name.starts_with("ram") || name.starts_with("zram") {
+            return None;
+        }
+        let dev = fs::read_to_string(entry.path().join("dev")).ok()?;
+        dev.parse().ok()
+    })
+}
+
+#[test]
+fn test_blkio_weight_device() {
+    let dev = match find_disk() {
+        Some(dev) => dev,
+        None => return,
+    };
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_blkio_weight_device")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        // blkio.weight_device is only there with the CFQ or BFQ schedulers.
+        if !blkio.v2() && blkio.path().join("blkio.weight_device").exists() {
+            blkio.set_weight_for_device(dev, 500).unwrap();
+            assert!(blkio.blkio().weight_device.contains(&BlkIoData {
+                major: dev.major as i16,
+                minor: dev.minor as i16,
+                data: 500,
+            }));
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_blkio_throttle_read_bps() {
+    let dev = match find_disk() {
+        Some(dev) => dev,
+        None => return,
+    };
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_blkio_throttle_read_bps")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        if !blkio.v2() {
+            blkio.throttle_read_bps_for_device(dev, 1048576).unwrap();
+            assert_eq!(
+                blkio.blkio().throttle.read_bps_device,
+                vec![BlkIoData {
+                    major: dev.major as i16,
+                    minor: dev.minor as i16,
+                    data: 1048576,
+                }]
+            );
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_blkio_io_max() {
+    let dev = match find_disk() {
+        Some(dev) => dev,
+        None => return,
+    };
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_blkio_io_max")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        let limits = IoMax {
+            rbps: Some(1048576),
+            ..Default::default()
+        };
+        if blkio.v2() {
+            blkio.set_io_max(dev, limits).unwrap();
+            assert_eq!(blkio.io_max().unwrap().get(&dev), Some(&limits));
+            // A device only shows up in io.stat once it has been used.
+            let stats = blkio.io_stat().unwrap();
+            if let Some(stat) = stats.get(&dev) {
+                assert_eq!(stat.major as u64, dev.major);
+            }
+        } else {
+            let err = blkio.set_io_max(dev, limits).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+            let err = blkio.io_stat().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_blkio_io_weight() {
+    let dev = match find_disk() {
+        Some(dev) => dev,
+        None => return,
+    };
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_blkio_io_weight")).unwrap();
+    {
+        let blkio: &BlkIoController = cg.controller_of().unwrap();
+        for weight in [0, 10001].iter() {
+            let err = blkio.set_io_device_weight(dev, *weight).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError);
+        }
+
+        // io.weight needs an I/O scheduler supporting proportional weights, e.g. BFQ.
+        if blkio.v2() && blkio.path().join("io.weight").exists() {
+            blkio.set_io_weight(150).unwrap();
+            blkio.set_io_device_weight(dev, 200).unwrap();
+            let weight = blkio.io_weight().unwrap();
+            assert_eq!(weight.default, 150);
+            assert_eq!(weight.devices.get(&dev), Some(&200));
+        } else if !blkio.v2() {
+            let err = blkio.set_io_weight(100).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        }
+    }
+    cg.delete().unwrap();
+}
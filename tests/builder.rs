@@ -167,3 +167,32 @@ pub fn test_blkio_res_build() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+pub fn test_resources_builder() {
+    let res = ResourcesBuilder::new()
+        .cpu_shares(1024)
+        .cpuset_cpus("0-3")
+        .memory_limit(1 << 30)
+        .pids_max(MaxValue::Value(100))
+        .build();
+
+    // Only the configured limits are set, everything else is left for apply() to skip.
+    let expected = Resources {
+        cpu: CpuResources {
+            shares: Some(1024),
+            cpus: Some("0-3".to_string()),
+            ..Default::default()
+        },
+        memory: MemoryResources {
+            memory_hard_limit: Some(1 << 30),
+            ..Default::default()
+        },
+        pid: PidResources {
+            maximum_number_of_processes: Some(MaxValue::Value(100)),
+        },
+        ..Default::default()
+    };
+    assert_eq!(res, expected);
+    assert_eq!(ResourcesBuilder::new().build(), Resources::default());
+}
@@ -5,9 +5,13 @@
 //
 
 //! Simple unit tests about the control groups system.
+use cgroups_rs::cpu::CpuController;
+use cgroups_rs::error::ErrorKind;
 use cgroups_rs::memory::MemController;
+use cgroups_rs::pid::PidController;
 use cgroups_rs::Controller;
-use cgroups_rs::{Cgroup, CgroupPid, Subsystem};
+use cgroups_rs::{Cgroup, CgroupPid, Controllers, Subsystem};
+use std::path::PathBuf;
 use std::process::Command;
 use std::thread::sleep;
 use std::time::Duration;
@@ -74,7 +78,7 @@ fn test_kill_cgroup() {
                 }
             }
         };
-        assert!(!status.is_none());
+        assert!(status.is_some());
     }
     cg.delete().unwrap();
 }
@@ -200,3 +204,415 @@ fn test_tasks_iterator_cgroup_threaded_mode() {
     cg_threaded.delete().unwrap();
     cg.delete().unwrap();
 }
+
+#[test]
+#[should_panic(expected = "Subsystem is not a CpuController")]
+fn test_subsystem_into_wrong_controller() {
+    let sub = Subsystem::Mem(MemController::new(
+        PathBuf::from("/sys/fs/cgroup/memory"),
+        false,
+    ));
+    let _cpu: &CpuController = (&sub).into();
+}
+
+#[test]
+fn test_subsystem_try_as_controller() {
+    let sub = Subsystem::Mem(MemController::new(
+        PathBuf::from("/sys/fs/cgroup/memory"),
+        false,
+    ));
+    assert!(sub.try_as_controller::<MemController>().is_ok());
+
+    let err = sub.try_as_controller::<CpuController>().unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::WrongSubsystem);
+}
+
+#[test]
+fn test_controller_add_task() {
+    // On v2, tasks are moved via cgroup.threads which requires a threaded cgroup.
+    if cgroups_rs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_controller_add_task")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        assert!(pids.tasks().is_empty());
+
+        // sleep is single threaded, so its pid is also its only tid.
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        let pid = CgroupPid::from(&child);
+        pids.add_task(&pid).unwrap();
+
+        assert_eq!(pids.tasks(), vec![pid]);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_controller_create_and_delete() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_controller_create_and_delete")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        let nested = PidController::new(pids.path().join("nested"), cg.v2());
+        assert!(!nested.exists());
+
+        nested.create().unwrap();
+        assert!(nested.path().is_dir());
+
+        let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+        nested.add_task_by_tgid(&CgroupPid::from(&child)).unwrap();
+
+        // The kernel refuses to remove a control group with tasks attached.
+        let err = nested.delete().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Busy(_)));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        nested.delete().unwrap();
+        assert!(!nested.exists());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_missing_control_group_is_not_unsupported() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_missing_control_group")).unwrap();
+    let pids: &PidController = cg.controller_of().unwrap();
+    let missing = PidController::new(pids.path().join("missing"), cg.v2());
+
+    // The control file is absent because the control group is, not because the
+    // kernel lacks it.
+    let err = missing.get_pid_current().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::ReadFailed(_)));
+
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_subtree_control() {
+    let h = cgroups_rs::hierarchies::auto();
+    if !cgroups_rs::hierarchies::is_cgroup2_unified_mode() {
+        let cg = Cgroup::new(h, String::from("test_subtree_control")).unwrap();
+        let err = cg.enable_controllers(&[Controllers::Cpu]).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        cg.delete().unwrap();
+        return;
+    }
+
+    let child = Cgroup::new_with_specified_controllers(
+        h,
+        String::from("test_subtree_control/child"),
+        Some(vec![String::from("pids")]),
+    )
+    .unwrap();
+    let parent = child.parent_control_group();
+    {
+        parent.enable_controllers(&[Controllers::Cpu]).unwrap();
+        assert!(child
+            .available_controllers()
+            .unwrap()
+            .contains(&Controllers::Cpu));
+
+        parent.disable_controllers(&[Controllers::Cpu]).unwrap();
+        assert!(!child
+            .available_controllers()
+            .unwrap()
+            .contains(&Controllers::Cpu));
+    }
+    child.delete().unwrap();
+    parent.delete().unwrap();
+}
+
+#[test]
+fn test_notify_on_release() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_notify_on_release")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        if cg.v2() {
+            let err = pids.notify_on_release().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        } else {
+            pids.set_notify_on_release(true).unwrap();
+            assert!(pids.notify_on_release().unwrap());
+            pids.set_notify_on_release(false).unwrap();
+            assert!(!pids.notify_on_release().unwrap());
+
+            // release_agent only exists at the root of the hierarchy.
+            let err = pids.release_agent().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::NotSupported);
+            let root = PidController::new(pids.path().parent().unwrap().to_path_buf(), false);
+            assert!(root.release_agent().is_ok());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_controller_exists() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_controller_exists")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        assert!(pids.exists());
+
+        let missing = PidController::new(pids.path().join("missing"), cg.v2());
+        assert!(!missing.exists());
+        missing.create().unwrap();
+        assert!(missing.exists());
+        missing.delete().unwrap();
+
+        // A control file is not a control group.
+        let file = PidController::new(pids.path().join("cgroup.procs"), cg.v2());
+        assert!(!file.exists());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_controller_nested() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_controller_nested")).unwrap();
+    {
+        let parent: &PidController = cg.controller_of().unwrap();
+        let child = parent.nested("child").unwrap();
+        assert_eq!(child.path(), parent.path().join("child"));
+        assert!(!child.exists());
+
+        child.create().unwrap();
+        assert!(child.exists());
+        child.delete().unwrap();
+
+        for name in ["", ".", "..", "a/b", "../escape"] {
+            let err = parent.nested(name).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::InvalidPath);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_controller_children() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_controller_children")).unwrap();
+    {
+        let parent: &PidController = cg.controller_of().unwrap();
+        assert!(parent.children().unwrap().is_empty());
+
+        let b = parent.nested("b").unwrap();
+        let a = parent.nested("a").unwrap();
+        b.create().unwrap();
+        a.create().unwrap();
+        a.nested("grandchild").unwrap().create().unwrap();
+
+        let children = parent.children().unwrap();
+        let paths = children.iter().map(|c| c.path()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![a.path(), b.path()]);
+
+        a.nested("grandchild").unwrap().delete().unwrap();
+        a.delete().unwrap();
+        b.delete().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_controller_walk() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_controller_walk")).unwrap();
+    {
+        let root: &PidController = cg.controller_of().unwrap();
+        let a = root.nested("a").unwrap();
+        let a1 = a.nested("a1").unwrap();
+        let a2 = a.nested("a2").unwrap();
+        let b = root.nested("b").unwrap();
+        let b1 = b.nested("b1").unwrap();
+        for c in [&a, &a1, &a2, &b, &b1] {
+            c.create().unwrap();
+        }
+
+        let visited = root
+            .walk()
+            .map(|c| c.path().to_path_buf())
+            .collect::<Vec<_>>();
+        let expected = [root, &a, &a1, &a2, &b, &b1]
+            .iter()
+            .map(|c| c.path().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(visited, expected);
+
+        // b1 and a2 go away after they were listed by their parents.
+        let mut walk = a.walk();
+        assert_eq!(walk.next().unwrap().path(), a.path());
+        a2.delete().unwrap();
+        assert_eq!(walk.next().unwrap().path(), a1.path());
+        assert!(walk.next().is_none());
+
+        let mut walk = root.walk();
+        walk.next().unwrap();
+        b1.delete().unwrap();
+        let visited = walk.map(|c| c.path().to_path_buf()).collect::<Vec<_>>();
+        assert_eq!(visited, vec![a.path(), a1.path(), b.path()]);
+
+        for c in [&a1, &a, &b] {
+            c.delete().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_controller_relative_path() {
+    let h = cgroups_rs::hierarchies::auto();
+    let root = h.root_control_group();
+    let cg = Cgroup::new(h, String::from("test_controller_relative_path")).unwrap();
+    {
+        let root: &PidController = root.controller_of().unwrap();
+        assert_eq!(root.relative_path(), PathBuf::new());
+
+        let pids: &PidController = cg.controller_of().unwrap();
+        assert_eq!(
+            pids.relative_path(),
+            PathBuf::from("test_controller_relative_path")
+        );
+        let b = pids.nested("a").unwrap().nested("b").unwrap();
+        assert_eq!(
+            b.relative_path(),
+            PathBuf::from("test_controller_relative_path/a/b")
+        );
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_move_all_tasks_to() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_move_all_tasks_to")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        let src = pids.nested("src").unwrap();
+        let dest = pids.nested("dest").unwrap();
+        src.create().unwrap();
+        dest.create().unwrap();
+
+        let mut children = (0..3)
+            .map(|_| Command::new("sleep").arg("infinity").spawn().unwrap())
+            .collect::<Vec<_>>();
+        let mut expected = children.iter().map(CgroupPid::from).collect::<Vec<_>>();
+        expected.sort();
+        for pid in &expected {
+            src.add_task_by_tgid(pid).unwrap();
+        }
+
+        let err = src.move_all_tasks_to(&src).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidOperation);
+        assert_eq!(src.move_all_tasks_to(&dest).unwrap(), 3);
+        assert!(src.procs().is_empty());
+        let mut procs = dest.procs();
+        procs.sort();
+        assert_eq!(procs, expected);
+
+        for child in &mut children {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+        src.delete().unwrap();
+        dest.delete().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cgroup_events() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cgroup_events")).unwrap();
+    if cg.v2() {
+        let events = cg.events().unwrap();
+        assert!(!events.populated);
+        assert!(!events.frozen);
+    } else {
+        assert_eq!(cg.events().unwrap_err().kind(), &ErrorKind::CgroupVersion);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_watch_populated() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_watch_populated")).unwrap();
+    if !cg.v2() {
+        let err = cg.watch_populated().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        cg.delete().unwrap();
+        return;
+    }
+
+    let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+    cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+    let mut watcher = cg.watch_populated().unwrap();
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(!watcher.next().unwrap().unwrap());
+
+    cg.delete().unwrap();
+    assert!(watcher.next().is_none());
+}
+
+#[test]
+fn test_statistics() {
+    let h = cgroups_rs::hierarchies::auto();
+    let v2 = h.v2();
+    let cg = Cgroup::new(h, String::from("test_statistics")).unwrap();
+    {
+        let stats = cg.statistics();
+        let throttling = stats.cpu_throttling.unwrap();
+        assert_eq!(throttling.nr_throttled, 0);
+        assert!(stats.memory.is_some());
+        assert_eq!(stats.pids_current, Some(0));
+        if v2 {
+            assert_eq!(stats.cpuacct_usage, None);
+        } else {
+            assert!(stats.cpuacct_usage.is_some());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_attach_self() {
+    // Attaching moves the whole process, so run it in a child instead of the test runner.
+    if std::env::var_os("CGROUPS_RS_TEST_ATTACH_SELF").is_some() {
+        let h = cgroups_rs::hierarchies::auto();
+        let cg = Cgroup::load(h, "test_attach_self");
+        let me = CgroupPid::from(std::process::id() as u64);
+
+        cg.attach_self().unwrap();
+        assert_eq!(cg.procs(), vec![me]);
+
+        cg.escape_to_root().unwrap();
+        assert!(cg.procs().is_empty());
+        return;
+    }
+
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_attach_self")).unwrap();
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["test_attach_self", "--exact", "--nocapture"])
+        .env("CGROUPS_RS_TEST_ATTACH_SELF", "1")
+        .output()
+        .unwrap();
+    cg.delete().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -5,7 +5,9 @@
 
 //! Simple unit tests about the CPU control groups system.
 use cgroups_rs::cpu::CpuController;
-use cgroups_rs::Cgroup;
+use cgroups_rs::cpuset::online_cpus;
+use cgroups_rs::error::ErrorKind;
+use cgroups_rs::{Cgroup, Controller};
 
 #[test]
 fn test_cfs_quota_and_periods() {
@@ -59,3 +61,104 @@ fn test_cfs_quota_and_periods() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_cpu_half_quota() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpu_half_quota")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+
+        // 50% of a CPU
+        cpu_controller.set_cfs_period(100000).unwrap();
+        cpu_controller.set_cfs_quota(50000).unwrap();
+        assert_eq!(cpu_controller.cfs_quota().unwrap(), 50000);
+        assert_eq!(cpu_controller.cfs_period().unwrap(), 100000);
+
+        let stat = cpu_controller.cpu_stat().unwrap();
+        assert_eq!(stat.nr_throttled, 0);
+        assert_eq!(stat.throttled_time, 0);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpu_limit_percent() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpu_limit_percent")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+
+        cpu_controller.set_cfs_period(100000).unwrap();
+        cpu_controller.set_cpu_limit_percent(50.0).unwrap();
+        let cpus = online_cpus().unwrap().len() as i64;
+        assert_eq!(cpu_controller.cfs_quota().unwrap(), 50000 * cpus);
+        assert_eq!(cpu_controller.cfs_period().unwrap(), 100000);
+
+        for percent in [-1.0, 0.0, f64::NAN, f64::INFINITY] {
+            let err = cpu_controller.set_cpu_limit_percent(percent).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::OutOfRange(..)));
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpu_uclamp() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpu_uclamp")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+        match cpu_controller.set_uclamp_max(80.0) {
+            Ok(()) => assert_eq!(cpu_controller.uclamp_max().unwrap(), 80.0),
+            Err(e) => assert_eq!(e.kind(), &ErrorKind::NotSupported),
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpu_rt_runtime() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpu_rt_runtime")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+        if !cpu_controller.path().join("cpu.rt_runtime_us").exists() {
+            // CONFIG_RT_GROUP_SCHED is not set
+            let err = cpu_controller.rt_runtime().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::NotSupported);
+        } else {
+            assert_eq!(cpu_controller.rt_runtime().unwrap(), 0);
+            assert!(cpu_controller.rt_period_us().unwrap() > 0);
+
+            cpu_controller.set_rt_runtime(1000).unwrap();
+            assert_eq!(cpu_controller.rt_runtime().unwrap(), 1000);
+
+            cpu_controller.set_rt_runtime(0).unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cfs_quota_writes_cpu_max_on_v2() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cfs_quota_writes_cpu_max_on_v2")).unwrap();
+    {
+        let cpu_controller: &CpuController = cg.controller_of().unwrap();
+        // The unified hierarchy exposes cgroup.controllers at its root.
+        let unified = std::path::Path::new(cgroups_rs::hierarchies::UNIFIED_MOUNTPOINT)
+            .join("cgroup.controllers")
+            .exists();
+        assert_eq!(cg.v2(), unified);
+
+        if cg.v2() {
+            cpu_controller.set_cfs_quota(2000).unwrap();
+            let max = std::fs::read_to_string(cpu_controller.path().join("cpu.max")).unwrap();
+            assert_eq!(max.trim(), "2000 100000");
+        } else {
+            assert!(cpu_controller.path().join("cpu.cfs_quota_us").exists());
+        }
+    }
+    cg.delete().unwrap();
+}
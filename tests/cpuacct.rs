@@ -0,0 +1,83 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the cpuacct subsystem
+use cgroups_rs::cpuacct::CpuAcctController;
+use cgroups_rs::{Cgroup, CgroupPid, Controller};
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_cpuacct_usage() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuacct_usage")).unwrap();
+    {
+        // cpuacct is only available on cgroup v1
+        let cpuacct: Option<&CpuAcctController> = cg.controller_of();
+        if let Some(cpuacct) = cpuacct {
+            assert_eq!(cpuacct.usage().unwrap(), 0);
+            assert!(cpuacct.usage_percpu().unwrap().iter().all(|x| *x == 0));
+
+            // The child waits to be moved into the control group before burning some CPU.
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg("read x; i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done")
+                .stdin(Stdio::piped())
+                .spawn()
+                .unwrap();
+            cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+            child.stdin.take().unwrap().write_all(b"\n").unwrap();
+            assert!(child.wait().unwrap().success());
+
+            let usage = cpuacct.usage().unwrap();
+            assert!(usage > 0);
+            assert_eq!(cpuacct.usage_percpu().unwrap().iter().sum::<u64>(), usage);
+            let stat = cpuacct.stat().unwrap();
+            assert!(stat.user + stat.system > 0);
+
+            cpuacct.reset().unwrap();
+            assert_eq!(cpuacct.usage().unwrap(), 0);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuacct_usage_includes_descendants() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuacct_usage_includes_descendants")).unwrap();
+    {
+        let cpuacct: Option<&CpuAcctController> = cg.controller_of();
+        if let Some(parent) = cpuacct {
+            let children = ["a", "b"]
+                .iter()
+                .map(|name| parent.nested(name).unwrap())
+                .collect::<Vec<_>>();
+            for child in &children {
+                child.create().unwrap();
+                let mut task = Command::new("sh")
+                    .arg("-c")
+                    .arg("read x; i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done")
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .unwrap();
+                child.add_task_by_tgid(&CgroupPid::from(&task)).unwrap();
+                task.stdin.take().unwrap().write_all(b"\n").unwrap();
+                assert!(task.wait().unwrap().success());
+                assert!(child.usage().unwrap() > 0);
+            }
+
+            // Nothing ran in the parent itself, its usage is that of its children.
+            let sum = children.iter().map(|c| c.usage().unwrap()).sum::<u64>();
+            assert_eq!(parent.usage().unwrap(), sum);
+
+            for child in &children {
+                child.delete().unwrap();
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
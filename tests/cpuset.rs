@@ -4,11 +4,12 @@
 // SPDX-License-Identifier: Apache-2.0 or MIT
 //
 
-use cgroups_rs::cpuset::CpuSetController;
+use cgroups_rs::cpuset::{CpuSetController, PartitionType, RelaxDomainLevel};
 use cgroups_rs::error::ErrorKind;
-use cgroups_rs::{Cgroup, CgroupPid};
+use cgroups_rs::{Cgroup, CgroupPid, Controller};
 
 use std::fs;
+use std::path::PathBuf;
 
 #[test]
 fn test_cpuset_memory_pressure_root_cg() {
@@ -17,13 +18,35 @@ fn test_cpuset_memory_pressure_root_cg() {
     {
         let cpuset: &CpuSetController = cg.controller_of().unwrap();
 
+        assert!(!cpuset.is_root());
+
         // This is not a root control group, so it should fail via InvalidOperation.
-        let res = cpuset.set_enable_memory_pressure(true);
-        assert_eq!(res.unwrap_err().kind(), &ErrorKind::InvalidOperation);
+        let err = cpuset.set_enable_memory_pressure(true).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidOperation);
+        let flag = cpuset.path().join("cpuset.memory_pressure_enabled");
+        assert_eq!(err.path(), Some(flag.as_path()));
     }
     cg.delete().unwrap();
 }
 
+#[test]
+fn test_cpuset_checked() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_checked")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        if !cg.v2() {
+            let set = cpuset.cpuset_checked().unwrap();
+            assert_eq!(set.cpus, cpuset.cpuset().cpus);
+        }
+    }
+    cg.delete().unwrap();
+
+    // A control group that does not exist reports an error instead of defaults.
+    let missing = CpuSetController::new(PathBuf::from("/nonexistent/cgroups-rs"), false);
+    assert!(missing.cpuset_checked().is_err());
+}
+
 #[test]
 fn test_cpuset_set_cpus() {
     let h = cgroups_rs::hierarchies::auto();
@@ -90,3 +113,341 @@ fn test_cpuset_set_cpus_add_task() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_cpuset_cpus_list() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_cpus_list")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+
+        cpuset.set_cpus_from_list(&[0]).unwrap();
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![0]);
+
+        cpuset.set_mems_from_list(&[0]).unwrap();
+        assert_eq!(cpuset.mems_list().unwrap(), vec![0]);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_partition() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_partition")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        if cg.v2() {
+            assert_eq!(cpuset.partition().unwrap(), PartitionType::Member);
+
+            cpuset.set_cpus("0").unwrap();
+            cpuset.set_partition(PartitionType::Root).unwrap();
+            assert_eq!(cpuset.partition().unwrap(), PartitionType::Root);
+
+            cpuset.set_partition(PartitionType::Member).unwrap();
+        } else {
+            // cpuset.cpus.partition is not available on cgroup v1.
+            let err = cpuset.partition().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::NotSupported);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_clone_children() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_clone_children")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        if cg.v2() {
+            let err = cpuset.set_clone_children(true).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        } else {
+            cpuset.set_clone_children(true).unwrap();
+            assert!(cpuset.clone_children().unwrap());
+
+            // Create the child behind the controller's back, so that nothing copies the
+            // parent's cpus explicitly.
+            let child = cpuset.path().join("child");
+            fs::create_dir(&child).unwrap();
+            let cpus = fs::read_to_string(child.join("cpuset.cpus")).unwrap();
+            assert!(!cpus.trim().is_empty());
+            fs::remove_dir(&child).unwrap();
+
+            cpuset.set_clone_children(false).unwrap();
+            assert!(!cpuset.clone_children().unwrap());
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_watch_effective_cpus() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_watch_effective_cpus")).unwrap();
+    {
+        let parent: &CpuSetController = cg.controller_of().unwrap();
+        let cpus = parent.cpus_list().unwrap();
+        let child = parent.nested("child").unwrap();
+        child.create().unwrap();
+
+        let mut watcher = child.watch_effective_cpus().unwrap();
+        // Narrowing the CPUs needs at least two of them.
+        if cpus.len() >= 2 {
+            if cg.v2() {
+                parent.set_cpus_from_list(&cpus[..1]).unwrap();
+            } else {
+                // v1 refuses to take CPUs in use by a child away from its parent.
+                child.set_cpus_from_list(&cpus[..1]).unwrap();
+            }
+            assert_eq!(watcher.next().unwrap().unwrap(), cpus[..1].to_vec());
+        }
+
+        child.delete().unwrap();
+        assert!(watcher.next().is_none());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_fd_cache() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_fd_cache")).unwrap();
+    let cpuset: &CpuSetController = cg.controller_of().unwrap();
+    let uncached = cpuset.cpuset();
+
+    cpuset.set_fd_cache(true);
+    assert_eq!(cpuset.cpuset().cpus, uncached.cpus);
+    assert_eq!(cpuset.cpuset().mems, uncached.mems);
+
+    cpuset.set_cpus("0").unwrap();
+    assert_eq!(cpuset.cpus_list().unwrap(), vec![0]);
+    assert_eq!(cpuset.cpus_list().unwrap(), vec![0]);
+
+    cg.delete().unwrap();
+    assert!(cpuset.cpus_list().is_err());
+}
+
+#[test]
+fn test_cpuset_batched_matches_per_file() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_batched_matches_per_file")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        cpuset.set_cpus("0").unwrap();
+        let batched = cpuset.cpuset();
+
+        // With the descriptor cache enabled every file is opened on its own.
+        cpuset.set_fd_cache(true);
+        let per_file = cpuset.cpuset();
+        cpuset.set_fd_cache(false);
+
+        assert_eq!(batched.cpus, vec![(0, 0)]);
+        assert_eq!(batched.cpus, per_file.cpus);
+        assert_eq!(batched.mems, per_file.mems);
+        assert_eq!(batched.effective_cpus, per_file.effective_cpus);
+        assert_eq!(batched.effective_mems, per_file.effective_mems);
+        assert_eq!(batched.cpu_exclusive, per_file.cpu_exclusive);
+        assert_eq!(batched.mem_exclusive, per_file.mem_exclusive);
+        assert_eq!(batched.mem_hardwall, per_file.mem_hardwall);
+        assert_eq!(batched.memory_migrate, per_file.memory_migrate);
+        assert_eq!(batched.memory_pressure, per_file.memory_pressure);
+        assert_eq!(
+            batched.memory_pressure_enabled,
+            per_file.memory_pressure_enabled
+        );
+        assert_eq!(batched.memory_spread_page, per_file.memory_spread_page);
+        assert_eq!(batched.memory_spread_slab, per_file.memory_spread_slab);
+        assert_eq!(batched.sched_load_balance, per_file.sched_load_balance);
+        assert_eq!(
+            batched.sched_relax_domain_level,
+            per_file.sched_relax_domain_level
+        );
+
+        if !cg.v2() {
+            let checked = cpuset.cpuset_checked().unwrap();
+            assert_eq!(checked.cpus, batched.cpus);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_set_cpus_checked() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_set_cpus_checked")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        let parent = cpuset.path().parent().unwrap().to_path_buf();
+        let parent = CpuSetController::new(parent, cg.v2());
+        let allowed = parent.cpuset().effective_cpus;
+        let last = allowed.last().unwrap().1;
+
+        cpuset.set_cpus_checked(&last.to_string()).unwrap();
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![last as u32]);
+
+        let request = format!("{}-{}", last, last + 2);
+        let err = cpuset.set_cpus_checked(&request).unwrap_err();
+        match err.kind() {
+            ErrorKind::OutOfRange(file, missing) => {
+                assert!(file.ends_with("effective_cpus") || file.ends_with("cpus.effective"));
+                assert_eq!(missing, &format!("{}-{}", last + 1, last + 2));
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        // Nothing was written.
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![last as u32]);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_compare_read_back() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_compare_read_back")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        cpuset.set_cpus("0").unwrap();
+        let set = cpuset.cpuset();
+        assert_eq!(set, cpuset.cpuset());
+
+        let mut expected = set.clone();
+        expected.cpus = vec![(0, 0)];
+        assert_eq!(set, expected);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_relax_domain_level() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_relax_domain_level")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        // cpuset.sched_load_balance and cpuset.sched_relax_domain_level are v1 only.
+        if !cg.v2() {
+            // A new control group starts out with the system default, -1.
+            assert_eq!(
+                cpuset.cpuset().sched_relax_domain_level,
+                RelaxDomainLevel::SystemDefault
+            );
+
+            cpuset
+                .set_rebalance_relax_domain_level(RelaxDomainLevel::SameCore)
+                .unwrap();
+            assert_eq!(
+                cpuset.cpuset_checked().unwrap().sched_relax_domain_level,
+                RelaxDomainLevel::SameCore
+            );
+            let raw = fs::read_to_string(cpuset.path().join("cpuset.sched_relax_domain_level"));
+            assert_eq!(raw.unwrap().trim(), "1");
+
+            cpuset
+                .set_rebalance_relax_domain_level(RelaxDomainLevel::SystemDefault)
+                .unwrap();
+            assert_eq!(
+                cpuset.cpuset_checked().unwrap().sched_relax_domain_level,
+                RelaxDomainLevel::SystemDefault
+            );
+
+            cpuset.set_load_balancing(false).unwrap();
+            assert!(!cpuset.cpuset().sched_load_balance);
+            let raw = fs::read_to_string(cpuset.path().join("cpuset.sched_load_balance"));
+            assert_eq!(raw.unwrap().trim(), "0");
+            cpuset.set_load_balancing(true).unwrap();
+            assert!(cpuset.cpuset().sched_load_balance);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_configure() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_configure")).unwrap();
+    {
+        let parent: &CpuSetController = cg.controller_of().unwrap();
+        parent.configure("0", "0").unwrap();
+
+        // On cgroup v1 a fresh child starts out with empty cpus and mems. On v2 it would need
+        // the cpuset controller enabled in the parent's cgroup.subtree_control first.
+        let cpuset = if cg.v2() {
+            parent.clone()
+        } else {
+            let child = parent.nested("child").unwrap();
+            child.create().unwrap();
+            child
+        };
+        cpuset.configure("0", "0").unwrap();
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![0]);
+        assert_eq!(cpuset.mems_list().unwrap(), vec![0]);
+
+        let mut task = std::process::Command::new("sleep")
+            .arg("infinity")
+            .spawn()
+            .unwrap();
+        let pid = CgroupPid::from(&task);
+        cpuset.add_task_by_tgid(&pid).unwrap();
+        assert_eq!(cpuset.procs(), vec![pid]);
+        task.kill().unwrap();
+        task.wait().unwrap();
+
+        // Rejected before anything is written.
+        let err = cpuset.configure("0", "0-64").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::OutOfRange(..)));
+        assert_eq!(cpuset.mems_list().unwrap(), vec![0]);
+
+        if !cg.v2() {
+            cpuset.delete().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_migrate_mems() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_migrate_mems")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        cpuset.configure("0", "0").unwrap();
+        cpuset.migrate_mems("0").unwrap();
+        if !cg.v2() {
+            assert!(cpuset.cpuset().memory_migrate);
+        }
+    }
+
+    // Migrating between nodes needs at least two online memory nodes.
+    let nodes = cgroups_rs::cpuset::online_mems().unwrap();
+    if nodes.len() < 2 {
+        cg.delete().unwrap();
+        return;
+    }
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        let node = nodes.last().unwrap().to_string();
+        cpuset.migrate_mems(&node).unwrap();
+        assert_eq!(cpuset.mems_list().unwrap(), vec![*nodes.last().unwrap()]);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_cpuset_mounted() {
+    let cpuset = CpuSetController::mounted().unwrap();
+    assert!(cpuset.is_root());
+    assert_eq!(cpuset.relative_path(), PathBuf::new());
+}
+
+#[test]
+fn test_cpuset_cpus_mask() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_cpus_mask")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        cpuset.set_cpus_mask(0b1).unwrap();
+        assert_eq!(cpuset.cpus_mask().unwrap(), 0b1);
+        assert_eq!(cpuset.cpus_list().unwrap(), vec![0]);
+    }
+    cg.delete().unwrap();
+}
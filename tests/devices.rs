@@ -6,7 +6,7 @@
 
 //! Integration tests about the devices subsystem
 
-use cgroups_rs::devices::{DevicePermissions, DeviceType, DevicesController};
+use cgroups_rs::devices::{DevicePermissions, DeviceRule, DeviceType, DevicesController};
 use cgroups_rs::{Cgroup, DeviceResource};
 
 #[test]
@@ -71,3 +71,27 @@ fn test_devices_parsing() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_devices_rules() {
+    if cgroups_rs::hierarchies::is_cgroup2_unified_mode() {
+        return;
+    }
+
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_devices_rules")).unwrap();
+    {
+        let devices: &DevicesController = cg.controller_of().unwrap();
+
+        devices
+            .deny_rule(&"a *:* rwm".parse::<DeviceRule>().unwrap())
+            .unwrap();
+        assert_eq!(devices.rules().unwrap(), Vec::new());
+
+        // Allow access to /dev/null
+        let null = "c 1:3 rwm".parse::<DeviceRule>().unwrap();
+        devices.allow_rule(&null).unwrap();
+        assert_eq!(devices.rules().unwrap(), vec![null]);
+    }
+    cg.delete().unwrap();
+}
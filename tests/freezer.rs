@@ -0,0 +1,103 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the freezer subsystem
+use cgroups_rs::freezer::{FreezerController, FreezerState};
+use cgroups_rs::{Cgroup, CgroupPid};
+
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_freeze_and_thaw() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_freeze_and_thaw")).unwrap();
+    {
+        let freezer: &FreezerController = cg.controller_of().unwrap();
+        assert_eq!(freezer.state().unwrap(), FreezerState::Thawed);
+
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+
+        freezer.freeze().unwrap();
+        // freezing is asynchronous, the state goes through FREEZING on v1.
+        let mut state = freezer.state().unwrap();
+        for _ in 0..100 {
+            if state == FreezerState::Frozen {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+            state = freezer.state().unwrap();
+        }
+        assert_eq!(state, FreezerState::Frozen);
+
+        freezer.thaw().unwrap();
+        assert_eq!(freezer.state().unwrap(), FreezerState::Thawed);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_delete_recursive_frozen() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_delete_recursive_frozen")).unwrap();
+    let h = cgroups_rs::hierarchies::auto();
+    let leaf = Cgroup::new(h, String::from("test_delete_recursive_frozen/a/b")).unwrap();
+
+    let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+    leaf.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+
+    let freezer: &FreezerController = cg.controller_of().unwrap();
+    freezer.freeze().unwrap();
+
+    let paths = cg
+        .subsystems()
+        .iter()
+        .map(|sub| sub.to_controller().path().to_path_buf())
+        .collect::<Vec<_>>();
+    cg.delete_recursive().unwrap();
+    for path in paths {
+        assert!(!path.exists(), "{} still exists", path.display());
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn test_parent_freezing() {
+    let h = cgroups_rs::hierarchies::auto();
+    if h.v2() {
+        return;
+    }
+    let cg = Cgroup::new(h, String::from("test_parent_freezing")).unwrap();
+    let h = cgroups_rs::hierarchies::auto();
+    let leaf = Cgroup::new(h, String::from("test_parent_freezing/child")).unwrap();
+    {
+        let parent: &FreezerController = cg.controller_of().unwrap();
+        let child: &FreezerController = leaf.controller_of().unwrap();
+        assert!(!child.self_freezing().unwrap());
+        assert!(!child.parent_freezing().unwrap());
+
+        parent.freeze().unwrap();
+        assert!(parent.self_freezing().unwrap());
+        assert!(!parent.parent_freezing().unwrap());
+        assert!(!child.self_freezing().unwrap());
+        assert!(child.parent_freezing().unwrap());
+
+        // Thawing the child does not undo a freeze of its parent.
+        child.thaw().unwrap();
+        assert!(child.parent_freezing().unwrap());
+
+        parent.thaw().unwrap();
+        assert!(!child.parent_freezing().unwrap());
+    }
+    leaf.delete().unwrap();
+    cg.delete().unwrap();
+}
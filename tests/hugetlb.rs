@@ -42,3 +42,23 @@ fn test_hugetlb_sizes() {
 fn assert_no_error(r: Result<u64>) {
     assert!(r.is_ok())
 }
+
+#[test]
+fn test_hugetlb_discovered_sizes() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_hugetlb_discovered_sizes")).unwrap();
+    {
+        let hugetlb_controller: Option<&HugeTlbController> = cg.controller_of();
+        // skip if the host doesn't expose the hugetlb controller at all
+        if let Some(hugetlb_controller) = hugetlb_controller {
+            for size in hugetlb_controller.sizes() {
+                assert!(hugetlb_controller.size_supported(&size));
+                hugetlb_controller
+                    .set_limit_in_bytes(&size, 2 * 1024 * 1024 * 1024)
+                    .unwrap();
+                assert_no_error(hugetlb_controller.usage_in_bytes(&size));
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
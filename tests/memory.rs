@@ -3,10 +3,44 @@
 // SPDX-License-Identifier: Apache-2.0 or MIT
 //
 
-//! Integration tests about the hugetlb subsystem
+//! Integration tests about the memory subsystem
+use cgroups_rs::error::ErrorKind;
 use cgroups_rs::memory::{MemController, SetMemory};
 use cgroups_rs::Controller;
-use cgroups_rs::{Cgroup, MaxValue};
+use cgroups_rs::{Cgroup, CgroupPid, MaxValue};
+
+use std::fs;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// A file under the system temporary directory, unique to the test process and removed when
+/// dropped, even if the test panics.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("cgroups-rs-test_{}-{}", name, std::process::id()));
+        TempFile(path)
+    }
+}
+
+impl Deref for TempFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
 
 #[test]
 fn test_disable_oom_killer() {
@@ -48,6 +82,77 @@ fn set_kmem_limit_v1() {
     cg.delete().unwrap();
 }
 
+#[test]
+fn test_kmem_usage_in_bytes() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_kmem_usage_in_bytes")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        match mem_controller.kmem_usage_in_bytes() {
+            Ok(_) => {
+                assert!(!mem_controller.v2());
+                mem_controller.kmem_max_usage_in_bytes().unwrap();
+            }
+            Err(e) => {
+                assert_eq!(e.kind(), &ErrorKind::NotSupported);
+                let err = mem_controller.set_kmem_limit_in_bytes(1).unwrap_err();
+                assert_eq!(err.kind(), &ErrorKind::NotSupported);
+            }
+        }
+        match mem_controller.kmem_tcp_usage_in_bytes() {
+            Ok(_) => {
+                mem_controller.kmem_tcp_limit_in_bytes().unwrap();
+            }
+            Err(e) => assert_eq!(e.kind(), &ErrorKind::NotSupported),
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_write_permission_denied() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_write_permission_denied")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        let path = mem_controller.path().join(if mem_controller.v2() {
+            "memory.max"
+        } else {
+            "memory.limit_in_bytes"
+        });
+
+        // Drop the filesystem privileges of a single thread, as the file belongs to root.
+        let err = std::thread::scope(|s| {
+            s.spawn(|| {
+                unsafe { libc::setfsuid(65534) };
+                mem_controller.set_limit(1 << 30).unwrap_err()
+            })
+            .join()
+            .unwrap()
+        });
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::PermissionDenied(path.display().to_string())
+        );
+        assert!(err.to_string().contains("root privileges"));
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_oom_group() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_oom_group")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        match mem_controller.set_oom_group(true) {
+            Ok(()) => assert!(mem_controller.oom_group().unwrap()),
+            Err(e) => assert_eq!(e.kind(), &ErrorKind::NotSupported),
+        }
+    }
+    cg.delete().unwrap();
+}
+
 #[test]
 fn set_mem_v2() {
     let h = cgroups_rs::hierarchies::auto();
@@ -104,3 +209,247 @@ fn set_mem_v2() {
 
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_memory_usage_in_bytes() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_usage_in_bytes")).unwrap();
+    let file = TempFile::new("memory_usage_in_bytes");
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        assert_eq!(mem_controller.usage_in_bytes().unwrap(), 0);
+
+        // The child waits to be moved into the control group before writing 8M of page cache,
+        // which stays charged to the control group after it exits.
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "read x; dd if=/dev/zero of={} bs=1M count=8 2>/dev/null",
+                file.display()
+            ))
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+        child.stdin.take().unwrap().write_all(b"\n").unwrap();
+        assert!(child.wait().unwrap().success());
+
+        assert!(mem_controller.usage_in_bytes().unwrap() > 0);
+        // v1 reports page cache as `cache`, v2 as `file`.
+        let stat = mem_controller.stat().unwrap();
+        assert!(stat.cache > 0 || stat.raw.get("file").copied().unwrap_or(0) > 0);
+        if !mem_controller.v2() {
+            assert!(mem_controller.max_usage_in_bytes().unwrap() > 0);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_oom_control() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_oom_control")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            assert!(mem_controller.oom_control().is_err());
+        } else {
+            let oom = mem_controller.oom_control().unwrap();
+            assert!(!oom.oom_kill_disable);
+            assert!(!oom.under_oom);
+
+            mem_controller.set_oom_control(true).unwrap();
+            assert!(mem_controller.oom_control().unwrap().oom_kill_disable);
+
+            mem_controller.set_oom_control(false).unwrap();
+            assert!(!mem_controller.oom_control().unwrap().oom_kill_disable);
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_swappiness_and_memswap() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_swappiness_and_memswap")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            let err = mem_controller.set_swappiness(0).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        } else {
+            let err = mem_controller.set_swappiness(101).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::ParseError);
+
+            mem_controller.set_swappiness(0).unwrap();
+            assert_eq!(mem_controller.swappiness().unwrap(), 0);
+        }
+
+        // The memory+swap limit may not be lower than the memory limit.
+        mem_controller.set_limit(32 * 1024 * 1024).unwrap();
+        match mem_controller.set_memswap_limit(64 * 1024 * 1024) {
+            Err(e) if e.kind() == &ErrorKind::NotSupported => {
+                // swap accounting is disabled
+                let err = mem_controller.memswap_usage().unwrap_err();
+                assert_eq!(err.kind(), &ErrorKind::NotSupported);
+            }
+            r => {
+                r.unwrap();
+                assert_eq!(mem_controller.memswap().limit_in_bytes, 64 * 1024 * 1024);
+                assert_eq!(mem_controller.memswap_usage().unwrap(), 0);
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_memory_watermarks() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_watermarks")).unwrap();
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            mem_controller
+                .set_high(MaxValue::Value(16 * 1024 * 1024))
+                .unwrap();
+            mem_controller
+                .set_low(MaxValue::Value(4 * 1024 * 1024))
+                .unwrap();
+            mem_controller
+                .set_min(MaxValue::Value(1024 * 1024))
+                .unwrap();
+
+            let m = mem_controller.get_mem().unwrap();
+            assert_eq!(m.high, Some(MaxValue::Value(16 * 1024 * 1024)));
+            assert_eq!(m.low, Some(MaxValue::Value(4 * 1024 * 1024)));
+            assert_eq!(m.min, Some(MaxValue::Value(1024 * 1024)));
+
+            mem_controller.set_high(MaxValue::Max).unwrap();
+            assert_eq!(mem_controller.get_mem().unwrap().high, Some(MaxValue::Max));
+        } else {
+            let err = mem_controller.set_high(MaxValue::Max).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+
+            mem_controller.set_soft_limit(16 * 1024 * 1024).unwrap();
+            assert_eq!(
+                mem_controller.memory_stat().soft_limit_in_bytes,
+                16 * 1024 * 1024
+            );
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_memory_force_empty() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_force_empty")).unwrap();
+    let file = TempFile::new("memory_force_empty");
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "read x; dd if=/dev/zero of={} bs=1M count=8 2>/dev/null",
+                file.display()
+            ))
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+        child.stdin.take().unwrap().write_all(b"\n").unwrap();
+        assert!(child.wait().unwrap().success());
+
+        let before = mem_controller.usage_in_bytes().unwrap();
+        assert!(before > 0);
+        match mem_controller.force_empty() {
+            // memory.reclaim is missing before Linux 5.19
+            Err(e) if e.kind() == &ErrorKind::NotSupported => assert!(mem_controller.v2()),
+            r => {
+                r.unwrap();
+                assert!(mem_controller.usage_in_bytes().unwrap() < before);
+            }
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_memory_usage_threshold() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_usage_threshold")).unwrap();
+    let file = TempFile::new("memory_usage_threshold");
+    {
+        let mem_controller: &MemController = cg.controller_of().unwrap();
+        if mem_controller.v2() {
+            let err = mem_controller
+                .register_usage_threshold("threshold", 4 * 1024 * 1024)
+                .unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::CgroupVersion);
+        } else {
+            let rx = mem_controller
+                .register_usage_threshold("threshold", 4 * 1024 * 1024)
+                .unwrap();
+
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(format!(
+                    "read x; dd if=/dev/zero of={} bs=1M count=8 2>/dev/null",
+                    file.display()
+                ))
+                .stdin(Stdio::piped())
+                .spawn()
+                .unwrap();
+            cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+            child.stdin.take().unwrap().write_all(b"\n").unwrap();
+            assert!(child.wait().unwrap().success());
+
+            let key = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(key, "threshold");
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_reset_stats() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_reset_stats")).unwrap();
+    {
+        let memory: &MemController = cg.controller_of().unwrap();
+        if memory.v2() {
+            let err = memory.reset_stats().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::NotSupported);
+            cg.delete().unwrap();
+            return;
+        }
+
+        // Hold `size` bytes in a shell variable once the process is in the control group.
+        let allocate = |size: usize| {
+            let mut child = Command::new("sh")
+                .arg("-c")
+                .arg(format!(
+                    "read _; x=$(head -c {} /dev/zero | tr '\\0' a)",
+                    size
+                ))
+                .stdin(Stdio::piped())
+                .spawn()
+                .unwrap();
+            cg.add_task_by_tgid(CgroupPid::from(&child)).unwrap();
+            child.stdin.take().unwrap().write_all(b"\n").unwrap();
+            child.wait().unwrap();
+        };
+
+        allocate(32 << 20);
+        assert!(memory.max_usage_in_bytes().unwrap() >= 32 << 20);
+
+        memory.reset_stats().unwrap();
+        allocate(4 << 20);
+        let max_usage = memory.max_usage_in_bytes().unwrap();
+        assert!(max_usage >= 4 << 20, "{}", max_usage);
+        assert!(max_usage < 32 << 20, "{}", max_usage);
+    }
+    cg.delete().unwrap();
+}
@@ -0,0 +1,33 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests about the perf_event subsystem
+use cgroups_rs::perf_event::PerfEventController;
+use cgroups_rs::Controller;
+use cgroups_rs::{Cgroup, CgroupPid};
+
+use std::process::Command;
+
+#[test]
+fn test_perf_event_add_task() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_perf_event_add_task")).unwrap();
+    {
+        // skip if the host doesn't expose the perf_event controller
+        let perf_event: Option<&PerfEventController> = cg.controller_of();
+        if let Some(perf_event) = perf_event {
+            assert!(perf_event.exists());
+
+            let mut child = Command::new("sleep").arg("infinity").spawn().unwrap();
+            let pid = CgroupPid::from(&child);
+            perf_event.add_task_by_tgid(&pid).unwrap();
+            assert_eq!(perf_event.tasks(), vec![pid]);
+
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+    cg.delete().unwrap();
+}
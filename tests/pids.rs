@@ -7,13 +7,19 @@
 //! Integration tests about the pids subsystem
 use cgroups_rs::pid::PidController;
 use cgroups_rs::Controller;
-use cgroups_rs::{Cgroup, MaxValue};
+use cgroups_rs::{Cgroup, CgroupPid, MaxValue};
 
 use nix::sys::wait::{waitpid, WaitStatus};
 use nix::unistd::{fork, ForkResult};
 
 use libc::pid_t;
 
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
 #[test]
 fn create_and_delete_cgroup() {
     let h = cgroups_rs::hierarchies::auto();
@@ -101,3 +107,94 @@ fn test_pid_events_is_not_zero() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+fn test_add_task_by_tgid_moves_all_threads() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_add_task_by_tgid_moves_all_threads")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child, .. }) => {
+                // wait for the child to start its second thread
+                let task_dir = format!("/proc/{}/task", child);
+                let mut threads = Vec::new();
+                for _ in 0..100 {
+                    threads = fs::read_dir(&task_dir)
+                        .unwrap()
+                        .map(|e| e.unwrap().file_name().into_string().unwrap())
+                        .map(|tid| CgroupPid::from(tid.parse::<u64>().unwrap()))
+                        .collect::<Vec<_>>();
+                    if threads.len() == 2 {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                assert_eq!(threads.len(), 2);
+
+                pids.add_task_by_tgid(&(pid_t::from(child) as u64).into())
+                    .unwrap();
+
+                threads.sort();
+                let mut tasks = pids.tasks();
+                tasks.sort();
+                assert_eq!(tasks, threads);
+                assert_eq!(pids.procs(), vec![(pid_t::from(child) as u64).into()]);
+
+                unsafe { libc::kill(pid_t::from(child), libc::SIGKILL) };
+                waitpid(child, None).unwrap();
+            }
+            Ok(ForkResult::Child) => {
+                thread::spawn(|| loop {
+                    thread::sleep(Duration::from_secs(1));
+                });
+                loop {
+                    thread::sleep(Duration::from_secs(1));
+                }
+            }
+            Err(_) => panic!("failed to fork"),
+        }
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_pids_max_limits_forks() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_pids_max_limits_forks")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+        pids.set_pid_max(MaxValue::Value(5)).unwrap();
+
+        // Once moved into the cgroup, the shell tries to fork six children: together with the
+        // shell itself only four of them fit under the limit.
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("read x; for i in 1 2 3 4 5 6; do sleep 60 & done 2>/dev/null; read x")
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        pids.add_task_by_tgid(&CgroupPid::from(&child)).unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(b"\n").unwrap();
+
+        let mut events = 0;
+        for _ in 0..100 {
+            events = pids.get_pid_events().unwrap();
+            if events > 0 && pids.get_pid_current().unwrap() == 5 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pids.get_pid_current().unwrap(), 5);
+        assert!(events > 0);
+        assert!(pids.pid_events().unwrap().max >= events);
+
+        for pid in pids.procs() {
+            unsafe { libc::kill(pid.pid as pid_t, libc::SIGKILL) };
+        }
+        child.wait().unwrap();
+    }
+    cg.delete().unwrap();
+}
@@ -5,8 +5,10 @@
 //
 
 //! Integration test about setting resources using `apply()`
+use cgroups_rs::cpu::CpuController;
+use cgroups_rs::cpuset::CpuSetController;
 use cgroups_rs::pid::PidController;
-use cgroups_rs::{Cgroup, MaxValue, PidResources, Resources};
+use cgroups_rs::{Cgroup, CpuResources, MaxValue, MemoryResources, PidResources, Resources};
 
 #[test]
 fn pid_resources() {
@@ -29,3 +31,90 @@ fn pid_resources() {
     }
     cg.delete().unwrap();
 }
+
+#[test]
+fn cpu_resources() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("cpu_resources")).unwrap();
+    {
+        let res = Resources {
+            cpu: CpuResources {
+                cpus: Some("0".to_string()),
+                shares: Some(512),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cg.apply(&res).unwrap();
+
+        // verify that both the cpu and the cpuset controllers got written
+        let cpu: &CpuController = cg.controller_of().unwrap();
+        assert_eq!(cpu.shares().unwrap(), 512);
+
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+        assert_eq!(cpuset.cpuset().cpus, vec![(0, 0)]);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn apply_reports_errors() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("apply_reports_errors")).unwrap();
+    {
+        // The kernel rejects CPUs that do not exist.
+        let res = Resources {
+            cpu: CpuResources {
+                cpus: Some("100000".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(cg.apply(&res).is_err());
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn snapshot_resources() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("snapshot_resources")).unwrap();
+    {
+        let res = Resources {
+            cpu: CpuResources {
+                cpus: Some("0".to_string()),
+                mems: Some("0".to_string()),
+                shares: Some(512),
+                quota: Some(50000),
+                period: Some(100000),
+                ..Default::default()
+            },
+            memory: MemoryResources {
+                memory_hard_limit: Some(64 * 1024 * 1024),
+                ..Default::default()
+            },
+            pid: PidResources {
+                maximum_number_of_processes: Some(MaxValue::Value(512)),
+            },
+            ..Default::default()
+        };
+        cg.apply(&res).unwrap();
+
+        let snapshot = cg.snapshot();
+        assert_eq!(snapshot.cpu.cpus, res.cpu.cpus);
+        assert_eq!(snapshot.cpu.mems, res.cpu.mems);
+        assert_eq!(snapshot.cpu.shares, res.cpu.shares);
+        assert_eq!(snapshot.cpu.quota, res.cpu.quota);
+        assert_eq!(snapshot.cpu.period, res.cpu.period);
+        assert_eq!(
+            snapshot.memory.memory_hard_limit,
+            res.memory.memory_hard_limit
+        );
+        assert_eq!(snapshot.pid, res.pid);
+
+        // Applying the snapshot again is a no-op.
+        cg.apply(&snapshot).unwrap();
+        assert_eq!(cg.snapshot(), snapshot);
+    }
+    cg.delete().unwrap();
+}
@@ -0,0 +1,71 @@
+// Copyright (c) 2020 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0 or MIT
+//
+
+//! Integration tests for the async getters and setters behind the `tokio` feature.
+#![cfg(feature = "tokio")]
+
+use cgroups_rs::cpuset::CpuSetController;
+use cgroups_rs::memory::MemController;
+use cgroups_rs::pid::PidController;
+use cgroups_rs::{Cgroup, Controller, MaxValue};
+
+#[tokio::test]
+async fn test_cpuset_async() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_cpuset_async")).unwrap();
+    {
+        let cpuset: &CpuSetController = cg.controller_of().unwrap();
+
+        cpuset.set_cpus_async("0").await.unwrap();
+        let set = cpuset.cpuset_async().await;
+        assert_eq!(set.cpus, vec![(0, 0)]);
+        assert_eq!(set.cpus, cpuset.cpuset().cpus);
+    }
+    cg.delete().unwrap();
+}
+
+#[test]
+fn test_async_futures_are_send() {
+    fn assert_send<T: Send>(_: T) {}
+
+    let cpuset = CpuSetController::new("/sys/fs/cgroup/cpuset".into(), false);
+    assert_send(cpuset.cpuset_async());
+    assert_send(cpuset.set_cpus_async("0"));
+    let pids = PidController::new("/sys/fs/cgroup/pids".into(), false);
+    assert_send(pids.get_pid_max_async());
+}
+
+#[tokio::test]
+async fn test_pid_max_async() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_pid_max_async")).unwrap();
+    {
+        let pids: &PidController = cg.controller_of().unwrap();
+
+        pids.set_pid_max_async(MaxValue::Value(10)).await.unwrap();
+        assert_eq!(pids.get_pid_max_async().await.unwrap(), MaxValue::Value(10));
+        assert_eq!(pids.get_pid_max().unwrap(), MaxValue::Value(10));
+    }
+    cg.delete().unwrap();
+}
+
+#[tokio::test]
+async fn test_memory_async() {
+    let h = cgroups_rs::hierarchies::auto();
+    let cg = Cgroup::new(h, String::from("test_memory_async")).unwrap();
+    {
+        let mem: &MemController = cg.controller_of().unwrap();
+
+        if !mem.v2() {
+            mem.set_soft_limit_async(16 * 1024 * 1024).await.unwrap();
+            assert_eq!(mem.memory_stat().soft_limit_in_bytes, 16 * 1024 * 1024);
+        }
+        assert_eq!(
+            mem.usage_in_bytes_async().await.unwrap(),
+            mem.usage_in_bytes().unwrap()
+        );
+    }
+    cg.delete().unwrap();
+}